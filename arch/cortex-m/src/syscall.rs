@@ -96,6 +96,22 @@ impl kernel::syscall::UserspaceKernelBoundary for SysCall {
         write_volatile(sp, return_value);
     }
 
+    unsafe fn set_syscall_return(
+        &self,
+        stack_pointer: *const usize,
+        _state: &mut Self::StoredState,
+        return_value: kernel::syscall::SyscallReturn,
+    ) {
+        // r0-r3 are the first four words of the hardware stacked frame.
+        let sp = stack_pointer as *mut usize;
+        write_volatile(sp, return_value.return_code() as usize);
+        if let Some(values) = return_value.values() {
+            for (i, value) in values.iter().enumerate() {
+                write_volatile(sp.add(i + 1), *value);
+            }
+        }
+    }
+
     /// When the process calls `svc` to enter the kernel, the hardware
     /// automatically pushes a stack frame that will be unstacked when the
     /// kernel returns to the process. In the special case of process startup,
@@ -269,4 +285,41 @@ impl kernel::syscall::UserspaceKernelBoundary for SysCall {
             },
         ));
     }
+
+    unsafe fn store_fault_context(
+        &self,
+        stack_pointer: *const usize,
+        state: &CortexMStoredState,
+        context: &mut kernel::syscall::FaultContext,
+    ) {
+        // Registers in order R0-R12, SP, LR, PC, xPSR. R0-R3, R12, LR, PC and
+        // xPSR were stacked by the hardware, the rest are in the stored state.
+        for (i, offset) in [0, 1, 2, 3].iter().enumerate() {
+            context.registers[i] = read_volatile(stack_pointer.offset(*offset));
+        }
+        context.registers[4..12].copy_from_slice(&state.regs);
+        context.registers[12] = read_volatile(stack_pointer.offset(4));
+        context.registers[13] = stack_pointer as usize;
+        context.registers[14] = read_volatile(stack_pointer.offset(5));
+        context.registers[15] = read_volatile(stack_pointer.offset(6));
+        context.registers[16] = read_volatile(stack_pointer.offset(7));
+        context.num_registers = 17;
+
+        // The fault handler saved the fault status registers when the process
+        // faulted. They are stale if the kernel faulted the process itself.
+        if !context.hardware_fault {
+            return;
+        }
+        let cfsr = SCB_REGISTERS[1];
+        context.fault_reason = cfsr as usize;
+        context.fault_address = if cfsr & 0x80 == 0x80 {
+            // MMFARVALID
+            Some(SCB_REGISTERS[3] as usize)
+        } else if cfsr & 0x8000 == 0x8000 {
+            // BFARVALID
+            Some(SCB_REGISTERS[4] as usize)
+        } else {
+            None
+        };
+    }
 }
@@ -254,6 +254,14 @@ pub struct CortexMRegion {
     attributes: FieldValue<u32, RegionAttributes::Register>,
 }
 
+/// Whether regions with `permissions` are guard regions.
+fn is_guard(permissions: mpu::Permissions) -> bool {
+    match permissions {
+        mpu::Permissions::NoAccess => true,
+        _ => false,
+    }
+}
+
 impl CortexMRegion {
     fn new(
         logical_start: *const u8,
@@ -284,6 +292,10 @@ impl CortexMRegion {
             mpu::Permissions::ExecuteOnly => {
                 (RegionAttributes::AP::NoAccess, RegionAttributes::XN::Enable)
             }
+            mpu::Permissions::NoAccess => (
+                RegionAttributes::AP::PrivilegedOnly,
+                RegionAttributes::XN::Disable,
+            ),
         };
 
         // Base address register
@@ -395,10 +407,15 @@ impl kernel::mpu::MPU for MPU {
         permissions: mpu::Permissions,
         config: &mut Self::MpuConfig,
     ) -> Option<mpu::Region> {
-        // Check that no previously allocated regions overlap the unallocated memory.
-        for region in config.regions.iter() {
-            if region.overlaps(unallocated_memory_start, unallocated_memory_size) {
-                return None;
+        // Check that no previously allocated regions overlap the unallocated
+        // memory. Guard regions may overlap other regions, and take precedence
+        // over them because they always have a higher region number than the
+        // app memory region.
+        if !is_guard(permissions) {
+            for region in config.regions.iter() {
+                if region.overlaps(unallocated_memory_start, unallocated_memory_size) {
+                    return None;
+                }
             }
         }
 
@@ -514,6 +531,25 @@ impl kernel::mpu::MPU for MPU {
         Some(mpu::Region::new(start as *const u8, size))
     }
 
+    fn remove_memory_region(
+        &self,
+        region: mpu::Region,
+        config: &mut Self::MpuConfig,
+    ) -> Result<(), ()> {
+        let (number, _) = config
+            .regions
+            .iter()
+            .enumerate()
+            .filter(|(number, _)| *number != APP_MEMORY_REGION_NUM)
+            .find(|(_, r)| r.location() == Some((region.start_address(), region.size())))
+            .ok_or(())?;
+
+        config.regions[number] = CortexMRegion::empty(number);
+        config.is_dirty.set(true);
+
+        Ok(())
+    }
+
     fn allocate_app_memory_region(
         &self,
         unallocated_memory_start: *const u8,
@@ -20,6 +20,9 @@ extern "C" {
     // start).
     static _estack: usize;
 
+    // Where the bottom of the stack region is.
+    static mut _sstack: usize;
+
     // Boundaries of the .bss section.
     static mut _szero: usize;
     static mut _ezero: usize;
@@ -82,10 +85,12 @@ pub extern "C" fn _start() {
 
 /// Setup memory for the kernel.
 ///
-/// This moves the data segment from flash to RAM and zeros out the BSS section.
+/// This moves the data segment from flash to RAM, zeros out the BSS section
+/// and paints the stack so its high-water mark can be measured.
 pub unsafe fn init_memory() {
     tock_rt0::init_data(&mut _etext, &mut _srelocate, &mut _erelocate);
     tock_rt0::zero_bss(&mut _szero, &mut _ezero);
+    kernel::introspection::paint_stack(&mut _sstack, &_estack as *const usize as *mut usize);
 }
 
 /// The various privilege levels in RISC-V.
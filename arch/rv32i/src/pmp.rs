@@ -120,6 +120,9 @@ impl PMPRegion {
             mpu::Permissions::ExecuteOnly => {
                 pmpcfg::r::CLEAR + pmpcfg::w::CLEAR + pmpcfg::x::SET + pmpcfg::a::TOR
             }
+            mpu::Permissions::NoAccess => {
+                pmpcfg::r::CLEAR + pmpcfg::w::CLEAR + pmpcfg::x::CLEAR + pmpcfg::a::TOR
+            }
         };
 
         PMPRegion {
@@ -132,6 +135,12 @@ impl PMPRegion {
         self.location
     }
 
+    /// Whether this is a guard region, which denies all access.
+    fn is_guard(&self) -> bool {
+        self.cfg.value & (pmpcfg::r::SET.value | pmpcfg::w::SET.value | pmpcfg::x::SET.value)
+            == 0
+    }
+
     fn overlaps(&self, other_start: *const u8, other_size: usize) -> bool {
         let other_start = other_start as usize;
         let other_end = other_start + other_size;
@@ -212,17 +221,12 @@ impl PMPConfig {
             None
         };
 
-        // Sort the regions
-        self.regions.sort_unstable_by(|a, b| {
-            let (a_start, _a_size) = match a {
-                Some(region) => (region.location().0 as usize, region.location().1),
-                None => (0xFFFF_FFFF, 0xFFFF_FFFF),
-            };
-            let (b_start, _b_size) = match b {
-                Some(region) => (region.location().0 as usize, region.location().1),
-                None => (0xFFFF_FFFF, 0xFFFF_FFFF),
-            };
-            a_start.cmp(&b_start)
+        // Sort the regions. Guard regions come first, because the PMP entry
+        // with the lowest number that matches an address decides the access.
+        self.regions.sort_unstable_by_key(|region| match region {
+            Some(region) if region.is_guard() => (0, region.location().0 as usize),
+            Some(region) => (1, region.location().0 as usize),
+            None => (2, 0xFFFF_FFFF),
         });
 
         // Update the app region after the sort
@@ -230,7 +234,7 @@ impl PMPConfig {
             for (i, region) in self.regions.iter().enumerate() {
                 match region {
                     Some(reg) => {
-                        if reg.location.0 == app_addres.unwrap() {
+                        if reg.location.0 == app_addres.unwrap() && !reg.is_guard() {
                             self.app_memory_region.set(i);
                         }
                     }
@@ -320,8 +324,14 @@ impl kernel::mpu::MPU for PMP {
         permissions: mpu::Permissions,
         config: &mut Self::MpuConfig,
     ) -> Option<mpu::Region> {
+        // Guard regions may overlap other regions, and are sorted before them
+        // so that they take precedence.
+        let guard = match permissions {
+            mpu::Permissions::NoAccess => true,
+            _ => false,
+        };
         for region in config.regions.iter() {
-            if region.is_some() {
+            if region.is_some() && !guard {
                 if region
                     .unwrap()
                     .overlaps(unallocated_memory_start, unallocated_memory_size)
@@ -362,6 +372,31 @@ impl kernel::mpu::MPU for PMP {
         Some(mpu::Region::new(start as *const u8, size))
     }
 
+    fn remove_memory_region(
+        &self,
+        region: mpu::Region,
+        config: &mut Self::MpuConfig,
+    ) -> Result<(), ()> {
+        let (number, _) = config
+            .regions
+            .iter()
+            .enumerate()
+            .filter(|(number, _)| !config.app_memory_region.contains(number))
+            .find(|(_, r)| {
+                r.map_or(false, |r| {
+                    r.location == (region.start_address(), region.size())
+                })
+            })
+            .ok_or(())?;
+
+        config.regions[number] = None;
+        config.is_dirty.set(true);
+
+        config.sort_regions();
+
+        Ok(())
+    }
+
     fn allocate_app_memory_region(
         &self,
         unallocated_memory_start: *const u8,
@@ -510,7 +545,18 @@ impl kernel::mpu::MPU for PMP {
                             _ => break,
                         }
                     }
-                    None => {}
+                    None => {
+                        // Clear both entries of unused regions, which may
+                        // still hold a region of another process or a
+                        // region that was removed.
+                        match x % 2 {
+                            0 => csr::CSR.pmpcfg[x / 2].set(csr::CSR.pmpcfg[x / 2].get() & !0xFFFF),
+                            1 => csr::CSR.pmpcfg[x / 2].set(csr::CSR.pmpcfg[x / 2].get() & 0xFFFF),
+                            _ => break,
+                        }
+                        csr::CSR.pmpaddr[x * 2].set(0x0);
+                        csr::CSR.pmpaddr[(x * 2) + 1].set(0x0);
+                    }
                 };
             }
             config.is_dirty.set(false);
@@ -83,6 +83,20 @@ impl kernel::syscall::UserspaceKernelBoundary for SysCall {
         state.regs[R_A0] = return_value as usize; // a0 = return value
     }
 
+    unsafe fn set_syscall_return(
+        &self,
+        _stack_pointer: *const usize,
+        state: &mut Self::StoredState,
+        return_value: kernel::syscall::SyscallReturn,
+    ) {
+        state.regs[R_A0] = return_value.return_code() as usize;
+        if let Some(values) = return_value.values() {
+            state.regs[R_A1] = values[0];
+            state.regs[R_A2] = values[1];
+            state.regs[R_A3] = values[2];
+        }
+    }
+
     unsafe fn set_process_function(
         &self,
         stack_pointer: *const usize,
@@ -441,4 +455,22 @@ impl kernel::syscall::UserspaceKernelBoundary for SysCall {
             state.mtval,
         ));
     }
+
+    unsafe fn store_fault_context(
+        &self,
+        _stack_pointer: *const usize,
+        state: &RiscvimacStoredState,
+        context: &mut kernel::syscall::FaultContext,
+    ) {
+        // Registers x1-x31 followed by the PC.
+        context.registers[..31].copy_from_slice(&state.regs);
+        context.registers[31] = state.pc;
+        context.num_registers = 32;
+        // mcause and mtval describe the last trap, which is not a fault if
+        // the kernel faulted the process itself.
+        if context.hardware_fault {
+            context.fault_reason = state.mcause;
+            context.fault_address = Some(state.mtval);
+        }
+    }
 }
@@ -114,7 +114,7 @@ pub unsafe fn reset_handler() {
     let main_loop_capability = create_capability!(capabilities::MainLoopCapability);
     let memory_allocation_capability = create_capability!(capabilities::MemoryAllocationCapability);
 
-    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
+    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&mut PROCESSES));
 
     let dynamic_deferred_call_clients =
         static_init!([DynamicDeferredCallClientState; 2], Default::default());
@@ -483,6 +483,8 @@ pub unsafe fn reset_handler() {
         static _eappmem: u8;
     }
 
+    board_kernel.set_kernel_stack(&STACK_MEMORY, &process_management_capability);
+
     kernel::procs::load_processes(
         board_kernel,
         chip,
@@ -494,8 +496,8 @@ pub unsafe fn reset_handler() {
             &mut _sappmem as *mut u8,
             &_eappmem as *const u8 as usize - &_sappmem as *const u8 as usize,
         ),
-        &mut PROCESSES,
         FAULT_RESPONSE,
+        &kernel::procs::NoCredentialsCheck {},
         &process_management_capability,
     )
     .unwrap_or_else(|err| {
@@ -503,8 +505,10 @@ pub unsafe fn reset_handler() {
         debug!("{:?}", err);
     });
 
-    let scheduler = components::sched::round_robin::RoundRobinComponent::new(&PROCESSES)
-        .finalize(components::rr_component_helper!(NUM_PROCS));
+    let scheduler = components::sched::round_robin::RoundRobinComponent::new(
+        board_kernel.process_slots(&process_management_capability),
+    )
+    .finalize(components::rr_component_helper!(NUM_PROCS));
     board_kernel.kernel_loop(
         &platform,
         chip,
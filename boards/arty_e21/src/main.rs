@@ -91,7 +91,7 @@ pub unsafe fn reset_handler() {
     let process_mgmt_cap = create_capability!(capabilities::ProcessManagementCapability);
     let main_loop_cap = create_capability!(capabilities::MainLoopCapability);
 
-    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
+    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&mut PROCESSES));
 
     let dynamic_deferred_call_clients =
         static_init!([DynamicDeferredCallClientState; 2], Default::default());
@@ -226,6 +226,8 @@ pub unsafe fn reset_handler() {
         static _eappmem: u8;
     }
 
+    board_kernel.set_kernel_stack(&STACK_MEMORY, &process_mgmt_cap);
+
     kernel::procs::load_processes(
         board_kernel,
         chip,
@@ -237,8 +239,8 @@ pub unsafe fn reset_handler() {
             &mut _sappmem as *mut u8,
             &_eappmem as *const u8 as usize - &_sappmem as *const u8 as usize,
         ),
-        &mut PROCESSES,
         FAULT_RESPONSE,
+        &kernel::procs::NoCredentialsCheck {},
         &process_mgmt_cap,
     )
     .unwrap_or_else(|err| {
@@ -5,7 +5,8 @@
 //! Usage
 //! -----
 //! ```rust
-//! let scheduler = components::cooperative::CooperativeComponent::new(&PROCESSES)
+//! let processes = board_kernel.process_slots(&process_management_capability);
+//! let scheduler = components::cooperative::CooperativeComponent::new(processes)
 //!     .finalize(components::coop_component_helper!(NUM_PROCS));
 //! ```
 
@@ -13,7 +14,7 @@
 
 use core::mem::MaybeUninit;
 use kernel::component::Component;
-use kernel::procs::ProcessType;
+use kernel::procs::ProcessSlot;
 use kernel::{static_init, static_init_half};
 use kernel::{CoopProcessNode, CooperativeSched};
 
@@ -29,11 +30,11 @@ macro_rules! coop_component_helper {
 }
 
 pub struct CooperativeComponent {
-    processes: &'static [Option<&'static dyn ProcessType>],
+    processes: &'static [ProcessSlot],
 }
 
 impl CooperativeComponent {
-    pub fn new(processes: &'static [Option<&'static dyn ProcessType>]) -> CooperativeComponent {
+    pub fn new(processes: &'static [ProcessSlot]) -> CooperativeComponent {
         CooperativeComponent { processes }
     }
 }
@@ -40,11 +40,21 @@ static mut PROCESSES: [Option<&'static dyn kernel::procs::ProcessType>; NUM_PROC
     [None; NUM_PROCS];
 static mut CHIP: Option<&'static sam4l::chip::Sam4l> = None;
 
+// Flash for the log of process faults. This is what `storage_volume!` creates,
+// without making the volume public.
+#[link_section = ".storage"]
+#[used]
+static FAULT_LOG: [u8; 4096] = [0; 4096];
+
 /// Dummy buffer that causes the linker to reserve enough space for the stack.
 #[no_mangle]
 #[link_section = ".stack_buffer"]
 pub static mut STACK_MEMORY: [u8; 0x1000] = [0; 0x1000];
 
+/// Capability for the process manager, which keeps its own copy.
+struct ProcessMgmtCap;
+unsafe impl capabilities::ProcessManagementCapability for ProcessMgmtCap {}
+
 /// A structure representing this platform that holds references to all
 /// capsules for this platform.
 struct Hail {
@@ -68,8 +78,10 @@ struct Hail {
     button: &'static capsules::button::Button<'static, sam4l::gpio::GPIOPin<'static>>,
     rng: &'static capsules::rng::RngDriver<'static>,
     ipc: kernel::ipc::IPC,
+    mailbox: kernel::ipc::Mailbox,
     crc: &'static capsules::crc::Crc<'static, sam4l::crccu::Crccu<'static>>,
     dac: &'static capsules::dac::Dac<'static>,
+    process_manager: &'static capsules::process_manager::ProcessManager<ProcessMgmtCap>,
 }
 
 /// Mapping of integer syscalls to objects that implement syscalls.
@@ -99,7 +111,10 @@ impl Platform for Hail {
 
             capsules::dac::DRIVER_NUM => f(Some(self.dac)),
 
+            capsules::process_manager::DRIVER_NUM => f(Some(self.process_manager)),
+
             kernel::ipc::DRIVER_NUM => f(Some(&self.ipc)),
+            kernel::ipc::MAILBOX_DRIVER_NUM => f(Some(&self.mailbox)),
             _ => f(None),
         }
     }
@@ -187,7 +202,7 @@ pub unsafe fn reset_handler() {
 
     set_pin_primary_functions();
 
-    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
+    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&mut PROCESSES));
 
     // Create capabilities that the board needs to call certain protected kernel
     // functions.
@@ -207,7 +222,7 @@ pub unsafe fn reset_handler() {
     CHIP = Some(chip);
 
     let dynamic_deferred_call_clients =
-        static_init!([DynamicDeferredCallClientState; 2], Default::default());
+        static_init!([DynamicDeferredCallClientState; 3], Default::default());
     let dynamic_deferred_caller = static_init!(
         DynamicDeferredCall,
         DynamicDeferredCall::new(dynamic_deferred_call_clients)
@@ -408,6 +423,58 @@ pub unsafe fn reset_handler() {
     );
     let fault_response = kernel::procs::FaultResponse::Restart(restart_policy);
 
+    // Keep a record of process faults in flash, so that they can be read back
+    // after a reboot.
+    sam4l::flashcalw::FLASH_CONTROLLER.configure();
+    let fault_log_pagebuffer = static_init!(
+        sam4l::flashcalw::Sam4lPage,
+        sam4l::flashcalw::Sam4lPage::default()
+    );
+    let fault_log_storage = static_init!(
+        capsules::log::Log<'static, sam4l::flashcalw::FLASHCALW>,
+        capsules::log::Log::new(
+            &FAULT_LOG,
+            &sam4l::flashcalw::FLASH_CONTROLLER,
+            fault_log_pagebuffer,
+            dynamic_deferred_caller,
+            true
+        )
+    );
+    kernel::hil::flash::HasClient::set_client(
+        &sam4l::flashcalw::FLASH_CONTROLLER,
+        fault_log_storage,
+    );
+    fault_log_storage.initialize_callback_handle(
+        dynamic_deferred_caller
+            .register(fault_log_storage)
+            .expect("no deferred call slot available for the fault log"),
+    );
+    let fault_log = static_init!(
+        capsules::process_fault_log::ProcessFaultLog<
+            'static,
+            capsules::log::Log<'static, sam4l::flashcalw::FLASHCALW>,
+        >,
+        capsules::process_fault_log::ProcessFaultLog::new(
+            fault_log_storage,
+            &mut capsules::process_fault_log::BUFFER
+        )
+    );
+    kernel::hil::log::LogWrite::set_append_client(fault_log_storage, fault_log);
+    board_kernel.add_fault_handler(fault_log, &process_management_capability);
+
+    // Let a signed app named "supervisor" manage the other processes. Hail
+    // loads apps without checking their credentials, so until it does no app
+    // can use this.
+    let process_manager = static_init!(
+        capsules::process_manager::ProcessManager<ProcessMgmtCap>,
+        capsules::process_manager::ProcessManager::new(
+            board_kernel,
+            &["supervisor"],
+            board_kernel.create_grant(&memory_allocation_capability),
+            ProcessMgmtCap
+        )
+    );
+
     let hail = Hail {
         console: console,
         gpio: gpio,
@@ -423,8 +490,10 @@ pub unsafe fn reset_handler() {
         button: button,
         rng: rng,
         ipc: kernel::ipc::IPC::new(board_kernel, &memory_allocation_capability),
+        mailbox: kernel::ipc::Mailbox::new(board_kernel, &memory_allocation_capability),
         crc: crc,
         dac: dac,
+        process_manager: process_manager,
     };
 
     // Setup the UART bus for nRF51 serialization..
@@ -449,6 +518,8 @@ pub unsafe fn reset_handler() {
         static _eappmem: u8;
     }
 
+    board_kernel.set_kernel_stack(&STACK_MEMORY, &process_management_capability);
+
     kernel::procs::load_processes(
         board_kernel,
         chip,
@@ -460,8 +531,8 @@ pub unsafe fn reset_handler() {
             &mut _sappmem as *mut u8,
             &_eappmem as *const u8 as usize - &_sappmem as *const u8 as usize,
         ),
-        &mut PROCESSES,
         fault_response,
+        &kernel::procs::NoCredentialsCheck {},
         &process_management_capability,
     )
     .unwrap_or_else(|err| {
@@ -469,8 +540,10 @@ pub unsafe fn reset_handler() {
         debug!("{:?}", err);
     });
 
-    let scheduler = components::sched::round_robin::RoundRobinComponent::new(&PROCESSES)
-        .finalize(components::rr_component_helper!(NUM_PROCS));
+    let scheduler = components::sched::round_robin::RoundRobinComponent::new(
+        board_kernel.process_slots(&process_management_capability),
+    )
+    .finalize(components::rr_component_helper!(NUM_PROCS));
     board_kernel.kernel_loop(
         &hail,
         chip,
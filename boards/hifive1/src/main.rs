@@ -99,7 +99,7 @@ pub unsafe fn reset_handler() {
 
     let main_loop_cap = create_capability!(capabilities::MainLoopCapability);
 
-    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
+    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&mut PROCESSES));
 
     let dynamic_deferred_call_clients =
         static_init!([DynamicDeferredCallClientState; 2], Default::default());
@@ -220,6 +220,8 @@ pub unsafe fn reset_handler() {
         led,
     };
 
+    board_kernel.set_kernel_stack(&STACK_MEMORY, &process_mgmt_cap);
+
     kernel::procs::load_processes(
         board_kernel,
         chip,
@@ -231,8 +233,8 @@ pub unsafe fn reset_handler() {
             &mut _sappmem as *mut u8,
             &_eappmem as *const u8 as usize - &_sappmem as *const u8 as usize,
         ),
-        &mut PROCESSES,
         FAULT_RESPONSE,
+        &kernel::procs::NoCredentialsCheck {},
         &process_mgmt_cap,
     )
     .unwrap_or_else(|err| {
@@ -240,7 +242,9 @@ pub unsafe fn reset_handler() {
         debug!("{:?}", err);
     });
 
-    let scheduler = components::sched::cooperative::CooperativeComponent::new(&PROCESSES)
-        .finalize(components::coop_component_helper!(NUM_PROCS));
+    let scheduler = components::sched::cooperative::CooperativeComponent::new(
+        board_kernel.process_slots(&process_mgmt_cap),
+    )
+    .finalize(components::coop_component_helper!(NUM_PROCS));
     board_kernel.kernel_loop(&hifive1, chip, None, scheduler, &main_loop_cap);
 }
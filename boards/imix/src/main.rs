@@ -274,7 +274,7 @@ pub unsafe fn reset_handler() {
         trng: true,
     });
 
-    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
+    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&mut PROCESSES));
 
     let dynamic_deferred_call_clients =
         static_init!([DynamicDeferredCallClientState; 2], Default::default());
@@ -550,6 +550,8 @@ pub unsafe fn reset_handler() {
         static _eappmem: u8;
     }
 
+    board_kernel.set_kernel_stack(&STACK_MEMORY, &process_mgmt_cap);
+
     kernel::procs::load_processes(
         board_kernel,
         chip,
@@ -561,8 +563,8 @@ pub unsafe fn reset_handler() {
             &mut _sappmem as *mut u8,
             &_eappmem as *const u8 as usize - &_sappmem as *const u8 as usize,
         ),
-        &mut PROCESSES,
         FAULT_RESPONSE,
+        &kernel::procs::NoCredentialsCheck {},
         &process_mgmt_cap,
     )
     .unwrap_or_else(|err| {
@@ -570,7 +572,9 @@ pub unsafe fn reset_handler() {
         debug!("{:?}", err);
     });
 
-    let scheduler = components::sched::round_robin::RoundRobinComponent::new(&PROCESSES)
-        .finalize(components::rr_component_helper!(NUM_PROCS));
+    let scheduler = components::sched::round_robin::RoundRobinComponent::new(
+        board_kernel.process_slots(&process_mgmt_cap),
+    )
+    .finalize(components::rr_component_helper!(NUM_PROCS));
     board_kernel.kernel_loop(&imix, chip, Some(&imix.ipc), scheduler, &main_cap);
 }
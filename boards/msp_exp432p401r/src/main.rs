@@ -118,7 +118,7 @@ pub unsafe fn reset_handler() {
     msp432::gpio::PINS[msp432::gpio::PinNr::P01_2 as usize].enable_primary_function();
     msp432::gpio::PINS[msp432::gpio::PinNr::P01_3 as usize].enable_primary_function();
 
-    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
+    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&mut PROCESSES));
     let chip = static_init!(msp432::chip::Msp432, msp432::chip::Msp432::new());
     CHIP = Some(chip);
 
@@ -211,6 +211,8 @@ pub unsafe fn reset_handler() {
         static _eappmem: u8;
     }
 
+    board_kernel.set_kernel_stack(&STACK_MEMORY, &process_management_capability);
+
     kernel::procs::load_processes(
         board_kernel,
         chip,
@@ -222,14 +224,16 @@ pub unsafe fn reset_handler() {
             &mut _sappmem as *mut u8,
             &_eappmem as *const u8 as usize - &_sappmem as *const u8 as usize,
         ),
-        &mut PROCESSES,
         FAULT_RESPONSE,
+        &kernel::procs::NoCredentialsCheck {},
         &process_management_capability,
     )
     .unwrap();
 
-    let scheduler = components::sched::round_robin::RoundRobinComponent::new(&PROCESSES)
-        .finalize(components::rr_component_helper!(NUM_PROCS));
+    let scheduler = components::sched::round_robin::RoundRobinComponent::new(
+        board_kernel.process_slots(&process_management_capability),
+    )
+    .finalize(components::rr_component_helper!(NUM_PROCS));
     board_kernel.kernel_loop(
         &msp_exp432p4014,
         chip,
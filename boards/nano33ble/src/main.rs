@@ -104,7 +104,7 @@ pub unsafe fn reset_handler() {
     // Loads relocations and clears BSS
     nrf52840::init();
 
-    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
+    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&mut PROCESSES));
 
     //--------------------------------------------------------------------------
     // CAPABILITIES
@@ -290,6 +290,8 @@ pub unsafe fn reset_handler() {
         static _eappmem: u8;
     }
 
+    board_kernel.set_kernel_stack(&STACK_MEMORY, &process_management_capability);
+
     kernel::procs::load_processes(
         board_kernel,
         chip,
@@ -301,8 +303,8 @@ pub unsafe fn reset_handler() {
             &mut _sappmem as *mut u8,
             &_eappmem as *const u8 as usize - &_sappmem as *const u8 as usize,
         ),
-        &mut PROCESSES,
         FAULT_RESPONSE,
+        &kernel::procs::NoCredentialsCheck {},
         &process_management_capability,
     )
     .unwrap_or_else(|err| {
@@ -310,8 +312,10 @@ pub unsafe fn reset_handler() {
         debug!("{:?}", err);
     });
 
-    let scheduler = components::sched::round_robin::RoundRobinComponent::new(&PROCESSES)
-        .finalize(components::rr_component_helper!(NUM_PROCS));
+    let scheduler = components::sched::round_robin::RoundRobinComponent::new(
+        board_kernel.process_slots(&process_management_capability),
+    )
+    .finalize(components::rr_component_helper!(NUM_PROCS));
     board_kernel.kernel_loop(
         &platform,
         chip,
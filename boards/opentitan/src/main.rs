@@ -108,7 +108,7 @@ pub unsafe fn reset_handler() {
 
     let main_loop_cap = create_capability!(capabilities::MainLoopCapability);
 
-    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
+    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&mut PROCESSES));
 
     let dynamic_deferred_call_clients =
         static_init!([DynamicDeferredCallClientState; 1], Default::default());
@@ -320,6 +320,8 @@ pub unsafe fn reset_handler() {
         i2c_master,
     };
 
+    board_kernel.set_kernel_stack(&STACK_MEMORY, &process_mgmt_cap);
+
     kernel::procs::load_processes(
         board_kernel,
         chip,
@@ -331,8 +333,8 @@ pub unsafe fn reset_handler() {
             &mut _sappmem as *mut u8,
             &_eappmem as *const u8 as usize - &_sappmem as *const u8 as usize,
         ),
-        &mut PROCESSES,
         FAULT_RESPONSE,
+        &kernel::procs::NoCredentialsCheck {},
         &process_mgmt_cap,
     )
     .unwrap_or_else(|err| {
@@ -100,7 +100,7 @@ pub unsafe fn reset_handler() {
     );
     DynamicDeferredCall::set_global_instance(dynamic_deferred_caller);
 
-    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
+    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&mut PROCESSES));
 
     // Power up components
     apollo3::pwrctrl::PWRCTRL.enable_uart0();
@@ -218,6 +218,8 @@ pub unsafe fn reset_handler() {
         ble_radio,
     };
 
+    board_kernel.set_kernel_stack(&STACK_MEMORY, &process_mgmt_cap);
+
     kernel::procs::load_processes(
         board_kernel,
         chip,
@@ -229,8 +231,8 @@ pub unsafe fn reset_handler() {
             &mut _sappmem as *mut u8,
             &_eappmem as *const u8 as usize - &_sappmem as *const u8 as usize,
         ),
-        &mut PROCESSES,
         FAULT_RESPONSE,
+        &kernel::procs::NoCredentialsCheck {},
         &process_mgmt_cap,
     )
     .unwrap_or_else(|err| {
@@ -238,7 +240,9 @@ pub unsafe fn reset_handler() {
         debug!("{:?}", err);
     });
 
-    let scheduler = components::sched::round_robin::RoundRobinComponent::new(&PROCESSES)
-        .finalize(components::rr_component_helper!(NUM_PROCS));
+    let scheduler = components::sched::round_robin::RoundRobinComponent::new(
+        board_kernel.process_slots(&process_mgmt_cap),
+    )
+    .finalize(components::rr_component_helper!(NUM_PROCS));
     board_kernel.kernel_loop(&artemis_nano, chip, None, scheduler, &main_loop_cap);
 }
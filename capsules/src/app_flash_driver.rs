@@ -90,7 +90,13 @@ impl<'a> AppFlash<'a> {
                                     *c = d[i];
                                 }
 
-                                self.driver.write(buffer, flash_address, length)
+                                match self.driver.write(buffer, flash_address, length) {
+                                    Ok(()) => ReturnCode::SUCCESS,
+                                    Err((error, buffer)) => {
+                                        self.buffer.replace(buffer);
+                                        error
+                                    }
+                                }
                             })
                         })
                 } else {
@@ -144,8 +150,13 @@ impl hil::nonvolatile_storage::NonvolatileStorageClient<'static> for AppFlash<'_
                                     *c = d[i];
                                 }
 
-                                self.driver.write(buffer, flash_address, length)
-                                    == ReturnCode::SUCCESS
+                                match self.driver.write(buffer, flash_address, length) {
+                                    Ok(()) => true,
+                                    Err((_, buffer)) => {
+                                        self.buffer.replace(buffer);
+                                        false
+                                    }
+                                }
                             }
                         })
                     })
@@ -36,11 +36,18 @@
 //! When the buffer has been written successfully, the buffer is released from
 //! the driver. Successive writes must call `allow` each time a buffer is to be
 //! written.
+//!
+//! Constant strings in flash can be written without copying them into RAM by
+//! sharing them with `readonly_allow` instead of `allow`, using the same allow
+//! number.
 
 use core::cmp;
 use kernel::common::cells::{OptionalCell, TakeCell};
 use kernel::hil::uart;
-use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+use kernel::{
+    AppId, AppSlice, Callback, Driver, Grant, ReadOnlyAppSlice, ReadableAppSlice, ReturnCode,
+    Shared,
+};
 
 /// Syscall driver number.
 use crate::driver;
@@ -49,7 +56,7 @@ pub const DRIVER_NUM: usize = driver::NUM::Console as usize;
 #[derive(Default)]
 pub struct App {
     write_callback: Option<Callback>,
-    write_buffer: Option<AppSlice<Shared, u8>>,
+    write_buffer: Option<ReadableAppSlice<u8>>,
     write_len: usize,
     write_remaining: usize, // How many bytes didn't fit in the buffer and still need to be printed.
     pending_write: bool,
@@ -118,7 +125,7 @@ impl<'a> Console<'a> {
 
     /// Internal helper function for sending data for an existing transaction.
     /// Cannot fail. If can't send now, it will schedule for sending later.
-    fn send(&self, app_id: AppId, app: &mut App, slice: AppSlice<Shared, u8>) {
+    fn send(&self, app_id: AppId, app: &mut App, slice: ReadableAppSlice<u8>) {
         if self.tx_in_progress.is_none() {
             self.tx_in_progress.set(app_id);
             self.tx_buffer.take().map(|buffer| {
@@ -201,7 +208,7 @@ impl Driver for Console<'_> {
             1 => self
                 .apps
                 .enter(appid, |app, _| {
-                    app.write_buffer = slice;
+                    app.write_buffer = slice.map(ReadableAppSlice::from);
                     ReturnCode::SUCCESS
                 })
                 .unwrap_or_else(|err| err.into()),
@@ -216,6 +223,29 @@ impl Driver for Console<'_> {
         }
     }
 
+    /// Setup read-only shared buffers.
+    ///
+    /// ### `allow_num`
+    ///
+    /// - `1`: Read-only buffer for write buffer, which may be in flash
+    fn allow_readonly(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<ReadOnlyAppSlice<u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            1 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.write_buffer = slice.map(ReadableAppSlice::from);
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
     /// Setup callbacks.
     ///
     /// ### `subscribe_num`
@@ -249,8 +279,8 @@ impl Driver for Console<'_> {
     /// ### `command_num`
     ///
     /// - `0`: Driver check.
-    /// - `1`: Transmits a buffer passed via `allow`, up to the length
-    ///        passed in `arg1`
+    /// - `1`: Transmits a buffer passed via `allow` or `allow_readonly`, up
+    ///        to the length passed in `arg1`
     /// - `2`: Receives into a buffer passed via `allow`, up to the length
     ///        passed in `arg1`
     /// - `3`: Cancel any in progress receives and return (via callback)
@@ -19,6 +19,7 @@ pub enum NUM {
 
     // Kernel
     Ipc                   = 0x10000,
+    IpcMailbox            = 0x10001,
 
     // HW Buses
     Spi                   = 0x20001,
@@ -69,6 +70,8 @@ pub enum NUM {
     // Misc
     Buzzer                = 0x90000,
     Screen                = 0x90001,
-    Touch                 = 0x90002
+    Touch                 = 0x90002,
+    EdfParameters         = 0x90003,
+    ProcessManager        = 0x90004
 }
 }
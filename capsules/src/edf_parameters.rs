@@ -0,0 +1,83 @@
+//! Lets processes set their own period and budget under the earliest deadline
+//! first scheduler.
+//!
+//! Processes can also declare these in the `Periodic` TLV of their TBF header.
+//! This driver is for processes whose timing is only known at runtime.
+//!
+//! Without a limit any process could claim the whole CPU and starve the
+//! others, so the board sets the largest share of the CPU, in percent, that
+//! all periodic processes together may use. A process can only set parameters
+//! that keep the total within that share. Parameters from TBF headers count
+//! toward the total but are not checked themselves.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! # use kernel::static_init;
+//!
+//! let edf_parameters = static_init!(
+//!     capsules::edf_parameters::EdfParameters<
+//!         'static,
+//!         capsules::virtual_alarm::VirtualMuxAlarm<'static, sam4l::ast::Ast>>,
+//!     capsules::edf_parameters::EdfParameters::new(scheduler, 80)
+//! );
+//! ```
+
+use kernel::hil::time;
+use kernel::{AppId, Driver, EDFSched, ReturnCode};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::EdfParameters as usize;
+
+pub struct EdfParameters<'a, A: 'static + time::Alarm<'static>> {
+    scheduler: &'a EDFSched<'a, A>,
+    max_utilization_percent: u32,
+}
+
+impl<'a, A: 'static + time::Alarm<'static>> EdfParameters<'a, A> {
+    pub fn new(
+        scheduler: &'a EDFSched<'a, A>,
+        max_utilization_percent: u32,
+    ) -> EdfParameters<'a, A> {
+        EdfParameters {
+            scheduler,
+            max_utilization_percent,
+        }
+    }
+}
+
+impl<'a, A: 'static + time::Alarm<'static>> Driver for EdfParameters<'a, A> {
+    /// Command interface.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Return SUCCESS if this driver is included on the platform.
+    /// - `1`: Make the calling process periodic, with a period of `data1`
+    ///        microseconds and a budget of `data2` microseconds per period.
+    ///        Its first period starts immediately. Returns EINVAL if the
+    ///        budget is 0 or longer than the period, and EBUSY if the
+    ///        periodic processes would use more of the CPU than the board
+    ///        allows.
+    /// - `2`: Make the calling process run in the background again.
+    fn command(&self, command_num: usize, data1: usize, data2: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => {
+                let (period_us, budget_us) = (data1 as u32, data2 as u32);
+                if period_us == 0 || budget_us == 0 || budget_us > period_us {
+                    return ReturnCode::EINVAL;
+                }
+                let utilization = budget_us as u64 * 1_000_000 / period_us as u64;
+                let others = self.scheduler.utilization_except(appid);
+                if utilization + others > self.max_utilization_percent as u64 * 10_000 {
+                    return ReturnCode::EBUSY;
+                }
+                self.scheduler.set_parameters(appid, period_us, budget_us)
+            }
+            2 => self.scheduler.set_parameters(appid, 0, 0),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
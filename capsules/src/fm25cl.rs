@@ -134,50 +134,81 @@ impl<'a, S: hil::spi::SpiMasterDevice> FM25CL<'a, S> {
         );
     }
 
-    pub fn write(&self, address: u16, buffer: &'static mut [u8], len: u16) -> ReturnCode {
+    pub fn write(
+        &self,
+        address: u16,
+        buffer: &'static mut [u8],
+        len: u16,
+    ) -> Result<(), (ReturnCode, &'static mut [u8])> {
         self.configure_spi();
 
-        self.txbuffer
-            .take()
-            .map_or(ReturnCode::ERESERVE, move |txbuffer| {
-                txbuffer[0] = Opcodes::WriteEnable as u8;
+        let txbuffer = match self.txbuffer.take() {
+            Some(txbuffer) => txbuffer,
+            None => return Err((ReturnCode::ERESERVE, buffer)),
+        };
+        txbuffer[0] = Opcodes::WriteEnable as u8;
 
-                let write_len = cmp::min(txbuffer.len(), len as usize);
+        let write_len = cmp::min(txbuffer.len(), len as usize);
 
+        // Save address and len for the actual write.
+        self.client_write_address.set(address);
+        self.client_write_len.set(write_len as u16);
+
+        self.state.set(State::WriteEnable);
+        match self.spi.read_write_bytes(txbuffer, None, 1) {
+            Ok(()) => {
                 // Need to save the buffer passed to us so we can give it back.
                 self.client_buffer.replace(buffer);
-                // Also save address and len for the actual write.
-                self.client_write_address.set(address);
-                self.client_write_len.set(write_len as u16);
-
-                self.state.set(State::WriteEnable);
-                self.spi.read_write_bytes(txbuffer, None, 1)
-            })
+                Ok(())
+            }
+            Err((error, txbuffer, rxbuffer)) => {
+                self.txbuffer.replace(txbuffer);
+                rxbuffer.map(|rxbuffer| self.rxbuffer.replace(rxbuffer));
+                self.state.set(State::Idle);
+                Err((error, buffer))
+            }
+        }
     }
 
-    pub fn read(&self, address: u16, buffer: &'static mut [u8], len: u16) -> ReturnCode {
+    pub fn read(
+        &self,
+        address: u16,
+        buffer: &'static mut [u8],
+        len: u16,
+    ) -> Result<(), (ReturnCode, &'static mut [u8])> {
         self.configure_spi();
 
-        self.txbuffer
-            .take()
-            .map_or(ReturnCode::ERESERVE, |txbuffer| {
-                self.rxbuffer
-                    .take()
-                    .map_or(ReturnCode::ERESERVE, move |rxbuffer| {
-                        txbuffer[0] = Opcodes::ReadMemory as u8;
-                        txbuffer[1] = ((address >> 8) & 0xFF) as u8;
-                        txbuffer[2] = (address & 0xFF) as u8;
-
-                        // Save the user buffer for later
-                        self.client_buffer.replace(buffer);
-
-                        let read_len = cmp::min(rxbuffer.len() - 3, len as usize);
-
-                        self.state.set(State::ReadMemory);
-                        self.spi
-                            .read_write_bytes(txbuffer, Some(rxbuffer), read_len + 3)
-                    })
-            })
+        let (txbuffer, rxbuffer) = match (self.txbuffer.take(), self.rxbuffer.take()) {
+            (Some(txbuffer), Some(rxbuffer)) => (txbuffer, rxbuffer),
+            (txbuffer, rxbuffer) => {
+                txbuffer.map(|txbuffer| self.txbuffer.replace(txbuffer));
+                rxbuffer.map(|rxbuffer| self.rxbuffer.replace(rxbuffer));
+                return Err((ReturnCode::ERESERVE, buffer));
+            }
+        };
+        txbuffer[0] = Opcodes::ReadMemory as u8;
+        txbuffer[1] = ((address >> 8) & 0xFF) as u8;
+        txbuffer[2] = (address & 0xFF) as u8;
+
+        let read_len = cmp::min(rxbuffer.len() - 3, len as usize);
+
+        self.state.set(State::ReadMemory);
+        match self
+            .spi
+            .read_write_bytes(txbuffer, Some(rxbuffer), read_len + 3)
+        {
+            Ok(()) => {
+                // Save the user buffer for later
+                self.client_buffer.replace(buffer);
+                Ok(())
+            }
+            Err((error, txbuffer, rxbuffer)) => {
+                self.txbuffer.replace(txbuffer);
+                rxbuffer.map(|rxbuffer| self.rxbuffer.replace(rxbuffer));
+                self.state.set(State::Idle);
+                Err((error, buffer))
+            }
+        }
     }
 }
 
@@ -207,7 +238,7 @@ impl<S: hil::spi::SpiMasterDevice> hil::spi::SpiMasterClient for FM25CL<'_, S> {
             State::WriteEnable => {
                 self.state.set(State::WriteMemory);
 
-                self.client_buffer.map(move |buffer| {
+                let result = self.client_buffer.map(move |buffer| {
                     write_buffer[0] = Opcodes::WriteMemory as u8;
                     write_buffer[1] = ((self.client_write_address.get() >> 8) & 0xFF) as u8;
                     write_buffer[2] = (self.client_write_address.get() & 0xFF) as u8;
@@ -220,8 +251,19 @@ impl<S: hil::spi::SpiMasterDevice> hil::spi::SpiMasterClient for FM25CL<'_, S> {
                     }
 
                     self.spi
-                        .read_write_bytes(write_buffer, read_buffer, write_len + 3);
+                        .read_write_bytes(write_buffer, read_buffer, write_len + 3)
                 });
+
+                // If the write could not be started, put our buffers back and
+                // tell the client that nothing was written.
+                if let Some(Err((_, write_buffer, read_buffer))) = result {
+                    self.state.set(State::Idle);
+                    self.txbuffer.replace(write_buffer);
+                    read_buffer.map(|read_buffer| self.rxbuffer.replace(read_buffer));
+                    self.client_buffer.take().map(move |buffer| {
+                        self.client.map(move |client| client.write_done(buffer, 0));
+                    });
+                }
             }
             State::WriteMemory => {
                 self.state.set(State::Idle);
@@ -281,9 +323,16 @@ impl<S: hil::spi::SpiMasterDevice> FM25CLCustom for FM25CL<'_, S> {
 
                         // Use 4 bytes instead of the required 2 because that works better
                         // with DMA for some reason.
-                        self.spi.read_write_bytes(txbuffer, Some(rxbuffer), 4);
                         self.state.set(State::ReadStatus);
-                        ReturnCode::SUCCESS
+                        match self.spi.read_write_bytes(txbuffer, Some(rxbuffer), 4) {
+                            Ok(()) => ReturnCode::SUCCESS,
+                            Err((error, txbuffer, rxbuffer)) => {
+                                self.txbuffer.replace(txbuffer);
+                                rxbuffer.map(|rxbuffer| self.rxbuffer.replace(rxbuffer));
+                                self.state.set(State::Idle);
+                                error
+                            }
+                        }
                     })
             })
     }
@@ -298,11 +347,21 @@ impl<S: hil::spi::SpiMasterDevice> hil::nonvolatile_storage::NonvolatileStorage<
         self.client.set(client);
     }
 
-    fn read(&self, buffer: &'static mut [u8], address: usize, length: usize) -> ReturnCode {
+    fn read(
+        &self,
+        buffer: &'static mut [u8],
+        address: usize,
+        length: usize,
+    ) -> Result<(), (ReturnCode, &'static mut [u8])> {
         self.read(address as u16, buffer, length as u16)
     }
 
-    fn write(&self, buffer: &'static mut [u8], address: usize, length: usize) -> ReturnCode {
+    fn write(
+        &self,
+        buffer: &'static mut [u8],
+        address: usize,
+        length: usize,
+    ) -> Result<(), (ReturnCode, &'static mut [u8])> {
         self.write(address as u16, buffer, length as u16)
     }
 }
@@ -34,7 +34,10 @@ use kernel::common::cells::{OptionalCell, TakeCell};
 use kernel::common::leasable_buffer::LeasableBuffer;
 use kernel::hil::digest;
 use kernel::hil::digest::DigestType;
-use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+use kernel::{
+    AppId, AppSlice, Callback, Driver, Grant, ReadOnlyAppSlice, ReadableAppSlice, ReturnCode,
+    Shared,
+};
 
 pub struct HmacDriver<'a, H: digest::Digest<'a, T>, T: 'static + DigestType> {
     hmac: &'a H,
@@ -311,7 +314,7 @@ impl<'a, H: digest::Digest<'a, T> + digest::HMACSha256, T: DigestType> Driver
             0 => self
                 .apps
                 .enter(appid, |app, _| {
-                    app.key = slice;
+                    app.key = slice.map(ReadableAppSlice::from);
                     ReturnCode::SUCCESS
                 })
                 .unwrap_or(ReturnCode::FAIL),
@@ -320,7 +323,7 @@ impl<'a, H: digest::Digest<'a, T> + digest::HMACSha256, T: DigestType> Driver
             1 => self
                 .apps
                 .enter(appid, |app, _| {
-                    app.data = slice;
+                    app.data = slice.map(ReadableAppSlice::from);
                     ReturnCode::SUCCESS
                 })
                 .unwrap_or(ReturnCode::FAIL),
@@ -339,6 +342,38 @@ impl<'a, H: digest::Digest<'a, T> + digest::HMACSha256, T: DigestType> Driver
         }
     }
 
+    /// The key and the data can also be shared read-only, so that they can
+    /// be kept in the app's flash.
+    ///
+    /// ### `allow_num`
+    ///
+    /// - `0`: Read-only buffer for the key.
+    /// - `1`: Read-only buffer for the data.
+    fn allow_readonly(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<ReadOnlyAppSlice<u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.key = slice.map(ReadableAppSlice::from);
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            1 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.data = slice.map(ReadableAppSlice::from);
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::FAIL),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
     /// Subscribe to HmacDriver events.
     ///
     /// ### `subscribe_num`
@@ -465,8 +500,8 @@ impl<'a, H: digest::Digest<'a, T> + digest::HMACSha256, T: DigestType> Driver
 pub struct App {
     callback: OptionalCell<Callback>,
     pending_run_app: Option<AppId>,
-    key: Option<AppSlice<Shared, u8>>,
-    data: Option<AppSlice<Shared, u8>>,
+    key: Option<ReadableAppSlice<u8>>,
+    data: Option<ReadableAppSlice<u8>>,
     dest: Option<AppSlice<Shared, u8>>,
 }
 
@@ -22,6 +22,7 @@ pub mod crc;
 pub mod dac;
 pub mod debug_process_restart;
 pub mod driver;
+pub mod edf_parameters;
 pub mod fm25cl;
 pub mod ft6x06;
 pub mod fxos8700cq;
@@ -52,6 +53,9 @@ pub mod nrf51822_serialization;
 pub mod panic_button;
 pub mod pca9544a;
 pub mod process_console;
+pub mod process_core_dump;
+pub mod process_fault_log;
+pub mod process_manager;
 pub mod rf233;
 pub mod rf233_const;
 pub mod rng;
@@ -232,7 +232,10 @@ impl<
                         txbuffer[0] = Opcodes::RDID as u8;
 
                         self.state.set(State::ReadId);
-                        self.spi.read_write_bytes(txbuffer, Some(rxbuffer), 4)
+                        self.spi
+                            .read_write_bytes(txbuffer, Some(rxbuffer), 4)
+                            .err()
+                            .map_or(ReturnCode::SUCCESS, |(error, _, _)| error)
                     })
             })
     }
@@ -245,7 +248,10 @@ impl<
             .take()
             .map_or(ReturnCode::ERESERVE, |txbuffer| {
                 txbuffer[0] = Opcodes::WREN as u8;
-                self.spi.read_write_bytes(txbuffer, None, 1)
+                self.spi
+                    .read_write_bytes(txbuffer, None, 1)
+                    .err()
+                    .map_or(ReturnCode::SUCCESS, |(error, _, _)| error)
             })
     }
 
@@ -283,11 +289,10 @@ impl<
                             sector_index,
                             page_index: 0,
                         });
-                        self.spi.read_write_bytes(
-                            txbuffer,
-                            Some(rxbuffer),
-                            (PAGE_SIZE + 4) as usize,
-                        )
+                        self.spi
+                            .read_write_bytes(txbuffer, Some(rxbuffer), (PAGE_SIZE + 4) as usize)
+                            .err()
+                            .map_or(ReturnCode::SUCCESS, |(error, _, _)| error)
                     })
             });
 
@@ -378,7 +383,7 @@ impl<
                                 page_index: page_index + 1,
                             });
                             self.client_sector.replace(sector);
-                            self.spi.read_write_bytes(
+                            let _ = self.spi.read_write_bytes(
                                 write_buffer,
                                 Some(read_buffer),
                                 (PAGE_SIZE + 4) as usize,
@@ -397,7 +402,7 @@ impl<
                 write_buffer[2] = ((sector_index * SECTOR_SIZE) >> 8) as u8;
                 write_buffer[3] = ((sector_index * SECTOR_SIZE) >> 0) as u8;
 
-                self.spi.read_write_bytes(write_buffer, None, 4);
+                let _ = self.spi.read_write_bytes(write_buffer, None, 4);
             }
             State::EraseSectorErase { operation } => {
                 self.state.set(State::EraseSectorCheckDone { operation });
@@ -415,7 +420,8 @@ impl<
                     // Check the status byte to see if the erase is done or not.
                     if status & 0x01 == 0x01 {
                         // Erase is still in progress.
-                        self.spi
+                        let _ = self
+                            .spi
                             .read_write_bytes(write_buffer, Some(read_buffer), 2);
                     } else {
                         // Erase has finished, so jump to the next state.
@@ -462,7 +468,7 @@ impl<
                     });
                     // Need to write enable before each PP
                     write_buffer[0] = Opcodes::WREN as u8;
-                    self.spi.read_write_bytes(write_buffer, None, 1);
+                    let _ = self.spi.read_write_bytes(write_buffer, None, 1);
                 }
             }
             State::WriteSectorWrite {
@@ -486,7 +492,8 @@ impl<
                     }
                 });
 
-                self.spi
+                let _ = self
+                    .spi
                     .read_write_bytes(write_buffer, None, (PAGE_SIZE + 4) as usize);
             }
             State::WriteSectorCheckDone {
@@ -514,7 +521,8 @@ impl<
                     // Check the status byte to see if the write is done or not.
                     if status & 0x01 == 0x01 {
                         // Write is still in progress.
-                        self.spi
+                        let _ = self
+                            .spi
                             .read_write_bytes(write_buffer, Some(read_buffer), 2);
                     } else {
                         // Write has finished, so go back to writing.
@@ -545,7 +553,8 @@ impl<
         self.txbuffer.take().map(|write_buffer| {
             self.rxbuffer.take().map(move |read_buffer| {
                 write_buffer[0] = Opcodes::RDSR as u8;
-                self.spi
+                let _ = self
+                    .spi
                     .read_write_bytes(write_buffer, Some(read_buffer), 2);
             });
         });
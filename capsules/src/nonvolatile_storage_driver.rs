@@ -172,7 +172,7 @@ impl<'a> NonvolatileStorage<'a> {
 
     // Check so see if we are doing something. If not, go ahead and do this
     // command. If so, this is queued and will be run when the pending
-    // command completes.
+    // command completes. This is only for commands from userspace.
     fn enqueue_command(
         &self,
         command: NonvolatileCommand,
@@ -193,15 +193,8 @@ impl<'a> NonvolatileStorage<'a> {
                 }
             }
             NonvolatileCommand::KernelRead | NonvolatileCommand::KernelWrite => {
-                // Because the kernel uses the NonvolatileStorage interface,
-                // its calls are absolute addresses.
-                if offset < self.kernel_start_address
-                    || offset >= self.kernel_start_address + self.kernel_length
-                    || length > self.kernel_length
-                    || offset + length > self.kernel_start_address + self.kernel_length
-                {
-                    return ReturnCode::EINVAL;
-                }
+                // The kernel's commands are started by `kernel_command()`.
+                return ReturnCode::FAIL;
             }
         }
 
@@ -279,40 +272,55 @@ impl<'a> NonvolatileStorage<'a> {
                         .unwrap_or_else(|err| err.into())
                 })
             }
-            NonvolatileCommand::KernelRead | NonvolatileCommand::KernelWrite => {
-                self.kernel_buffer
-                    .take()
-                    .map_or(ReturnCode::ENOMEM, |kernel_buffer| {
-                        let active_len = cmp::min(length, kernel_buffer.len());
-
-                        // Check if there is something going on.
-                        if self.current_user.is_none() {
-                            // Nothing is using this, lets go!
-                            self.current_user.set(NonvolatileUser::Kernel);
-
-                            match command {
-                                NonvolatileCommand::KernelRead => {
-                                    self.driver.read(kernel_buffer, offset, active_len)
-                                }
-                                NonvolatileCommand::KernelWrite => {
-                                    self.driver.write(kernel_buffer, offset, active_len)
-                                }
-                                _ => ReturnCode::FAIL,
-                            }
-                        } else {
-                            if self.kernel_pending_command.get() == true {
-                                ReturnCode::ENOMEM
-                            } else {
-                                self.kernel_pending_command.set(true);
-                                self.kernel_command.set(command);
-                                self.kernel_readwrite_length.set(active_len);
-                                self.kernel_readwrite_address.set(offset);
-                                self.kernel_buffer.replace(kernel_buffer);
-                                ReturnCode::SUCCESS
-                            }
-                        }
-                    })
+            NonvolatileCommand::KernelRead | NonvolatileCommand::KernelWrite => ReturnCode::FAIL,
+        }
+    }
+
+    // Start a read or write for the kernel, or queue it if the storage is
+    // busy. The buffer is handed back if the command can be neither started
+    // nor queued.
+    fn kernel_command(
+        &self,
+        command: NonvolatileCommand,
+        buffer: &'static mut [u8],
+        offset: usize,
+        length: usize,
+    ) -> Result<(), (ReturnCode, &'static mut [u8])> {
+        // Because the kernel uses the NonvolatileStorage interface, its calls
+        // are absolute addresses.
+        if offset < self.kernel_start_address
+            || offset >= self.kernel_start_address + self.kernel_length
+            || length > self.kernel_length
+            || offset + length > self.kernel_start_address + self.kernel_length
+        {
+            return Err((ReturnCode::EINVAL, buffer));
+        }
+
+        let active_len = cmp::min(length, buffer.len());
+
+        // Check if there is something going on.
+        if self.current_user.is_none() {
+            // Nothing is using this, lets go!
+            self.current_user.set(NonvolatileUser::Kernel);
+
+            let result = match command {
+                NonvolatileCommand::KernelRead => self.driver.read(buffer, offset, active_len),
+                NonvolatileCommand::KernelWrite => self.driver.write(buffer, offset, active_len),
+                _ => Err((ReturnCode::FAIL, buffer)),
+            };
+            if result.is_err() {
+                self.current_user.clear();
             }
+            result
+        } else if self.kernel_pending_command.get() {
+            Err((ReturnCode::ENOMEM, buffer))
+        } else {
+            self.kernel_pending_command.set(true);
+            self.kernel_command.set(command);
+            self.kernel_readwrite_length.set(active_len);
+            self.kernel_readwrite_address.set(offset);
+            self.kernel_buffer.replace(buffer);
+            Ok(())
         }
     }
 
@@ -332,14 +340,22 @@ impl<'a> NonvolatileStorage<'a> {
             let active_len = cmp::min(length, buffer.len());
 
             // self.current_app.set(Some(appid));
-            match command {
+            let result = match command {
                 NonvolatileCommand::UserspaceRead => {
                     self.driver.read(buffer, physical_address, active_len)
                 }
                 NonvolatileCommand::UserspaceWrite => {
                     self.driver.write(buffer, physical_address, active_len)
                 }
-                _ => ReturnCode::FAIL,
+                _ => Err((ReturnCode::FAIL, buffer)),
+            };
+            match result {
+                Ok(()) => ReturnCode::SUCCESS,
+                Err((error, buffer)) => {
+                    self.buffer.replace(buffer);
+                    self.current_user.clear();
+                    error
+                }
             }
         })
     }
@@ -351,7 +367,8 @@ impl<'a> NonvolatileStorage<'a> {
                 self.kernel_pending_command.set(false);
                 self.current_user.set(NonvolatileUser::Kernel);
 
-                match self.kernel_command.get() {
+                let command = self.kernel_command.get();
+                let result = match command {
                     NonvolatileCommand::KernelRead => self.driver.read(
                         kernel_buffer,
                         self.kernel_readwrite_address.get(),
@@ -362,7 +379,16 @@ impl<'a> NonvolatileStorage<'a> {
                         self.kernel_readwrite_address.get(),
                         self.kernel_readwrite_length.get(),
                     ),
-                    _ => ReturnCode::FAIL,
+                    _ => Err((ReturnCode::FAIL, kernel_buffer)),
+                };
+                if let Err((_, buffer)) = result {
+                    // Give the buffer back to the kernel without having read
+                    // or written anything.
+                    self.current_user.clear();
+                    self.kernel_client.map(move |client| match command {
+                        NonvolatileCommand::KernelRead => client.read_done(buffer, 0),
+                        _ => client.write_done(buffer, 0),
+                    });
                 }
             });
         } else {
@@ -455,14 +481,22 @@ impl hil::nonvolatile_storage::NonvolatileStorage<'static> for NonvolatileStorag
         self.kernel_client.set(client);
     }
 
-    fn read(&self, buffer: &'static mut [u8], address: usize, length: usize) -> ReturnCode {
-        self.kernel_buffer.replace(buffer);
-        self.enqueue_command(NonvolatileCommand::KernelRead, address, length, None)
+    fn read(
+        &self,
+        buffer: &'static mut [u8],
+        address: usize,
+        length: usize,
+    ) -> Result<(), (ReturnCode, &'static mut [u8])> {
+        self.kernel_command(NonvolatileCommand::KernelRead, buffer, address, length)
     }
 
-    fn write(&self, buffer: &'static mut [u8], address: usize, length: usize) -> ReturnCode {
-        self.kernel_buffer.replace(buffer);
-        self.enqueue_command(NonvolatileCommand::KernelWrite, address, length, None)
+    fn write(
+        &self,
+        buffer: &'static mut [u8],
+        address: usize,
+        length: usize,
+    ) -> Result<(), (ReturnCode, &'static mut [u8])> {
+        self.kernel_command(NonvolatileCommand::KernelWrite, buffer, address, length)
     }
 }
 
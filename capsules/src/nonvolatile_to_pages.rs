@@ -94,85 +94,96 @@ impl<'a, F: hil::flash::Flash> hil::nonvolatile_storage::NonvolatileStorage<'sta
         self.client.set(client);
     }
 
-    fn read(&self, buffer: &'static mut [u8], address: usize, length: usize) -> ReturnCode {
+    fn read(
+        &self,
+        buffer: &'static mut [u8],
+        address: usize,
+        length: usize,
+    ) -> Result<(), (ReturnCode, &'static mut [u8])> {
         if self.state.get() != State::Idle {
-            return ReturnCode::EBUSY;
+            return Err((ReturnCode::EBUSY, buffer));
         }
 
-        self.pagebuffer
-            .take()
-            .map_or(ReturnCode::ERESERVE, move |pagebuffer| {
-                let page_size = pagebuffer.as_mut().len();
+        let pagebuffer = match self.pagebuffer.take() {
+            Some(pagebuffer) => pagebuffer,
+            None => return Err((ReturnCode::ERESERVE, buffer)),
+        };
+        let page_size = pagebuffer.as_mut().len();
 
-                // Just start reading. We'll worry about how much of the page we
-                // want later.
-                self.state.set(State::Read);
-                self.buffer.replace(buffer);
-                self.address.set(address);
-                self.length.set(length);
-                self.remaining_length.set(length);
-                self.buffer_index.set(0);
+        // Just start reading. We'll worry about how much of the page we want
+        // later.
+        self.state.set(State::Read);
+        self.address.set(address);
+        self.length.set(length);
+        self.remaining_length.set(length);
+        self.buffer_index.set(0);
 
-                match self.driver.read_page(address / page_size, pagebuffer) {
-                    Ok(()) => ReturnCode::SUCCESS,
-                    Err((return_code, pagebuffer)) => {
-                        self.pagebuffer.replace(pagebuffer);
-                        return_code
-                    }
-                }
-            })
+        match self.driver.read_page(address / page_size, pagebuffer) {
+            Ok(()) => {
+                self.buffer.replace(buffer);
+                Ok(())
+            }
+            Err((return_code, pagebuffer)) => {
+                self.pagebuffer.replace(pagebuffer);
+                self.state.set(State::Idle);
+                Err((return_code, buffer))
+            }
+        }
     }
 
-    fn write(&self, buffer: &'static mut [u8], address: usize, length: usize) -> ReturnCode {
+    fn write(
+        &self,
+        buffer: &'static mut [u8],
+        address: usize,
+        length: usize,
+    ) -> Result<(), (ReturnCode, &'static mut [u8])> {
         if self.state.get() != State::Idle {
-            return ReturnCode::EBUSY;
+            return Err((ReturnCode::EBUSY, buffer));
         }
 
-        self.pagebuffer
-            .take()
-            .map_or(ReturnCode::ERESERVE, move |pagebuffer| {
-                let page_size = pagebuffer.as_mut().len();
+        let pagebuffer = match self.pagebuffer.take() {
+            Some(pagebuffer) => pagebuffer,
+            None => return Err((ReturnCode::ERESERVE, buffer)),
+        };
+        let page_size = pagebuffer.as_mut().len();
 
-                self.state.set(State::Write);
-                self.length.set(length);
+        self.state.set(State::Write);
+        self.length.set(length);
 
-                if address % page_size == 0 && length >= page_size {
-                    // This write is aligned to a page and we are writing an entire
-                    // page or more.
+        let result = if address % page_size == 0 && length >= page_size {
+            // This write is aligned to a page and we are writing an entire
+            // page or more.
 
-                    // Copy data into page buffer.
-                    for i in 0..page_size {
-                        pagebuffer.as_mut()[i] = buffer[i];
-                    }
+            // Copy data into page buffer.
+            for i in 0..page_size {
+                pagebuffer.as_mut()[i] = buffer[i];
+            }
 
-                    self.buffer.replace(buffer);
-                    self.address.set(address + page_size);
-                    self.remaining_length.set(length - page_size);
-                    self.buffer_index.set(page_size);
+            self.address.set(address + page_size);
+            self.remaining_length.set(length - page_size);
+            self.buffer_index.set(page_size);
 
-                    match self.driver.write_page(address / page_size, pagebuffer) {
-                        Ok(()) => ReturnCode::SUCCESS,
-                        Err((return_code, pagebuffer)) => {
-                            self.pagebuffer.replace(pagebuffer);
-                            return_code
-                        }
-                    }
-                } else {
-                    // Need to do a read first.
-                    self.buffer.replace(buffer);
-                    self.address.set(address);
-                    self.remaining_length.set(length);
-                    self.buffer_index.set(0);
+            self.driver.write_page(address / page_size, pagebuffer)
+        } else {
+            // Need to do a read first.
+            self.address.set(address);
+            self.remaining_length.set(length);
+            self.buffer_index.set(0);
 
-                    match self.driver.read_page(address / page_size, pagebuffer) {
-                        Ok(()) => ReturnCode::SUCCESS,
-                        Err((return_code, pagebuffer)) => {
-                            self.pagebuffer.replace(pagebuffer);
-                            return_code
-                        }
-                    }
-                }
-            })
+            self.driver.read_page(address / page_size, pagebuffer)
+        };
+
+        match result {
+            Ok(()) => {
+                self.buffer.replace(buffer);
+                Ok(())
+            }
+            Err((return_code, pagebuffer)) => {
+                self.pagebuffer.replace(pagebuffer);
+                self.state.set(State::Idle);
+                Err((return_code, buffer))
+            }
+        }
     }
 }
 
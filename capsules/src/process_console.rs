@@ -5,13 +5,20 @@
 //! --------
 //!
 //! This module provides a simple text-based console to inspect and control
-//! which processes are running. The console has five commands:
+//! which processes are running. The console has the following commands:
 //!  - 'help' prints the available commands and arguments
 //!  - 'status' prints the current system status
+//!  - 'stack' prints the deepest the kernel stack has grown since boot
 //!  - 'list' lists the current processes with their IDs and running state
 //!  - 'stop n' stops the process with name n
 //!  - 'start n' starts the stopped process with name n
 //!  - 'fault n' forces the process with name n into a fault state
+//!  - 'trace' prints and removes the oldest records of the binary syscall
+//!    trace, if the board registered a `SyscallTracer` with the kernel
+//!  - 'strace n on|off' turns printing the syscalls of the process with name n
+//!    to the debug output on or off
+//!  - 'debug [switch on|off]' lists the kernel debugging switches, or turns
+//!    one on or off
 //!
 //! ### `list` Command Fields:
 //!
@@ -28,6 +35,18 @@
 //! - `State`: The state the process is in.
 //! - `Grants`: The number of grants that have been initialized for the process
 //!   out of the total number of grants defined by the kernel.
+//! - `Priority`: The effective priority of the process under a fixed-priority
+//!   scheduler, from its TBF header. Lower values are higher priorities, and
+//!   `-` means the process did not declare one and has the lowest priority.
+//! - `Runs`: How many times the scheduler has run the process since boot.
+//! - `CPU ms`: The CPU time the process has used since boot, in milliseconds.
+//!   It is measured with the scheduler timer, so only runs with a timeslice
+//!   count: under `PrioritySched`, `CooperativeSched`, and other schedulers
+//!   without timeslices this column is always 0.
+//! - `Max us`: The longest the process has run for at once, in microseconds.
+//!   Measured the same way as `CPU ms`, so it is also 0 without timeslices.
+//! - `Exit`: The completion code the process passed the last time it called
+//!   the exit syscall, or `-` if it never exited.
 //!
 //! Setup
 //! -----
@@ -90,9 +109,9 @@
 //! Initialization complete. Entering main loop
 //! Hello World!
 //! list
-//! PID    Name    Quanta  Syscalls  Dropped Callbacks  Restarts    State  Grants
-//! 00     blink        0       113                  0         0  Yielded    1/12
-//! 01     c_hello      0         8                  0         0  Yielded    3/12
+//! PID    Name    Quanta  Syscalls  Dropped Callbacks  Restarts    State  Grants  Priority    Runs  CPU ms  Max us  Exit
+//! 00     blink        0       113                  0         0  Yielded    1/12         1      57       3     112     -
+//! 01     c_hello      0         8                  0         0  Terminated 3/12         -       5       0      96     0
 //! ```
 //!
 //! To get a general view of the system, use the status command:
@@ -102,6 +121,18 @@
 //! Total processes: 2
 //! Active processes: 2
 //! Timeslice expirations: 0
+//! CPU time: 3 ms
+//! ```
+//!
+//! As with the `CPU ms` column of `list`, the CPU time only counts runs the
+//! scheduler gave a timeslice.
+//!
+//! The `stack` command shows how close the kernel has come to overflowing its
+//! own stack, if the board passed its stack to the kernel:
+//!
+//! ```text
+//! stack
+//! Kernel stack: 1536 of 8192 bytes used
 //! ```
 //!
 //! and you can control processes with the `start` and `stop` commands:
@@ -110,17 +141,48 @@
 //! stop blink
 //! Process blink stopped
 //! ```
+//!
+//! To diagnose a misbehaving process without rebuilding the kernel, turn on
+//! syscall tracing for just that process, or change the kernel's debugging
+//! switches:
+//!
+//! ```text
+//! strace blink on
+//! Tracing syscalls of process blink
+//! [0] cmd(0x2, 1, 0x0, 0x0) = 0x0 = SUCCESS
+//! strace blink off
+//! Stopped tracing syscalls of process blink
+//! debug
+//! trace_syscalls: off
+//! debug_load_processes: off
+//! debug debug_load_processes on
+//! debug_load_processes: on
+//! ```
+//!
+//! If the board records a binary syscall trace, `trace` prints the clock
+//! frequency of the trace followed by up to eight records, each as a line of
+//! hex. Run it repeatedly to drain the trace, save the output, and decode it
+//! with `tools/syscall_trace`:
+//!
+//! ```text
+//! trace
+//! trace clock 16000
+//! trace 5d0f0000000002000000000001000000000000000000000000000000
+//! ```
 
 use core::cell::Cell;
 use core::cmp;
+use core::fmt;
 use core::str;
 use kernel::capabilities::ProcessManagementCapability;
 use kernel::common::cells::TakeCell;
 use kernel::debug;
 use kernel::hil::uart;
 use kernel::introspection::KernelInfo;
+use kernel::DebugSwitch;
 use kernel::Kernel;
 use kernel::ReturnCode;
+use kernel::SYSCALL_TRACE_RECORD_LEN;
 
 // Since writes are character echoes, we do not need more than 4 bytes:
 // the longest write is 3 bytes for a backspace (backspace, space, backspace).
@@ -132,6 +194,56 @@ pub static mut READ_BUF: [u8; 4] = [0; 4];
 // characters, limiting arguments to 25 bytes or so seems fine for now.
 pub static mut COMMAND_BUF: [u8; 32] = [0; 32];
 
+/// How many syscall trace records the `trace` command prints at a time, to
+/// avoid overrunning the debug buffer.
+const TRACE_RECORDS_PER_COMMAND: usize = 8;
+
+/// Formats a process priority, showing the default lowest priority as `-`.
+struct Priority(u32);
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0 == u32::MAX {
+            f.pad("-")
+        } else {
+            fmt::Display::fmt(&self.0, f)
+        }
+    }
+}
+
+/// Formats the completion code of a process, showing `-` if it never exited.
+struct CompletionCode(Option<usize>);
+
+impl fmt::Display for CompletionCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(code) => fmt::Display::fmt(&code, f),
+            None => f.pad("-"),
+        }
+    }
+}
+
+/// Parses the argument of a command that turns something on or off.
+fn parse_on_off(argument: Option<&str>) -> Option<bool> {
+    match argument {
+        Some("on") => Some(true),
+        Some("off") => Some(false),
+        _ => None,
+    }
+}
+
+/// Formats bytes as a string of hex digits.
+struct Hex<'a>(&'a [u8]);
+
+impl fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
 pub struct ProcessConsole<'a, C: ProcessManagementCapability> {
     uart: &'a dyn uart::UartData<'a>,
     tx_in_progress: Cell<bool>,
@@ -210,7 +322,7 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
                         let clean_str = s.trim();
                         if clean_str.starts_with("help") {
                             debug!("Welcome to the process console.");
-                            debug!("Valid commands are: help status list stop start fault");
+                            debug!("Valid commands are: help status stack list stop start fault trace strace debug");
                         } else if clean_str.starts_with("start") {
                             let argument = clean_str.split_whitespace().nth(1);
                             argument.map(|name| {
@@ -253,8 +365,68 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
                                     },
                                 );
                             });
+                        } else if clean_str.starts_with("strace") {
+                            let mut arguments = clean_str.split_whitespace().skip(1);
+                            let name = arguments.next();
+                            match (name, parse_on_off(arguments.next())) {
+                                (Some(name), Some(enabled)) => {
+                                    self.kernel.process_each_capability(
+                                        &self.capability,
+                                        |proc| {
+                                            if proc.get_process_name() == name {
+                                                proc.debug_set_trace_syscalls(enabled);
+                                                if enabled {
+                                                    debug!("Tracing syscalls of process {}", name);
+                                                } else {
+                                                    debug!(
+                                                        "Stopped tracing syscalls of process {}",
+                                                        name
+                                                    );
+                                                }
+                                            }
+                                        },
+                                    );
+                                }
+                                _ => debug!("Usage: strace <process name> on|off"),
+                            }
+                        } else if clean_str.starts_with("debug") {
+                            let mut arguments = clean_str.split_whitespace().skip(1);
+                            match arguments.next() {
+                                None => {
+                                    for switch in DebugSwitch::ALL.iter() {
+                                        debug!(
+                                            "{}: {}",
+                                            switch.name(),
+                                            if self.kernel.get_debug_switch(*switch) {
+                                                "on"
+                                            } else {
+                                                "off"
+                                            }
+                                        );
+                                    }
+                                }
+                                Some(name) => match (
+                                    DebugSwitch::from_name(name),
+                                    parse_on_off(arguments.next()),
+                                ) {
+                                    (Some(switch), Some(enabled)) => {
+                                        self.kernel.set_debug_switch(
+                                            switch,
+                                            enabled,
+                                            &self.capability,
+                                        );
+                                        debug!(
+                                            "{}: {}",
+                                            switch.name(),
+                                            if enabled { "on" } else { "off" }
+                                        );
+                                    }
+                                    (None, _) => debug!("Unknown debug switch {}", name),
+                                    (Some(_), None) => debug!("Usage: debug <switch> on|off"),
+                                },
+                            }
                         } else if clean_str.starts_with("list") {
-                            debug!(" PID    Name                Quanta  Syscalls  Dropped Callbacks  Restarts    State  Grants");
+                            debug!(" PID    Name                Quanta  Syscalls  Dropped Callbacks  Restarts    State  Grants  Priority    Runs  CPU ms  Max us  Exit");
                             self.kernel
                                 .process_each_capability(&self.capability, |proc| {
                                     let info: KernelInfo = KernelInfo::new(self.kernel);
@@ -264,7 +436,7 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
                                     let (grants_used, grants_total) = info.number_app_grant_uses(appid, &self.capability);
 
                                     debug!(
-                                        "  {:?}\t{:<20}{:6}{:10}{:19}{:10}  {:?}{:5}/{}",
+                                        "  {:?}\t{:<20}{:6}{:10}{:19}{:10}  {:?}{:5}/{}{:>10}{:8}{:8}{:8}{:>6}",
                                         appid,
                                         pname,
                                         proc.debug_timeslice_expiration_count(),
@@ -273,9 +445,31 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
                                         proc.get_restart_count(),
                                         proc.get_state(),
                                         grants_used,
-                                        grants_total
+                                        grants_total,
+                                        Priority(proc.priority()),
+                                        info.number_app_runs(appid, &self.capability),
+                                        info.app_cpu_time_us(appid, &self.capability) / 1000,
+                                        info.app_longest_run_us(appid, &self.capability),
+                                        CompletionCode(
+                                            info.app_completion_code(appid, &self.capability)
+                                        )
                                     );
                                 });
+                        } else if clean_str.starts_with("trace") {
+                            match self.kernel.get_syscall_tracer(&self.capability) {
+                                Some(tracer) => {
+                                    let mut records =
+                                        [0; TRACE_RECORDS_PER_COMMAND * SYSCALL_TRACE_RECORD_LEN];
+                                    let length = tracer.drain(&mut records);
+                                    debug!("trace clock {}", tracer.frequency());
+                                    for record in
+                                        records[..length].chunks(SYSCALL_TRACE_RECORD_LEN)
+                                    {
+                                        debug!("trace {}", Hex(record));
+                                    }
+                                }
+                                None => debug!("No syscall trace is being recorded."),
+                            }
                         } else if clean_str.starts_with("status") {
                             let info: KernelInfo = KernelInfo::new(self.kernel);
                             debug!(
@@ -290,8 +484,20 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
                                 "Timeslice expirations: {}",
                                 info.timeslice_expirations(&self.capability)
                             );
+                            debug!(
+                                "CPU time: {} ms",
+                                info.cpu_time_us(&self.capability) / 1000
+                            );
+                        } else if clean_str.starts_with("stack") {
+                            let info: KernelInfo = KernelInfo::new(self.kernel);
+                            match info.kernel_stack_usage(&self.capability) {
+                                Some((used, size)) => {
+                                    debug!("Kernel stack: {} of {} bytes used", used, size)
+                                }
+                                None => debug!("The board did not register the kernel stack."),
+                            }
                         } else {
-                            debug!("Valid commands are: help status list stop start fault");
+                            debug!("Valid commands are: help status stack list stop start fault trace strace debug");
                         }
                     }
                     Err(_e) => debug!("Invalid command: {:?}", command),
@@ -0,0 +1,261 @@
+//! Writes a core dump of a faulted process to nonvolatile storage.
+//!
+//! `ProcessCoreDump` is a `ProcessFaultHandler` that, when a process faults,
+//! takes a snapshot of the process's registers, its memory layout, and the RAM
+//! it can access, and writes it to a region of a `NonvolatileStorage` device.
+//! Unlike `print_full_process()` this does not need a console to be attached,
+//! and the dump can be read off the device later and turned into a core file
+//! for GDB with the `tools/coredump` tool.
+//!
+//! The snapshot is taken synchronously when the fault happens, so it is
+//! consistent even if the process is restarted before the dump is written. It
+//! is limited by the size of the buffer passed to the capsule (and the size of
+//! the storage region): RAM that does not fit is left out, starting at the top.
+//! Since the process's stack sits at the bottom of its RAM, a small buffer still
+//! captures the stack.
+//!
+//! Only the most recent fault is kept. A fault that happens while a dump is
+//! still being written is not recorded. With `FaultResponse::Panic` the kernel
+//! panics before the dump can be written, so use `Stop` or `Restart` instead.
+//! The same applies to the fault that makes a restart policy such as
+//! `ThresholdRestartThenPanic` give up and panic.
+//! Dumps the storage device refused to write are counted by `failed_dumps()`.
+//!
+//! Format
+//! ------
+//!
+//! All fields are little-endian 32-bit words unless noted.
+//!
+//! ```text
+//! offset  size  field
+//!      0     4  magic, "TKCD"; written last, so a dump with a different
+//!               value here is incomplete
+//!      4     4  format version (1)
+//!      8     4  total length of the dump in bytes
+//!     12     4  flags, bit 0 set if fault_address is valid, bit 1 set if the
+//!               RAM was truncated, bit 2 set if the kernel faulted the
+//!               process itself, in which case the fault reason is 0
+//!     16     4  process short ID
+//!     20     4  process restart count
+//!     24     4  fault reason (architecture-specific)
+//!     28     4  fault address
+//!     32     4  flash start
+//!     36     4  flash start after the TBF header
+//!     40     4  flash end
+//!     44     4  memory start
+//!     48     4  memory end
+//!     52     4  app break
+//!     56     4  kernel memory break
+//!     60     4  stack pointer
+//!     64     4  number of registers (N)
+//!     68     4  length of the process name (M)
+//!     72     4  length of the RAM snapshot (L)
+//!     76   4*N  registers, in the order of the architecture's
+//!               `UserspaceKernelBoundary::store_fault_context()`
+//!  76+4N     M  process name, padded with zeros to a multiple of 4 bytes
+//!      -     L  RAM, starting at memory start
+//! ```
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let core_dump = static_init!(
+//!     capsules::process_core_dump::ProcessCoreDump<'static>,
+//!     capsules::process_core_dump::ProcessCoreDump::new(
+//!         nv_to_page,
+//!         0x60000, // Start of the region in storage
+//!         0x20000, // Length of the region
+//!         &mut CORE_DUMP_BUFFER
+//!     )
+//! );
+//! hil::nonvolatile_storage::NonvolatileStorage::set_client(nv_to_page, core_dump);
+//! board_kernel.add_fault_handler(core_dump, &process_management_capability);
+//! ```
+
+use core::cell::Cell;
+use core::cmp;
+use kernel::common::cells::TakeCell;
+use kernel::hil::nonvolatile_storage::{NonvolatileStorage, NonvolatileStorageClient};
+use kernel::procs::{ProcessFaultHandler, ProcessMemoryMap, ProcessType};
+use kernel::syscall::FaultContext;
+
+/// Marks a complete dump.
+const MAGIC: u32 = 0x4443_4B54; // "TKCD"
+
+/// Version of the dump format written by this capsule.
+const FORMAT_VERSION: u32 = 1;
+
+/// Size of the fixed part of the dump, before the registers.
+const HEADER_LEN: usize = 76;
+
+const FLAG_FAULT_ADDRESS_VALID: u32 = 0x1;
+const FLAG_MEMORY_TRUNCATED: u32 = 0x2;
+const FLAG_KERNEL_FAULT: u32 = 0x4;
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Idle,
+    /// Writing everything except for the magic.
+    WritingDump,
+    /// Writing the magic to mark the dump complete.
+    WritingMagic,
+}
+
+pub struct ProcessCoreDump<'a> {
+    storage: &'a dyn NonvolatileStorage<'static>,
+    region_start: usize,
+    region_length: usize,
+    buffer: TakeCell<'static, [u8]>,
+    state: Cell<State>,
+    failed_dumps: Cell<usize>,
+}
+
+impl<'a> ProcessCoreDump<'a> {
+    pub fn new(
+        storage: &'a dyn NonvolatileStorage<'static>,
+        region_start: usize,
+        region_length: usize,
+        buffer: &'static mut [u8],
+    ) -> ProcessCoreDump<'a> {
+        ProcessCoreDump {
+            storage: storage,
+            region_start: region_start,
+            region_length: region_length,
+            buffer: TakeCell::new(buffer),
+            state: Cell::new(State::Idle),
+            failed_dumps: Cell::new(0),
+        }
+    }
+
+    /// How many core dumps could not be written since boot.
+    pub fn failed_dumps(&self) -> usize {
+        self.failed_dumps.get()
+    }
+
+    /// Record that writing a dump failed and keep `buffer` for the next one.
+    fn write_failed(&self, buffer: &'static mut [u8]) {
+        self.state.set(State::Idle);
+        self.buffer.replace(buffer);
+        self.failed_dumps.set(self.failed_dumps.get() + 1);
+    }
+
+    /// Fill `buffer` with the dump. Returns the length of the dump, or `None`
+    /// if not even the header fits.
+    fn serialize(
+        &self,
+        buffer: &mut [u8],
+        process: &dyn ProcessType,
+        context: &FaultContext,
+        memory_map: &ProcessMemoryMap,
+    ) -> Option<usize> {
+        let limit = cmp::min(buffer.len(), self.region_length);
+        let num_registers = cmp::min(context.num_registers, context.registers.len());
+        let name = process.get_process_name().as_bytes();
+        let name_offset = HEADER_LEN + 4 * num_registers;
+        let memory_offset = name_offset + ((name.len() + 3) & !3);
+        if memory_offset > limit {
+            return None;
+        }
+        let buffer = &mut buffer[..limit];
+
+        let app_size = memory_map.app_break - memory_map.memory_start;
+        let memory_length = process.read_app_memory(0, &mut buffer[memory_offset..]);
+        let length = memory_offset + memory_length;
+
+        let mut flags = 0;
+        if context.fault_address.is_some() {
+            flags |= FLAG_FAULT_ADDRESS_VALID;
+        }
+        if memory_length < app_size {
+            flags |= FLAG_MEMORY_TRUNCATED;
+        }
+        if !context.hardware_fault {
+            flags |= FLAG_KERNEL_FAULT;
+        }
+
+        let header = [
+            0, // Magic, written once the rest of the dump is.
+            FORMAT_VERSION,
+            length as u32,
+            flags,
+            process.short_id(),
+            process.get_restart_count() as u32,
+            context.fault_reason as u32,
+            context.fault_address.unwrap_or(0) as u32,
+            memory_map.flash_start as u32,
+            process.flash_non_protected_start() as u32,
+            memory_map.flash_end as u32,
+            memory_map.memory_start as u32,
+            memory_map.memory_end as u32,
+            memory_map.app_break as u32,
+            memory_map.kernel_memory_break as u32,
+            memory_map.stack_pointer as u32,
+            num_registers as u32,
+            name.len() as u32,
+            memory_length as u32,
+        ];
+        let registers = context.registers[..num_registers]
+            .iter()
+            .map(|register| *register as u32);
+        for (i, word) in header.iter().cloned().chain(registers).enumerate() {
+            buffer[4 * i..4 * i + 4].copy_from_slice(&word.to_le_bytes());
+        }
+
+        buffer[name_offset..name_offset + name.len()].copy_from_slice(name);
+        for byte in buffer[name_offset + name.len()..memory_offset].iter_mut() {
+            *byte = 0;
+        }
+
+        Some(length)
+    }
+}
+
+impl ProcessFaultHandler for ProcessCoreDump<'_> {
+    fn process_fault(
+        &self,
+        process: &dyn ProcessType,
+        context: &FaultContext,
+        memory_map: &ProcessMemoryMap,
+    ) {
+        if self.state.get() != State::Idle {
+            return;
+        }
+        self.buffer.take().map(|buffer| {
+            match self.serialize(buffer, process, context, memory_map) {
+                Some(length) => {
+                    self.state.set(State::WritingDump);
+                    if let Err((_, buffer)) = self.storage.write(buffer, self.region_start, length)
+                    {
+                        self.write_failed(buffer);
+                    }
+                }
+                None => {
+                    self.buffer.replace(buffer);
+                }
+            }
+        });
+    }
+}
+
+impl NonvolatileStorageClient<'static> for ProcessCoreDump<'_> {
+    fn read_done(&self, buffer: &'static mut [u8], _length: usize) {
+        self.buffer.replace(buffer);
+    }
+
+    fn write_done(&self, buffer: &'static mut [u8], _length: usize) {
+        match self.state.get() {
+            State::WritingDump => {
+                buffer[0..4].copy_from_slice(&MAGIC.to_le_bytes());
+                self.state.set(State::WritingMagic);
+                if let Err((_, buffer)) = self.storage.write(buffer, self.region_start, 4) {
+                    self.write_failed(buffer);
+                }
+            }
+            State::WritingMagic | State::Idle => {
+                self.state.set(State::Idle);
+                self.buffer.replace(buffer);
+            }
+        }
+    }
+}
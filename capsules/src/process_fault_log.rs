@@ -0,0 +1,185 @@
+//! Records process faults in a persistent log.
+//!
+//! `ProcessFaultLog` is a `ProcessFaultHandler` that appends a compact record
+//! of every process fault to a log (for example a `capsules::log::Log`
+//! volume) and syncs it, so that faults that happen in the field survive a
+//! reboot and can be read back from the log later.
+//!
+//! Each fault is one log entry. All multi-byte fields are little-endian.
+//!
+//! ```text
+//! offset  size  field
+//!      0     1  record version (1)
+//!      1     1  flags, bit 0 set if fault_address is valid, bit 1 set if
+//!               the kernel faulted the process itself, in which case the
+//!               fault reason is 0
+//!      2     1  number of registers (N)
+//!      3     1  length of the process name (M)
+//!      4     4  process short ID
+//!      8     4  process restart count
+//!     12     4  fault reason (architecture-specific)
+//!     16     4  fault address
+//!     20     4  flash start
+//!     24     4  flash end
+//!     28     4  memory start
+//!     32     4  memory end
+//!     36     4  app break
+//!     40     4  kernel memory break
+//!     44     4  stack pointer
+//!     48   4*N  registers (architecture-specific order)
+//!  48+4N     M  process name (UTF-8, truncated to 32 bytes)
+//! ```
+//!
+//! Records are dropped if a fault happens while the previous record is still
+//! being written. The record is appended after the fault handlers return, so
+//! with `FaultResponse::Panic` the kernel panics before it is written. Use
+//! `Stop` or `Restart` instead. The same applies to the fault that makes a
+//! restart policy such as `ThresholdRestartThenPanic` give up and panic.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let fault_log = static_init!(
+//!     capsules::process_fault_log::ProcessFaultLog<'static, Log<'static, Flash>>,
+//!     capsules::process_fault_log::ProcessFaultLog::new(
+//!         log,
+//!         &mut capsules::process_fault_log::BUFFER
+//!     )
+//! );
+//! log.set_append_client(fault_log);
+//! board_kernel.add_fault_handler(fault_log, &process_management_capability);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::TakeCell;
+use kernel::hil::log::{LogWrite, LogWriteClient};
+use kernel::procs::{ProcessFaultHandler, ProcessMemoryMap, ProcessType};
+use kernel::syscall::FaultContext;
+use kernel::ReturnCode;
+
+/// Version of the record format written by this capsule.
+const RECORD_VERSION: u8 = 1;
+
+const FLAG_FAULT_ADDRESS_VALID: u8 = 0x1;
+const FLAG_KERNEL_FAULT: u8 = 0x2;
+
+/// Size of the fixed part of a record, before the registers.
+const HEADER_LEN: usize = 48;
+
+/// Process names longer than this are truncated in the record.
+const MAX_NAME_LEN: usize = 32;
+
+/// Length of the buffer needed for the largest record.
+pub const BUFFER_LEN: usize = HEADER_LEN + 4 * 32 + MAX_NAME_LEN;
+
+pub static mut BUFFER: [u8; BUFFER_LEN] = [0; BUFFER_LEN];
+
+pub struct ProcessFaultLog<'a, L: LogWrite<'a>> {
+    log: &'a L,
+    buffer: TakeCell<'static, [u8]>,
+    dropped_records: Cell<usize>,
+}
+
+impl<'a, L: LogWrite<'a>> ProcessFaultLog<'a, L> {
+    pub fn new(log: &'a L, buffer: &'static mut [u8]) -> ProcessFaultLog<'a, L> {
+        ProcessFaultLog {
+            log: log,
+            buffer: TakeCell::new(buffer),
+            dropped_records: Cell::new(0),
+        }
+    }
+
+    /// How many fault records could not be written since boot.
+    pub fn dropped_records(&self) -> usize {
+        self.dropped_records.get()
+    }
+}
+
+fn put_u32(buffer: &mut [u8], offset: usize, value: usize) {
+    buffer[offset..offset + 4].copy_from_slice(&(value as u32).to_le_bytes());
+}
+
+impl<'a, L: LogWrite<'a>> ProcessFaultHandler for ProcessFaultLog<'a, L> {
+    fn process_fault(
+        &self,
+        process: &dyn ProcessType,
+        context: &FaultContext,
+        memory_map: &ProcessMemoryMap,
+    ) {
+        let buffer = match self.buffer.take() {
+            Some(buffer) => buffer,
+            None => {
+                // Still writing the previous record.
+                self.dropped_records.set(self.dropped_records.get() + 1);
+                return;
+            }
+        };
+
+        let num_registers = core::cmp::min(context.num_registers, context.registers.len());
+        let name = process.get_process_name().as_bytes();
+        let name_len = core::cmp::min(name.len(), MAX_NAME_LEN);
+        let length = HEADER_LEN + 4 * num_registers + name_len;
+        if buffer.len() < length {
+            self.buffer.replace(buffer);
+            self.dropped_records.set(self.dropped_records.get() + 1);
+            return;
+        }
+
+        buffer[0] = RECORD_VERSION;
+        let mut flags = 0;
+        if context.fault_address.is_some() {
+            flags |= FLAG_FAULT_ADDRESS_VALID;
+        }
+        if !context.hardware_fault {
+            flags |= FLAG_KERNEL_FAULT;
+        }
+        buffer[1] = flags;
+        buffer[2] = num_registers as u8;
+        buffer[3] = name_len as u8;
+        put_u32(buffer, 4, process.short_id() as usize);
+        put_u32(buffer, 8, process.get_restart_count());
+        put_u32(buffer, 12, context.fault_reason);
+        put_u32(buffer, 16, context.fault_address.unwrap_or(0));
+        put_u32(buffer, 20, memory_map.flash_start);
+        put_u32(buffer, 24, memory_map.flash_end);
+        put_u32(buffer, 28, memory_map.memory_start);
+        put_u32(buffer, 32, memory_map.memory_end);
+        put_u32(buffer, 36, memory_map.app_break);
+        put_u32(buffer, 40, memory_map.kernel_memory_break);
+        put_u32(buffer, 44, memory_map.stack_pointer);
+        for (i, register) in context.registers[..num_registers].iter().enumerate() {
+            put_u32(buffer, HEADER_LEN + 4 * i, *register);
+        }
+        let name_offset = HEADER_LEN + 4 * num_registers;
+        buffer[name_offset..name_offset + name_len].copy_from_slice(&name[..name_len]);
+
+        if let Err((_, buffer)) = self.log.append(buffer, length) {
+            buffer.map(|buffer| self.buffer.replace(buffer));
+            self.dropped_records.set(self.dropped_records.get() + 1);
+        }
+    }
+}
+
+impl<'a, L: LogWrite<'a>> LogWriteClient for ProcessFaultLog<'a, L> {
+    fn append_done(
+        &self,
+        buffer: &'static mut [u8],
+        _length: usize,
+        _records_lost: bool,
+        error: ReturnCode,
+    ) {
+        self.buffer.replace(buffer);
+        if error == ReturnCode::SUCCESS {
+            // Make the record persistent right away, since the reason we keep
+            // it is to survive a reboot.
+            self.log.sync();
+        } else {
+            self.dropped_records.set(self.dropped_records.get() + 1);
+        }
+    }
+
+    fn sync_done(&self, _error: ReturnCode) {}
+
+    fn erase_done(&self, _error: ReturnCode) {}
+}
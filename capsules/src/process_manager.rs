@@ -0,0 +1,320 @@
+//! Lets a privileged app list, stop, start and restart processes.
+//!
+//! This gives userspace the process control that the process console gives a
+//! developer, so that a supervisor app can decide what to do about processes
+//! that misbehave instead of the policy being built into the kernel. Only apps
+//! whose TBF package name is in the list the board passes to the driver can
+//! use it; for all other apps every command and allow returns `ENOSUPPORT`.
+//!
+//! The package name is a field in the TBF header that any app can set, so it
+//! only counts if the app was loaded with credentials that the board's
+//! `ProcessCredentialsPolicy` verified, as the credentials cover the header.
+//! On a board that loads apps with `NoCredentialsCheck` no app is privileged.
+//!
+//! Processes are identified by their `AppId` identifier. A restarted process
+//! gets a new identifier, so a supervisor notices when a process it is looking
+//! at restarted. The identifiers are found by enumerating the process slots
+//! with command `2`.
+//!
+//! Commands that return more than one value use the second to fourth return
+//! registers (see `SyscallReturn`).
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! # use kernel::static_init;
+//!
+//! struct ProcessMgmtCap;
+//! unsafe impl capabilities::ProcessManagementCapability for ProcessMgmtCap {}
+//!
+//! let process_manager = static_init!(
+//!     capsules::process_manager::ProcessManager<ProcessMgmtCap>,
+//!     capsules::process_manager::ProcessManager::new(
+//!         board_kernel,
+//!         &["supervisor"],
+//!         board_kernel.create_grant(&grant_cap),
+//!         ProcessMgmtCap
+//!     )
+//! );
+//! ```
+
+use core::cell::Cell;
+use core::cmp;
+use kernel::capabilities::ProcessManagementCapability;
+use kernel::introspection::KernelInfo;
+use kernel::procs::{ProcessType, State};
+use kernel::{AppId, AppSlice, Driver, Grant, Kernel, ReturnCode, Shared, SyscallReturn};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::ProcessManager as usize;
+
+/// The number userspace sees for each process state.
+fn state_number(state: State) -> usize {
+    match state {
+        State::Unstarted => 0,
+        State::Running => 1,
+        State::Yielded => 2,
+        State::StoppedRunning => 3,
+        State::StoppedYielded => 4,
+        State::StoppedFaulted => 5,
+        State::Fault => 6,
+        State::PendingRestart => 7,
+        State::Terminated => 8,
+    }
+}
+
+#[derive(Default)]
+pub struct App {
+    name_buffer: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct ProcessManager<C: ProcessManagementCapability> {
+    kernel: &'static Kernel,
+    kernel_info: KernelInfo,
+    privileged_apps: &'static [&'static str],
+    apps: Grant<App>,
+    capability: C,
+}
+
+impl<C: ProcessManagementCapability> ProcessManager<C> {
+    pub fn new(
+        kernel: &'static Kernel,
+        privileged_apps: &'static [&'static str],
+        grant: Grant<App>,
+        capability: C,
+    ) -> ProcessManager<C> {
+        ProcessManager {
+            kernel,
+            kernel_info: KernelInfo::new(kernel),
+            privileged_apps,
+            apps: grant,
+            capability,
+        }
+    }
+
+    fn is_privileged(&self, appid: AppId) -> bool {
+        let privileged = Cell::new(false);
+        self.kernel
+            .process_each_capability(&self.capability, |process| {
+                if process.appid() == appid {
+                    let name = process.get_process_name();
+                    privileged.set(
+                        process.has_verified_credentials()
+                            && self.privileged_apps.iter().any(|app| *app == name),
+                    );
+                }
+            });
+        privileged.get()
+    }
+
+    /// Get the identifier and state of the process in the `index`th used
+    /// process slot.
+    fn process_at(&self, index: usize) -> SyscallReturn {
+        let count = Cell::new(0);
+        let found = Cell::new(None);
+        self.kernel
+            .process_each_capability(&self.capability, |process| {
+                if count.get() == index {
+                    found.set(Some((
+                        process.appid().id(),
+                        state_number(process.get_state()),
+                    )));
+                }
+                count.set(count.get() + 1);
+            });
+        found
+            .get()
+            .map_or(ReturnCode::EINVAL.into(), |(id, state)| {
+                SyscallReturn::Success(id, state, 0)
+            })
+    }
+
+    /// Run `action` on the process with the identifier `id`. Returns `EINVAL`
+    /// if there is no such process.
+    fn with_process<F>(&self, id: usize, action: F) -> ReturnCode
+    where
+        F: Fn(&dyn ProcessType) -> ReturnCode,
+    {
+        let result = Cell::new(ReturnCode::EINVAL);
+        self.kernel
+            .process_each_capability(&self.capability, |process| {
+                if process.appid().id() == id {
+                    result.set(action(process));
+                }
+            });
+        result.get()
+    }
+
+    /// Get the statistics `stats` returns for the process with the identifier
+    /// `id`.
+    fn stats<F>(&self, id: usize, stats: F) -> SyscallReturn
+    where
+        F: Fn(AppId) -> (usize, usize, usize),
+    {
+        let appid = Cell::new(None);
+        self.with_process(id, |process| {
+            appid.set(Some(process.appid()));
+            ReturnCode::SUCCESS
+        });
+        appid.get().map_or(ReturnCode::EINVAL.into(), |appid| {
+            let (v0, v1, v2) = stats(appid);
+            SyscallReturn::Success(v0, v1, v2)
+        })
+    }
+
+    /// Copy the name of the process with the identifier `id` into the buffer
+    /// the caller shared.
+    fn copy_name(&self, caller: AppId, id: usize) -> SyscallReturn {
+        let name = Cell::new(None);
+        self.with_process(id, |process| {
+            name.set(Some(process.get_process_name()));
+            ReturnCode::SUCCESS
+        });
+        let name = match name.get() {
+            Some(name) => name,
+            None => return ReturnCode::EINVAL.into(),
+        };
+        self.apps
+            .enter(caller, |app, _| match app.name_buffer {
+                Some(ref mut buffer) => {
+                    let len = cmp::min(buffer.len(), name.len());
+                    buffer.as_mut()[..len].copy_from_slice(&name.as_bytes()[..len]);
+                    SyscallReturn::Success(name.len(), 0, 0)
+                }
+                None => ReturnCode::ERESERVE.into(),
+            })
+            .unwrap_or_else(|err| ReturnCode::from(err).into())
+    }
+}
+
+impl<C: ProcessManagementCapability> Driver for ProcessManager<C> {
+    /// Setup shared buffers.
+    ///
+    /// ### `allow_num`
+    ///
+    /// - `0`: Buffer that command `7` copies process names into.
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        if !self.is_privileged(appid) {
+            return ReturnCode::ENOSUPPORT;
+        }
+        match allow_num {
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.name_buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Command interface.
+    ///
+    /// `data1` is a process identifier for all commands from `3`.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Return SUCCESS if this driver is included on the platform and
+    ///        the calling app may use it.
+    /// - `1`: Return the number of processes.
+    /// - `2`: Return the identifier and state of the process in the `data1`th
+    ///        process slot. The states are 0 unstarted, 1 running, 2 yielded,
+    ///        3 stopped while running, 4 stopped while yielded, 5 stopped after
+    ///        a fault, 6 faulted, 7 waiting to be restarted and 8 exited.
+    /// - `3`: Return the number of system calls, dropped callbacks and
+    ///        restarts of the process.
+    /// - `4`: Return the number of times the process ran, its CPU time in
+    ///        milliseconds and its longest run in microseconds.
+    /// - `5`: Return the number of times the process exceeded its timeslice
+    ///        and its execution budget.
+    /// - `6`: Return 1 and the completion code of the process if it exited,
+    ///        or 0 if it did not.
+    /// - `7`: Copy the name of the process into the buffer shared with
+    ///        allow `0`, and return the length of the name. The name is cut
+    ///        off if it does not fit.
+    /// - `10`: Stop the process.
+    /// - `11`: Start the process after it was stopped.
+    /// - `12`: Terminate the process and start it again. Returns EINVAL for
+    ///         the calling process, which can use the exit system call
+    ///         instead.
+    fn command_with_values(
+        &self,
+        command_num: usize,
+        data1: usize,
+        _: usize,
+        appid: AppId,
+    ) -> SyscallReturn {
+        if !self.is_privileged(appid) {
+            return ReturnCode::ENOSUPPORT.into();
+        }
+        match command_num {
+            0 => ReturnCode::SUCCESS.into(),
+            1 => SyscallReturn::Success(
+                self.kernel_info.number_loaded_processes(&self.capability),
+                0,
+                0,
+            ),
+            2 => self.process_at(data1),
+            3 => self.stats(data1, |app| {
+                (
+                    self.kernel_info.number_app_syscalls(app, &self.capability),
+                    self.kernel_info
+                        .number_app_dropped_callbacks(app, &self.capability),
+                    self.kernel_info.number_app_restarts(app, &self.capability),
+                )
+            }),
+            4 => self.stats(data1, |app| {
+                (
+                    self.kernel_info.number_app_runs(app, &self.capability),
+                    (self.kernel_info.app_cpu_time_us(app, &self.capability) / 1000) as usize,
+                    self.kernel_info.app_longest_run_us(app, &self.capability) as usize,
+                )
+            }),
+            5 => self.stats(data1, |app| {
+                (
+                    self.kernel_info
+                        .number_app_timeslice_expirations(app, &self.capability),
+                    self.kernel_info
+                        .number_app_budget_overruns(app, &self.capability),
+                    0,
+                )
+            }),
+            6 => self.stats(data1, |app| {
+                self.kernel_info
+                    .app_completion_code(app, &self.capability)
+                    .map_or((0, 0, 0), |code| (1, code, 0))
+            }),
+            7 => self.copy_name(appid, data1),
+            10 => self
+                .with_process(data1, |process| {
+                    process.stop();
+                    ReturnCode::SUCCESS
+                })
+                .into(),
+            11 => self
+                .with_process(data1, |process| {
+                    process.resume();
+                    ReturnCode::SUCCESS
+                })
+                .into(),
+            12 => self
+                .with_process(data1, |process| {
+                    if process.appid() == appid {
+                        return ReturnCode::EINVAL;
+                    }
+                    process.restart_now();
+                    ReturnCode::SUCCESS
+                })
+                .into(),
+            _ => ReturnCode::ENOSUPPORT.into(),
+        }
+    }
+}
@@ -1105,7 +1105,7 @@ impl<'a, S: spi::SpiMasterDevice> RF233<'a, S> {
         let rbuf = self.spi_rx.take().unwrap();
         wbuf[0] = (reg as u8) | RF233BusCommand::REGISTER_WRITE as u8;
         wbuf[1] = val;
-        self.spi.read_write_bytes(wbuf, Some(rbuf), 2);
+        let _ = self.spi.read_write_bytes(wbuf, Some(rbuf), 2);
         self.spi_busy.set(true);
 
         ReturnCode::SUCCESS
@@ -1120,7 +1120,7 @@ impl<'a, S: spi::SpiMasterDevice> RF233<'a, S> {
         let rbuf = self.spi_rx.take().unwrap();
         wbuf[0] = (reg as u8) | RF233BusCommand::REGISTER_READ as u8;
         wbuf[1] = 0;
-        self.spi.read_write_bytes(wbuf, Some(rbuf), 2);
+        let _ = self.spi.read_write_bytes(wbuf, Some(rbuf), 2);
         self.spi_busy.set(true);
 
         ReturnCode::SUCCESS
@@ -1133,7 +1133,7 @@ impl<'a, S: spi::SpiMasterDevice> RF233<'a, S> {
 
         let buf_len = radio::PSDU_OFFSET + frame_len as usize;
         buf[0] = RF233BusCommand::FRAME_WRITE as u8;
-        self.spi.read_write_bytes(buf, self.spi_buf.take(), buf_len);
+        let _ = self.spi.read_write_bytes(buf, self.spi_buf.take(), buf_len);
         self.spi_busy.set(true);
         ReturnCode::SUCCESS
     }
@@ -1146,7 +1146,7 @@ impl<'a, S: spi::SpiMasterDevice> RF233<'a, S> {
         let buf_len = radio::PSDU_OFFSET + frame_len as usize;
         let wbuf = self.spi_buf.take().unwrap();
         wbuf[0] = RF233BusCommand::FRAME_READ as u8;
-        self.spi.read_write_bytes(wbuf, Some(buf), buf_len);
+        let _ = self.spi.read_write_bytes(wbuf, Some(buf), buf_len);
         self.spi_busy.set(true);
         ReturnCode::SUCCESS
     }
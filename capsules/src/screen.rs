@@ -16,7 +16,9 @@ use kernel::common::cells::{OptionalCell, TakeCell};
 use kernel::hil;
 use kernel::hil::screen::{ScreenPixelFormat, ScreenRotation};
 use kernel::ReturnCode;
-use kernel::{AppId, AppSlice, Callback, Driver, Grant, Shared};
+use kernel::{
+    AppId, AppSlice, Callback, Driver, Grant, ReadOnlyAppSlice, ReadableAppSlice, Shared,
+};
 
 /// Syscall driver number.
 use crate::driver;
@@ -76,7 +78,7 @@ fn pixels_in_bytes(pixels: usize, bits_per_pixel: usize) -> usize {
 pub struct App {
     callback: Option<Callback>,
     pending_command: bool,
-    shared: Option<AppSlice<Shared, u8>>,
+    shared: Option<ReadableAppSlice<u8>>,
     write_position: usize,
     write_len: usize,
     command: ScreenCommand,
@@ -378,6 +380,26 @@ impl<'a> Screen<'a> {
         }
     }
 
+    /// Share the buffer that holds the pixels to write or the fill color.
+    /// Only accepted if its length is a multiple of the color depth.
+    fn set_shared(&self, appid: AppId, slice: Option<ReadableAppSlice<u8>>) -> ReturnCode {
+        // TODO should refuse allow while writing
+        self.apps
+            .enter(appid, |app, _| {
+                let depth = pixels_in_bytes(1, self.screen.get_pixel_format().get_bits_per_pixel());
+                let len = if let Some(ref s) = slice { s.len() } else { 0 };
+                // allow only if the slice length is a a multiple of color depth
+                if len == 0 || (len > 0 && (len % depth == 0)) {
+                    app.shared = slice;
+                    app.write_position = 0;
+                    ReturnCode::SUCCESS
+                } else {
+                    ReturnCode::EINVAL
+                }
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+
     fn fill_next_buffer_for_write<'b>(&self, buffer: &'b mut [u8]) -> usize {
         self.current_app.map_or_else(
             || 0,
@@ -566,23 +588,21 @@ impl<'a> Driver for Screen<'a> {
         slice: Option<AppSlice<Shared, u8>>,
     ) -> ReturnCode {
         match allow_num {
-            // TODO should refuse allow while writing
-            0 => self
-                .apps
-                .enter(appid, |app, _| {
-                    let depth =
-                        pixels_in_bytes(1, self.screen.get_pixel_format().get_bits_per_pixel());
-                    let len = if let Some(ref s) = slice { s.len() } else { 0 };
-                    // allow only if the slice length is a a multiple of color depth
-                    if len == 0 || (len > 0 && (len % depth == 0)) {
-                        app.shared = slice;
-                        app.write_position = 0;
-                        ReturnCode::SUCCESS
-                    } else {
-                        ReturnCode::EINVAL
-                    }
-                })
-                .unwrap_or_else(|err| err.into()),
+            0 => self.set_shared(appid, slice.map(ReadableAppSlice::from)),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// The pixels to write or the fill color can also be shared read-only,
+    /// for example to draw images stored in the app's flash.
+    fn allow_readonly(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<ReadOnlyAppSlice<u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            0 => self.set_shared(appid, slice.map(ReadableAppSlice::from)),
             _ => ReturnCode::ENOSUPPORT,
         }
     }
@@ -78,7 +78,7 @@ impl<'a, S: SpiMasterDevice> Spi<'a, S> {
                 }
             });
         });
-        self.spi_master.read_write_bytes(
+        let _ = self.spi_master.read_write_bytes(
             self.kernel_write.take().unwrap(),
             self.kernel_read.take(),
             len,
@@ -425,7 +425,7 @@ impl<'a, A: Alarm<'a>> ST7735<'a, A> {
             || panic!("st7735: send command has no buffer"),
             |buffer| {
                 buffer[0] = cmd.id;
-                self.spi.read_write_bytes(buffer, None, 1);
+                let _ = self.spi.read_write_bytes(buffer, None, 1);
             },
         );
     }
@@ -438,7 +438,7 @@ impl<'a, A: Alarm<'a>> ST7735<'a, A> {
             || panic!("st7735: send command has no buffer"),
             |buffer| {
                 buffer[0] = cmd.id;
-                self.spi.read_write_bytes(buffer, None, 1);
+                let _ = self.spi.read_write_bytes(buffer, None, 1);
             },
         );
     }
@@ -456,7 +456,7 @@ impl<'a, A: Alarm<'a>> ST7735<'a, A> {
                         }
                     }
                     self.dc.set();
-                    self.spi.read_write_bytes(buffer, None, len);
+                    let _ = self.spi.read_write_bytes(buffer, None, len);
                 },
             );
         } else {
@@ -470,7 +470,7 @@ impl<'a, A: Alarm<'a>> ST7735<'a, A> {
             |buffer| {
                 self.status.set(Status::SendParametersSlice);
                 self.dc.set();
-                self.spi.read_write_bytes(buffer, None, len);
+                let _ = self.spi.read_write_bytes(buffer, None, len);
             },
         );
     }
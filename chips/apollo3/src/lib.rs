@@ -77,6 +77,7 @@ pub static IRQS: [unsafe extern "C" fn(); 32] = [generic_isr; 32];
 pub static PATCH: [unsafe extern "C" fn(); 16] = [unhandled_interrupt; 16];
 
 extern "C" {
+    static mut _sstack: usize;
     static mut _szero: usize;
     static mut _ezero: usize;
     static mut _etext: usize;
@@ -88,6 +89,7 @@ extern "C" {
 pub unsafe fn init() {
     tock_rt0::init_data(&mut _etext, &mut _srelocate, &mut _erelocate);
     tock_rt0::zero_bss(&mut _szero, &mut _ezero);
+    kernel::introspection::paint_stack(&mut _sstack, _estack as *mut usize);
 
     cachectrl::CACHECTRL.enable_cache();
 
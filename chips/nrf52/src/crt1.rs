@@ -18,6 +18,7 @@ extern "C" {
     static mut _etext: usize;
     static mut _ezero: usize;
     static mut _srelocate: usize;
+    static mut _sstack: usize;
     static mut _szero: usize;
     fn reset_handler();
 
@@ -147,6 +148,7 @@ pub unsafe extern "C" fn init() {
 
     tock_rt0::init_data(&mut _etext, &mut _srelocate, &mut _erelocate);
     tock_rt0::zero_bss(&mut _szero, &mut _ezero);
+    kernel::introspection::paint_stack(&mut _sstack, _estack as *mut usize);
 
     // Explicitly tell the core where Tock's vector table is located. If Tock is the
     // only thing on the chip then this is effectively a no-op. If, however, there is
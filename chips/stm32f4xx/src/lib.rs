@@ -60,6 +60,7 @@ pub static BASE_VECTORS: [unsafe extern "C" fn(); 16] = [
 ];
 
 extern "C" {
+    static mut _sstack: usize;
     static mut _szero: usize;
     static mut _ezero: usize;
     static mut _etext: usize;
@@ -70,6 +71,7 @@ extern "C" {
 pub unsafe fn init() {
     tock_rt0::init_data(&mut _etext, &mut _srelocate, &mut _erelocate);
     tock_rt0::zero_bss(&mut _szero, &mut _ezero);
+    kernel::introspection::paint_stack(&mut _sstack, _estack as *mut usize);
 
     cortexm4::nvic::disable_all();
     cortexm4::nvic::clear_all_pending();
@@ -4,7 +4,6 @@ use core::fmt;
 use core::ptr::NonNull;
 
 use crate::capabilities;
-use crate::config;
 use crate::debug;
 use crate::process;
 use crate::sched::Kernel;
@@ -140,6 +139,15 @@ impl AppId {
         self.identifier
     }
 
+    /// Get the persistent short ID of the app this `AppId` refers to.
+    ///
+    /// Unlike `id()`, the short ID does not change when the app restarts or
+    /// the board reboots. Returns `None` if the app no longer exists.
+    pub fn short_id(&self) -> Option<u32> {
+        self.kernel
+            .process_map_or(None, *self, |process| Some(process.short_id()))
+    }
+
     /// Returns the full address of the start and end of the flash region that
     /// the app owns and can write to. This includes the app's code and data and
     /// any padding at the end of the app. It does not include the TBF header,
@@ -210,7 +218,13 @@ impl Callback {
                     pc: self.fn_ptr.as_ptr() as usize,
                 }))
             });
-        if config::CONFIG.trace_syscalls {
+        let kernel = self.app_id.kernel;
+        let trace_syscalls = kernel.process_map_or(
+            kernel.debug_config().trace_syscalls(),
+            self.app_id,
+            |process| kernel.trace_syscalls_of(process),
+        );
+        if trace_syscalls {
             debug!(
                 "[{:?}] schedule[{:#x}:{}] @{:#x}({:#x}, {:#x}, {:#x}, {:#x}) = {}",
                 self.app_id,
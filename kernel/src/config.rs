@@ -22,19 +22,27 @@
 //! constants throughout the code, so for example a boolean condition used in an `if` block will in
 //! principle have a zero cost on the resulting binary - as if a Cargo feature was used instead.
 //! Some simple experiments on generated Tock code have confirmed this zero cost in practice.
+//!
+//! The debugging options are the exception: they only set the initial state of switches that can
+//! be changed while the kernel runs (see `DebugSwitch`), so that a misbehaving app can be
+//! diagnosed without rebuilding the kernel.
+
+use core::cell::Cell;
 
 /// Data structure holding compile-time configuration options.
 ///
 /// To change the configuration, modify the relevant values in the `CONFIG` constant object defined
 /// at the end of this file.
 pub(crate) struct Config {
-    /// Whether the kernel should trace syscalls to the debug output.
+    /// Whether the kernel should trace syscalls to the debug output when it boots.
     ///
     /// If enabled, the kernel will print a message in the debug output for each system call and
     /// callback, with details including the application ID, and system call or callback parameters.
+    /// For a trace that changes the timing of the system less, boards can register a
+    /// `SyscallTraceBuffer` with the kernel instead.
     pub(crate) trace_syscalls: bool,
 
-    /// Whether the kernel should show debugging output when loading processes.
+    /// Whether the kernel should show debugging output when loading processes when it boots.
     ///
     /// If enabled, the kernel will show from which addresses processes are loaded in flash and
     /// into which SRAM addresses. This can be useful to debug whether the kernel could
@@ -48,3 +56,74 @@ pub(crate) const CONFIG: Config = Config {
     trace_syscalls: false,
     debug_load_processes: false,
 };
+
+/// A kernel debugging switch that can be turned on and off while the kernel is running with
+/// `Kernel::set_debug_switch()`. The initial state comes from the compile-time configuration.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DebugSwitch {
+    /// Print every system call and callback of every process to the debug output. Tracing can also
+    /// be enabled for a single process with `ProcessType::debug_set_trace_syscalls()`.
+    TraceSyscalls,
+
+    /// Print where processes are loaded from and to.
+    DebugLoadProcesses,
+}
+
+impl DebugSwitch {
+    /// Every debugging switch.
+    pub const ALL: [DebugSwitch; 2] = [DebugSwitch::TraceSyscalls, DebugSwitch::DebugLoadProcesses];
+
+    /// The name of the switch, matching the name of its compile-time configuration option.
+    pub fn name(self) -> &'static str {
+        match self {
+            DebugSwitch::TraceSyscalls => "trace_syscalls",
+            DebugSwitch::DebugLoadProcesses => "debug_load_processes",
+        }
+    }
+
+    /// Look up a switch by its name.
+    pub fn from_name(name: &str) -> Option<DebugSwitch> {
+        DebugSwitch::ALL
+            .iter()
+            .find(|switch| switch.name() == name)
+            .copied()
+    }
+}
+
+/// Current state of the debugging switches.
+pub(crate) struct DebugConfig {
+    trace_syscalls: Cell<bool>,
+    debug_load_processes: Cell<bool>,
+}
+
+impl DebugConfig {
+    pub(crate) const fn new() -> DebugConfig {
+        DebugConfig {
+            trace_syscalls: Cell::new(CONFIG.trace_syscalls),
+            debug_load_processes: Cell::new(CONFIG.debug_load_processes),
+        }
+    }
+
+    fn switch(&self, switch: DebugSwitch) -> &Cell<bool> {
+        match switch {
+            DebugSwitch::TraceSyscalls => &self.trace_syscalls,
+            DebugSwitch::DebugLoadProcesses => &self.debug_load_processes,
+        }
+    }
+
+    pub(crate) fn get(&self, switch: DebugSwitch) -> bool {
+        self.switch(switch).get()
+    }
+
+    pub(crate) fn set(&self, switch: DebugSwitch, enabled: bool) {
+        self.switch(switch).set(enabled);
+    }
+
+    pub(crate) fn trace_syscalls(&self) -> bool {
+        self.trace_syscalls.get()
+    }
+
+    pub(crate) fn debug_load_processes(&self) -> bool {
+        self.debug_load_processes.get()
+    }
+}
@@ -14,6 +14,9 @@
 //!
 //!   * `allow` provides the driver access to an application buffer.
 //!
+//!   * `allow_readonly` provides the driver read-only access to an application
+//!     buffer, which may be in flash.
+//!
 //! ## Mapping system-calls to drivers
 //!
 //! Each of these three system calls takes at least two parameters. The first is
@@ -39,8 +42,9 @@
 //! understand its function and how it interacts with `subscribe`.
 
 use crate::callback::{AppId, Callback};
-use crate::mem::{AppSlice, Shared};
+use crate::mem::{AppSlice, ReadOnlyAppSlice, Shared};
 use crate::returncode::ReturnCode;
+use crate::syscall::SyscallReturn;
 
 /// `Driver`s implement the three driver-specific system calls: `subscribe`,
 /// `command` and `allow`.
@@ -96,6 +100,22 @@ pub trait Driver {
         ReturnCode::ENOSUPPORT
     }
 
+    /// `command_with_values` is `command` for commands that return more than
+    /// one value. The kernel calls this for the `command` system call, and by
+    /// default it calls `command` and returns its `ReturnCode` unchanged, so
+    /// drivers only implement it if some of their commands return several
+    /// values. Those drivers can handle all their commands here, or return
+    /// `self.command(...).into()` for the others.
+    fn command_with_values(
+        &self,
+        minor_num: usize,
+        r2: usize,
+        r3: usize,
+        caller_id: AppId,
+    ) -> SyscallReturn {
+        self.command(minor_num, r2, r3, caller_id).into()
+    }
+
     /// `allow` lets an application give the driver access to a buffer in the
     /// application's memory. This returns `ENOSUPPORT` if not used.
     ///
@@ -111,4 +131,20 @@ pub trait Driver {
     ) -> ReturnCode {
         ReturnCode::ENOSUPPORT
     }
+
+    /// `allow_readonly` lets an application give the driver read-only access
+    /// to a buffer in the application's memory or flash. This returns
+    /// `ENOSUPPORT` if not used.
+    ///
+    /// Read-only buffers are separate from the buffers passed to `allow`, and
+    /// a driver may accept both for the same `minor_num`.
+    #[allow(unused_variables)]
+    fn allow_readonly(
+        &self,
+        app: AppId,
+        minor_num: usize,
+        slice: Option<ReadOnlyAppSlice<u8>>,
+    ) -> ReturnCode {
+        ReturnCode::ENOSUPPORT
+    }
 }
@@ -1,12 +1,12 @@
 //! Data structure to store a list of userspace applications.
 
 use core::marker::PhantomData;
-use core::mem::{align_of, size_of};
+use core::mem::{self, align_of, size_of};
 use core::ops::{Deref, DerefMut};
 use core::ptr::{write, NonNull};
 
 use crate::callback::AppId;
-use crate::process::{Error, ProcessType};
+use crate::process::{Error, ProcessSlot, ProcessType};
 use crate::sched::Kernel;
 
 /// Region of process memory reserved for the kernel.
@@ -30,7 +30,11 @@ impl<T> AppliedGrant<T> {
     {
         let mut allocator = Allocator { appid: self.appid };
         let mut root = Owned::new(self.grant, self.appid);
-        fun(&mut root, &mut allocator)
+        let res = fun(&mut root, &mut allocator);
+        // The grant region stays allocated for the lifetime of the process,
+        // so it must not be freed when `root` goes out of scope.
+        mem::forget(root);
+        res
     }
 }
 
@@ -44,7 +48,7 @@ pub struct Owned<T: ?Sized> {
 }
 
 impl<T: ?Sized> Owned<T> {
-    fn new(data: NonNull<T>, appid: AppId) -> Owned<T> {
+    pub(crate) fn new(data: NonNull<T>, appid: AppId) -> Owned<T> {
         Owned {
             data: data,
             appid: appid,
@@ -81,6 +85,8 @@ impl<T: ?Sized> DerefMut for Owned<T> {
 }
 
 impl Allocator {
+    /// Allocate a `T` in the process's grant region. The memory is freed and
+    /// can be reused when the returned `Owned` is dropped.
     pub fn alloc<T: Default>(&mut self) -> Result<Owned<T>, Error> {
         unsafe {
             let ptr = self.alloc_unowned()?;
@@ -266,6 +272,7 @@ impl<T: Default> Grant<T> {
                 NonNull::new(grant_ptr).map(|grant| {
                     let mut root = Owned::new(grant.cast::<T>(), process.appid());
                     fun(&mut root);
+                    mem::forget(root);
                 });
             }
         });
@@ -284,8 +291,8 @@ impl<T: Default> Grant<T> {
 pub struct Iter<'a, T: 'a + Default> {
     grant: &'a Grant<T>,
     subiter: core::iter::FilterMap<
-        core::slice::Iter<'a, Option<&'static dyn ProcessType>>,
-        fn(&Option<&'static dyn ProcessType>) -> Option<&'static dyn ProcessType>,
+        core::slice::Iter<'a, ProcessSlot>,
+        fn(&ProcessSlot) -> Option<&'static dyn ProcessType>,
     >,
 }
 
@@ -135,12 +135,15 @@ pub trait SpiMasterDevice {
     /// read_buffer may be None. If read_buffer is Some, the
     /// length of the operation is the minimum of the size of
     /// the two buffers.
+    ///
+    /// If the operation cannot be started the buffers are returned with the
+    /// error.
     fn read_write_bytes(
         &self,
         write_buffer: &'static mut [u8],
         read_buffer: Option<&'static mut [u8]>,
         len: usize,
-    ) -> ReturnCode;
+    ) -> Result<(), (ReturnCode, &'static mut [u8], Option<&'static mut [u8]>)>;
 
     fn set_polarity(&self, cpol: ClockPolarity);
     fn set_phase(&self, cpal: ClockPhase);
@@ -11,6 +11,8 @@
 //! correct capabilities to can use it.
 
 use core::cell::Cell;
+use core::cmp;
+use core::mem::size_of;
 
 use crate::callback::AppId;
 use crate::capabilities::ProcessManagementCapability;
@@ -19,6 +21,12 @@ use crate::process;
 use crate::sched::Kernel;
 
 /// This struct provides the inspection functions.
+///
+/// The CPU time and longest run of a process are measured with the scheduler
+/// timer, so they only count runs the scheduler gave a timeslice. Under
+/// `PrioritySched`, `CooperativeSched`, or any other scheduler that runs
+/// processes without a timeslice, they stay 0. A chip without a real
+/// scheduler timer does not measure them either.
 pub struct KernelInfo {
     kernel: &'static Kernel,
 }
@@ -113,6 +121,17 @@ impl KernelInfo {
             .process_map_or(0, app, |process| process.get_restart_count())
     }
 
+    /// Returns the completion code this app passed when it last called the
+    /// exit syscall, or `None` if it has not exited.
+    pub fn app_completion_code(
+        &self,
+        app: AppId,
+        _capability: &dyn ProcessManagementCapability,
+    ) -> Option<usize> {
+        self.kernel
+            .process_map_or(None, app, |process| process.get_completion_code())
+    }
+
     /// Returns the number of time this app has exceeded its timeslice.
     pub fn number_app_timeslice_expirations(
         &self,
@@ -123,6 +142,50 @@ impl KernelInfo {
             .process_map_or(0, app, |process| process.debug_timeslice_expiration_count())
     }
 
+    /// Returns the number of times this app has been scheduled to run since
+    /// the kernel started.
+    pub fn number_app_runs(
+        &self,
+        app: AppId,
+        _capability: &dyn ProcessManagementCapability,
+    ) -> usize {
+        self.kernel
+            .process_map_or(0, app, |process| process.debug_scheduled_count())
+    }
+
+    /// Returns the CPU time in microseconds this app has used since the kernel
+    /// started. Only runs that the scheduler gave a timeslice are measured, so
+    /// this is 0 under schedulers without timeslices.
+    pub fn app_cpu_time_us(
+        &self,
+        app: AppId,
+        _capability: &dyn ProcessManagementCapability,
+    ) -> u64 {
+        self.kernel
+            .process_map_or(0, app, |process| process.debug_cpu_time_us())
+    }
+
+    /// Returns the longest time in microseconds this app ran for at once.
+    pub fn app_longest_run_us(
+        &self,
+        app: AppId,
+        _capability: &dyn ProcessManagementCapability,
+    ) -> u32 {
+        self.kernel
+            .process_map_or(0, app, |process| process.debug_longest_run_us())
+    }
+
+    /// Returns the number of times this app has used up its execution budget
+    /// for a period, if the scheduler gives processes a budget.
+    pub fn number_app_budget_overruns(
+        &self,
+        app: AppId,
+        _capability: &dyn ProcessManagementCapability,
+    ) -> usize {
+        self.kernel
+            .process_map_or(0, app, |process| process.debug_budget_overrun_count())
+    }
+
     /// Returns a tuple of the (the number of grants in the grant region this
     /// app has allocated, total number of grants that exist in the system).
     pub fn number_app_grant_uses(
@@ -159,4 +222,121 @@ impl KernelInfo {
         });
         count.get()
     }
+
+    /// Returns the total number of times all processes have used up their
+    /// execution budgets.
+    pub fn budget_overruns(&self, _capability: &dyn ProcessManagementCapability) -> usize {
+        let count: Cell<usize> = Cell::new(0);
+        self.kernel.process_each(|proc| {
+            count.add(proc.debug_budget_overrun_count());
+        });
+        count.get()
+    }
+
+    /// Returns the total CPU time in microseconds all processes have used
+    /// since the kernel started. Like `app_cpu_time_us()`, this only counts
+    /// runs that the scheduler gave a timeslice.
+    pub fn cpu_time_us(&self, _capability: &dyn ProcessManagementCapability) -> u64 {
+        let total: Cell<u64> = Cell::new(0);
+        self.kernel.process_each(|proc| {
+            total.set(total.get() + proc.debug_cpu_time_us());
+        });
+        total.get()
+    }
+
+    /// Returns the deepest the kernel stack has grown since boot and the size
+    /// of the stack, both in bytes, or `None` if the board did not pass the
+    /// stack to `Kernel::set_kernel_stack()`.
+    pub fn kernel_stack_usage(
+        &self,
+        _capability: &dyn ProcessManagementCapability,
+    ) -> Option<(usize, usize)> {
+        self.kernel
+            .kernel_stack()
+            .map(|(start, end)| unsafe { (stack_high_water_mark(start, end), end - start) })
+    }
+}
+
+/// The pattern the unused part of the kernel stack is filled with.
+const STACK_PAINT: usize = 0xDEAD_BEEF;
+
+/// Space below the current stack pointer that `paint_stack()` leaves alone,
+/// so it does not overwrite its own stack frame.
+const STACK_PAINT_MARGIN: usize = 64;
+
+/// Fills the unused part of the kernel stack with a known pattern, so that
+/// `KernelInfo::kernel_stack_usage()` can later tell how deep the stack has
+/// grown. Chips call this from their reset handler after zeroing the BSS,
+/// while the stack is still shallow.
+///
+/// - `start_stack`: The lowest address of the stack.
+/// - `end_stack`:   The first address after the top of the stack.
+#[inline(never)]
+pub unsafe fn paint_stack(start_stack: *mut usize, end_stack: *mut usize) {
+    let here = 0usize;
+    let limit = (&here as *const usize as usize).saturating_sub(STACK_PAINT_MARGIN);
+    let limit = cmp::min(limit, end_stack as usize);
+    let mut word = start_stack;
+    while (word as usize) < limit {
+        // `volatile` to make sure it doesn't get optimized out
+        word.write_volatile(STACK_PAINT);
+        word = word.offset(1);
+    }
+}
+
+/// Returns how many bytes at the top of the stack from `start` to `end` are
+/// no longer painted, which is the deepest the stack has grown.
+unsafe fn stack_high_water_mark(start: usize, end: usize) -> usize {
+    let align = size_of::<usize>();
+    let mut word = ((start + align - 1) / align * align) as *const usize;
+    while (word as usize) < end && word.read_volatile() == STACK_PAINT {
+        word = word.offset(1);
+    }
+    end.saturating_sub(word as usize)
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::{paint_stack, stack_high_water_mark, STACK_PAINT};
+    use core::mem::size_of;
+    use std::vec;
+
+    fn range(stack: &[usize]) -> (usize, usize) {
+        let start = stack.as_ptr() as usize;
+        (start, start + stack.len() * size_of::<usize>())
+    }
+
+    #[test]
+    fn high_water_mark_is_the_unpainted_top_of_the_stack() {
+        let mut stack = [STACK_PAINT; 16];
+        let (start, end) = range(&stack);
+        assert_eq!(unsafe { stack_high_water_mark(start, end) }, 0);
+
+        for word in stack[13..].iter_mut() {
+            *word = 0;
+        }
+        let (start, end) = range(&stack);
+        assert_eq!(
+            unsafe { stack_high_water_mark(start, end) },
+            3 * size_of::<usize>()
+        );
+
+        stack[0] = 0;
+        let (start, end) = range(&stack);
+        assert_eq!(unsafe { stack_high_water_mark(start, end) }, end - start);
+    }
+
+    #[test]
+    fn painting_stays_within_the_stack() {
+        // A heap buffer, which is below the stack of this test, stands in for
+        // the part of the stack below the stack pointer.
+        let mut memory = vec![0usize; 18];
+        let stack = &mut memory[1..17];
+        let (start, end) = range(stack);
+        unsafe { paint_stack(start as *mut usize, end as *mut usize) };
+        assert_eq!(unsafe { stack_high_water_mark(start, end) }, 0);
+        assert_eq!((memory[0], memory[17]), (0, 0));
+    }
 }
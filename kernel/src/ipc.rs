@@ -2,19 +2,44 @@
 //!
 //! This is a special syscall driver that allows userspace applications to
 //! share memory.
+//!
+//! Besides sharing memory, processes can exchange messages through the
+//! `Mailbox` driver. A process that wants to receive messages shares a
+//! receive buffer and divides it into a number of equally sized slots, one per
+//! message it can hold at once. When another process sends it a message, the
+//! kernel copies the message from the sender's send buffer into a free slot
+//! and remembers which process sent it, so a reply to that slot goes back to
+//! the sender. A full mailbox makes sends fail with `EBUSY` until the receiver
+//! frees a slot, which is how a slow service pushes back on its clients.
+//!
+//! A service can restrict which processes may use it by sharing a read-only
+//! list of the package names of its clients. Other processes cannot discover,
+//! share memory with or notify the service. A client that no longer wants the
+//! service to access the memory it shared can unshare it, which revokes the
+//! service's access and notifies the service.
+//!
+//! Processes are identified the same way for both drivers: by the number IPC
+//! service discovery returns, which is the process identifier plus one.
 
 use crate::callback::{AppId, Callback};
 use crate::capabilities::MemoryAllocationCapability;
 use crate::driver::Driver;
-use crate::grant::Grant;
-use crate::mem::{AppSlice, Shared};
-use crate::process;
+use crate::grant::{Grant, Owned};
+use crate::mem::{AppSlice, ReadOnlyAppSlice, ReadableAppSlice, Shared};
+use crate::platform::mpu;
+use crate::process::{self, Error};
 use crate::returncode::ReturnCode;
 use crate::sched::Kernel;
 
 /// Syscall number
 pub const DRIVER_NUM: usize = 0x10000;
 
+/// Syscall number of the mailbox driver.
+pub const MAILBOX_DRIVER_NUM: usize = 0x10001;
+
+/// The most messages a mailbox can hold at once.
+const MAILBOX_SLOTS: usize = 8;
+
 /// Enum to mark which type of callback is scheduled for the IPC mechanism.
 #[derive(Copy, Clone, Debug)]
 pub enum IPCCallbackType {
@@ -26,17 +51,103 @@ pub enum IPCCallbackType {
     Client,
 }
 
+/// State a process keeps about another process it communicates with over
+/// IPC. These are allocated in the grant region of the process as needed, so
+/// the number of processes a process can communicate with is only limited by
+/// the size of its grant region.
+#[derive(Default)]
+struct Peer {
+    /// The other application, or `None` if this entry is not in use.
+    app: Option<AppId>,
+    /// The app slice that this application has shared with the other
+    /// application.
+    shared_memory: Option<AppSlice<Shared, u8>>,
+    /// The MPU region that gives the other application access to
+    /// `shared_memory`, so that the access can be revoked.
+    exposed_region: Option<mpu::Region>,
+    /// The callback this process has registered to receive callbacks from the
+    /// other application as a service.
+    client_callback: Option<Callback>,
+    /// The next entry of the list.
+    next: Option<Owned<Peer>>,
+}
+
 /// State that is stored in each process's grant region to support IPC.
 #[derive(Default)]
 struct IPCData {
-    /// An array of app slices that this application has shared with other
-    /// applications.
-    shared_memory: [Option<AppSlice<Shared, u8>>; 8],
-    /// An array of callbacks this process has registered to receive callbacks
-    /// from other services.
-    client_callbacks: [Option<Callback>; 8],
+    /// The list of applications this application communicates with.
+    peers: Option<Owned<Peer>>,
     /// The callback setup by a service. Each process can only be one service.
     callback: Option<Callback>,
+    /// The comma separated package names of the processes that may use this
+    /// service, or `None` if every process may.
+    allowed_clients: Option<ReadOnlyAppSlice<u8>>,
+}
+
+impl IPCData {
+    /// Get the index of the first entry of the list for which `predicate`
+    /// returns true.
+    fn position<P>(&self, predicate: P) -> Option<usize>
+    where
+        P: Fn(&Peer) -> bool,
+    {
+        let mut link = &self.peers;
+        let mut index = 0;
+        while let Some(peer) = link {
+            if predicate(peer) {
+                return Some(index);
+            }
+            link = &peer.next;
+            index += 1;
+        }
+        None
+    }
+
+    /// Get the entry of the list at `index`.
+    fn nth(&mut self, index: usize) -> Option<&mut Peer> {
+        let mut link = &mut self.peers;
+        for _ in 0..index {
+            link = &mut link.as_mut()?.next;
+        }
+        link.as_mut().map(|peer| &mut **peer)
+    }
+
+    /// Get the state kept about `app`, if there is any.
+    fn peer(&mut self, app: AppId) -> Option<&mut Peer> {
+        let index = self.position(|peer| peer.app == Some(app))?;
+        self.nth(index)
+    }
+
+    /// Get the state kept about `app`, and create it if there is none.
+    ///
+    /// Entries of applications for which `is_gone` returns true, because they
+    /// exited or restarted, are reused before `alloc` is called to allocate a
+    /// new entry.
+    fn peer_or_insert<G, A>(&mut self, app: AppId, is_gone: G, alloc: A) -> Result<&mut Peer, Error>
+    where
+        G: Fn(AppId) -> bool,
+        A: FnOnce() -> Result<Owned<Peer>, Error>,
+    {
+        if let Some(index) = self.position(|peer| peer.app == Some(app)) {
+            return self.nth(index).ok_or(Error::KernelError);
+        }
+
+        if let Some(index) = self.position(|peer| peer.app.map_or(true, |other| is_gone(other))) {
+            let peer = self.nth(index).ok_or(Error::KernelError)?;
+            let next = peer.next.take();
+            *peer = Peer {
+                app: Some(app),
+                next,
+                ..Peer::default()
+            };
+            return Ok(peer);
+        }
+
+        let mut peer = alloc()?;
+        peer.app = Some(app);
+        peer.next = self.peers.take();
+        Ok(self.peers.get_or_insert(peer))
+    }
 }
 
 /// The IPC mechanism struct.
@@ -64,10 +175,9 @@ impl IPC {
             .enter(appid, |mydata, _| {
                 let callback = match cb_type {
                     IPCCallbackType::Service => mydata.callback,
-                    IPCCallbackType::Client => match otherapp.index() {
-                        Some(i) => *mydata.client_callbacks.get(i).unwrap_or(&None),
-                        None => None,
-                    },
+                    IPCCallbackType::Client => {
+                        mydata.peer(otherapp).and_then(|peer| peer.client_callback)
+                    }
                 };
                 callback.map_or((), |mut callback| {
                     self.data
@@ -76,27 +186,24 @@ impl IPC {
                             // sure we have access to that slice and then call
                             // the callback. If no slice was shared then just
                             // call the callback.
-                            match appid.index() {
-                                Some(i) => {
-                                    if i >= otherdata.shared_memory.len() {
-                                        return;
-                                    }
-
-                                    match otherdata.shared_memory[i] {
-                                        Some(ref slice) => {
-                                            slice.expose_to(appid);
-                                            callback.schedule(
-                                                otherapp.id() + 1,
-                                                slice.len(),
-                                                slice.ptr() as usize,
-                                            );
-                                        }
-                                        None => {
-                                            callback.schedule(otherapp.id() + 1, 0, 0);
-                                        }
+                            match otherdata.peer(appid) {
+                                Some(Peer {
+                                    shared_memory: Some(slice),
+                                    exposed_region,
+                                    ..
+                                }) => {
+                                    if exposed_region.is_none() {
+                                        *exposed_region = slice.expose_to(appid);
                                     }
+                                    callback.schedule(
+                                        otherapp.id() + 1,
+                                        slice.len(),
+                                        slice.ptr() as usize,
+                                    );
+                                }
+                                _ => {
+                                    callback.schedule(otherapp.id() + 1, 0, 0);
                                 }
-                                None => {}
                             }
                         })
                         .unwrap_or(());
@@ -104,6 +211,76 @@ impl IPC {
             })
             .unwrap_or(());
     }
+
+    /// Check whether `client` may discover, share memory with and notify
+    /// `service`.
+    fn is_allowed_client(&self, service: AppId, client: AppId) -> bool {
+        if service == client {
+            return true;
+        }
+        let name = self
+            .data
+            .kernel
+            .process_map_or("", client, |process| process.get_process_name());
+        self.data
+            .enter(service, |data, _| match data.allowed_clients {
+                Some(ref names) => names
+                    .as_ref()
+                    .split(|c| *c == b',')
+                    .any(|allowed| allowed == name.as_bytes()),
+                None => true,
+            })
+            .unwrap_or(false)
+    }
+
+    /// Revoke the access of the application `peer` is about to the slice
+    /// shared with it. Returns FAIL if the application is still alive and
+    /// its MPU region could not be removed, in which case the region is kept
+    /// so that revoking can be retried.
+    fn revoke(&self, peer: &mut Peer) -> ReturnCode {
+        if let (Some(otherapp), Some(region)) = (peer.app, peer.exposed_region) {
+            // If the other application restarted or exited it has no access
+            // left to revoke.
+            let revoked = self
+                .data
+                .kernel
+                .process_map_or(Ok(()), otherapp, |process| {
+                    process.remove_mpu_region(region)
+                });
+            if revoked.is_err() {
+                return ReturnCode::FAIL;
+            }
+            peer.exposed_region = None;
+        }
+        ReturnCode::SUCCESS
+    }
+
+    /// Stop sharing memory with `otherapp` and notify it as a service.
+    fn unshare(&self, appid: AppId, otherapp: AppId) -> ReturnCode {
+        let ret = self
+            .data
+            .enter(appid, |data, _| {
+                data.peer(otherapp).map_or(ReturnCode::SUCCESS, |peer| {
+                    let ret = self.revoke(peer);
+                    if ret == ReturnCode::SUCCESS {
+                        peer.shared_memory = None;
+                    }
+                    ret
+                })
+            })
+            .unwrap_or(ReturnCode::EBUSY);
+        if ret != ReturnCode::SUCCESS {
+            return ret;
+        }
+        self.data
+            .kernel
+            .process_map_or(ReturnCode::EINVAL, otherapp, |target| {
+                match target.enqueue_task(process::Task::IPC((appid, IPCCallbackType::Service))) {
+                    true => ReturnCode::SUCCESS,
+                    false => ReturnCode::FAIL,
+                }
+            })
+    }
 }
 
 impl Driver for IPC {
@@ -145,20 +322,23 @@ impl Driver for IPC {
                 let app_identifier = svc_id - 1;
                 // We first have to see if that identifier corresponds to a
                 // valid application by asking the kernel to do a lookup for us.
-                let otherapp = self.data.kernel.lookup_app_by_identifier(app_identifier);
+                let otherapp = match self.data.kernel.lookup_app_by_identifier(app_identifier) {
+                    Some(otherapp) => otherapp,
+                    None => return ReturnCode::EINVAL,
+                };
 
                 self.data
-                    .enter(app_id, |data, _| {
-                        match otherapp.map_or(None, |oa| oa.index()) {
-                            Some(i) => {
-                                if i > 8 {
-                                    ReturnCode::EINVAL
-                                } else {
-                                    data.client_callbacks[i] = callback;
-                                    ReturnCode::SUCCESS
-                                }
+                    .enter(app_id, |data, allocator| {
+                        match data.peer_or_insert(
+                            otherapp,
+                            |app| app.index().is_none(),
+                            || allocator.alloc(),
+                        ) {
+                            Ok(peer) => {
+                                peer.client_callback = callback;
+                                ReturnCode::SUCCESS
                             }
-                            None => ReturnCode::EINVAL,
+                            Err(err) => err.into(),
                         }
                     })
                     .unwrap_or(ReturnCode::EBUSY)
@@ -172,7 +352,14 @@ impl Driver for IPC {
     /// In either case, the target_id is the same number as provided in a notify
     /// callback or as returned by allow.
     ///
-    /// Returns EINVAL if the other process doesn't exist.
+    /// Setting client_or_svc to 2 unshares the slice shared with the service
+    /// target_id. The service can no longer access the slice, and its
+    /// callback is called without a slice.
+    ///
+    /// Returns EINVAL if the other process doesn't exist, and ENOSUPPORT if
+    /// the target is a service that does not allow this process as a client.
+    /// Unsharing returns FAIL if the access of the service could not be
+    /// revoked; the slice then stays shared.
     fn command(
         &self,
         target_id: usize,
@@ -186,12 +373,21 @@ impl Driver for IPC {
             IPCCallbackType::Client
         };
 
-        let app_identifier = target_id - 1;
+        let app_identifier = match target_id.checked_sub(1) {
+            Some(app_identifier) => app_identifier,
+            None => return ReturnCode::EINVAL,
+        };
 
         self.data
             .kernel
             .lookup_app_by_identifier(app_identifier)
             .map_or(ReturnCode::EINVAL, |otherapp| {
+                if client_or_svc == 2 {
+                    return self.unshare(appid, otherapp);
+                }
+                if client_or_svc == 0 && !self.is_allowed_client(otherapp, appid) {
+                    return ReturnCode::ENOSUPPORT;
+                }
                 self.data
                     .kernel
                     .process_map_or(ReturnCode::EINVAL, otherapp, |target| {
@@ -209,14 +405,17 @@ impl Driver for IPC {
     ///
     /// If allow is called with target_id == 0, it is an IPC service discover
     /// call. The contents of the slice should be the string name of the IPC
-    /// service. If this mechanism can find that service, allow will return
-    /// an ID that can be used to notify that service. Otherwise an error will
-    /// be returned.
+    /// service. If this mechanism can find that service, and the service allows
+    /// this process as a client, allow will return an ID that can be used to
+    /// notify that service. Otherwise an error will be returned.
     ///
     /// If allow is called with target_id >= 1, it is a share command where the
     /// application is explicitly sharing a slice with an IPC service (as
     /// specified by the target_id). allow() simply allows both processes to
-    /// access the buffer, it does not signal the service.
+    /// access the buffer, it does not signal the service. Sharing a new slice
+    /// revokes access to the old one. Returns ENOSUPPORT if the service does
+    /// not allow this process as a client, and FAIL if access to the old
+    /// slice could not be revoked.
     fn allow(
         &self,
         appid: AppId,
@@ -231,6 +430,7 @@ impl Driver for IPC {
                         // are slices equal?
                         if s.len() == slice_data.len()
                             && s.iter().zip(slice_data.iter()).all(|(c1, c2)| c1 == c2)
+                            && self.is_allowed_client(p.appid(), appid)
                         {
                             ReturnCode::SuccessWithValue {
                                 value: (p.appid().id() as usize) + 1,
@@ -248,27 +448,581 @@ impl Driver for IPC {
 
             return ReturnCode::EINVAL; /* AppSlice must have non-zero length */
         }
+        // Lookup the app based on the passed in identifier. This also lets us
+        // check that the other app is actually valid.
+        let app_identifier = target_id - 1;
+        let otherapp = match self.data.kernel.lookup_app_by_identifier(app_identifier) {
+            Some(otherapp) => otherapp,
+            None => return ReturnCode::EINVAL,
+        };
+        if !self.is_allowed_client(otherapp, appid) {
+            return ReturnCode::ENOSUPPORT;
+        }
         self.data
-            .enter(appid, |data, _| {
-                // Lookup the index of the app based on the passed in
-                // identifier. This also let's us check that the other app is
-                // actually valid.
-                let app_identifier = target_id - 1;
-                let otherapp = self.data.kernel.lookup_app_by_identifier(app_identifier);
-
-                match otherapp.map_or(None, |oa| oa.index()) {
-                    Some(i) => {
-                        data.shared_memory.get_mut(i).map_or(
-                            ReturnCode::EINVAL, /* Target process does not exist */
-                            |smem| {
-                                *smem = slice;
-                                ReturnCode::SUCCESS
-                            },
-                        )
+            .enter(appid, |data, allocator| {
+                match data.peer_or_insert(
+                    otherapp,
+                    |app| app.index().is_none(),
+                    || allocator.alloc(),
+                ) {
+                    Ok(peer) => {
+                        let ret = self.revoke(peer);
+                        if ret == ReturnCode::SUCCESS {
+                            peer.shared_memory = slice;
+                        }
+                        ret
                     }
-                    None => ReturnCode::EINVAL,
+                    Err(err) => err.into(),
                 }
             })
             .unwrap_or(ReturnCode::EBUSY)
     }
+
+    /// allow_readonly with target_id == 0 sets the comma separated package
+    /// names of the processes that may use this process as a service. Without
+    /// a list, every process may. Changing the list does not revoke access to
+    /// slices that were shared before.
+    fn allow_readonly(
+        &self,
+        appid: AppId,
+        target_id: usize,
+        slice: Option<ReadOnlyAppSlice<u8>>,
+    ) -> ReturnCode {
+        match target_id {
+            0 => self
+                .data
+                .enter(appid, |data, _| {
+                    data.allowed_clients = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::EBUSY),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+/// State that is stored in each process's grant region to support mailbox
+/// IPC.
+#[derive(Default)]
+struct MailboxData {
+    /// The buffer messages sent to this process are copied into.
+    receive_buffer: Option<AppSlice<Shared, u8>>,
+    /// The buffer messages are copied from when this process sends a message
+    /// or a reply.
+    send_buffer: Option<ReadableAppSlice<u8>>,
+    /// Called when a message was copied into the receive buffer.
+    callback: Option<Callback>,
+    /// The number of slots the receive buffer is divided into. Zero if the
+    /// process does not accept messages.
+    depth: usize,
+    /// The process that sent the message in each slot, or `None` if the slot
+    /// is free.
+    senders: [Option<AppId>; MAILBOX_SLOTS],
+}
+
+impl MailboxData {
+    /// The largest message that fits into one slot.
+    fn slot_len(&self) -> usize {
+        match self.receive_buffer {
+            Some(ref buffer) if self.depth > 0 => buffer.len() / self.depth,
+            _ => 0,
+        }
+    }
+}
+
+/// The mailbox IPC mechanism struct.
+pub struct Mailbox {
+    /// The grant regions for each process that hold the per-process mailbox
+    /// data.
+    data: Grant<MailboxData>,
+}
+
+impl Mailbox {
+    pub fn new(kernel: &'static Kernel, capability: &dyn MemoryAllocationCapability) -> Mailbox {
+        Mailbox {
+            data: kernel.create_grant(capability),
+        }
+    }
+
+    /// Copy the first `len` bytes of the send buffer of `sender` into a free
+    /// slot of the mailbox of `receiver`, and schedule the mailbox callback of
+    /// `receiver`.
+    fn deliver(&self, sender: AppId, receiver: AppId, len: usize) -> ReturnCode {
+        // Both grants are entered at once, which must not happen for the same
+        // process.
+        if sender == receiver {
+            return ReturnCode::EINVAL;
+        }
+        self.data
+            .enter(sender, |sender_data, _| {
+                let message = match sender_data.send_buffer {
+                    Some(ref message) => message,
+                    None => return ReturnCode::ERESERVE,
+                };
+                if len > message.len() {
+                    return ReturnCode::EINVAL;
+                }
+                self.data
+                    .enter(receiver, |receiver_data, _| {
+                        let slot_len = receiver_data.slot_len();
+                        if receiver_data.depth == 0 || receiver_data.receive_buffer.is_none() {
+                            return ReturnCode::ERESERVE;
+                        }
+                        if len > slot_len {
+                            return ReturnCode::ESIZE;
+                        }
+                        let depth = receiver_data.depth;
+                        let slot = match receiver_data.senders[..depth]
+                            .iter()
+                            .position(|s| s.is_none())
+                        {
+                            Some(slot) => slot,
+                            None => return ReturnCode::EBUSY,
+                        };
+                        receiver_data.receive_buffer.as_mut().map(|buffer| {
+                            let start = slot * slot_len;
+                            buffer.as_mut()[start..start + len]
+                                .copy_from_slice(&message.as_ref()[..len]);
+                        });
+                        receiver_data.senders[slot] = Some(sender);
+                        receiver_data.callback.map(|mut callback| {
+                            callback.schedule(slot, len, sender.id() + 1);
+                        });
+                        ReturnCode::SUCCESS
+                    })
+                    .unwrap_or_else(|err| err.into())
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+
+    /// Send `len` bytes of the send buffer of `appid` to the process with the
+    /// IPC identifier `target_id`.
+    fn send(&self, appid: AppId, target_id: usize, len: usize) -> ReturnCode {
+        target_id
+            .checked_sub(1)
+            .and_then(|identifier| self.data.kernel.lookup_app_by_identifier(identifier))
+            .map_or(ReturnCode::EINVAL, |target| {
+                self.deliver(appid, target, len)
+            })
+    }
+
+    /// Send `len` bytes of the send buffer of `appid` to the process that sent
+    /// the message in `slot` of its mailbox, and free the slot.
+    ///
+    /// If the sender's mailbox is full the slot stays in use so that the
+    /// reply can be sent again later. If the sender exited or restarted since
+    /// it sent the message the reply has nowhere to go, so the slot is freed
+    /// and `EINVAL` returned.
+    fn reply(&self, appid: AppId, slot: usize, len: usize) -> ReturnCode {
+        let sender = self
+            .data
+            .enter(appid, |data, _| data.senders.get(slot).and_then(|s| *s))
+            .unwrap_or(None);
+        let sender = match sender {
+            Some(sender) => sender,
+            None => return ReturnCode::EINVAL,
+        };
+        let sender_exists = self.data.kernel.process_map_or(false, sender, |_| true);
+        let ret = if sender_exists {
+            self.deliver(appid, sender, len)
+        } else {
+            ReturnCode::EINVAL
+        };
+        if ret == ReturnCode::SUCCESS || !sender_exists {
+            self.release(appid, slot);
+        }
+        ret
+    }
+
+    /// Free `slot` of the mailbox of `appid`.
+    fn release(&self, appid: AppId, slot: usize) -> ReturnCode {
+        self.data
+            .enter(appid, |data, _| match data.senders.get_mut(slot) {
+                Some(sender) if sender.is_some() => {
+                    *sender = None;
+                    ReturnCode::SUCCESS
+                }
+                _ => ReturnCode::EINVAL,
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+}
+
+impl Driver for Mailbox {
+    /// Setup callbacks.
+    ///
+    /// ### `subscribe_num`
+    ///
+    /// - `0`: Called when a message was copied into the receive buffer, with
+    ///        the slot, the length of the message and the IPC identifier of
+    ///        the sender.
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        app_id: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self
+                .data
+                .enter(app_id, |data, _| {
+                    data.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Command interface.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Return SUCCESS if this driver is included on the platform.
+    /// - `1`: Accept messages, dividing the receive buffer into `data1` slots
+    ///        of equal size. At most 8 slots are supported, and 0 stops
+    ///        accepting messages. Messages that were not freed are dropped.
+    /// - `2`: Send the first `data2` bytes of the send buffer to the process
+    ///        with the IPC identifier `data1`. Returns ERESERVE if either
+    ///        process is missing a buffer, ESIZE if the message does not fit
+    ///        in a slot and EBUSY if all slots of the receiver are in use.
+    /// - `3`: Reply to the message in slot `data1` with the first `data2`
+    ///        bytes of the send buffer, and free the slot. Fails like command
+    ///        `2`, in which case the slot stays in use, unless the sender of
+    ///        the message no longer exists.
+    /// - `4`: Free slot `data1` without replying.
+    fn command(&self, command_num: usize, data1: usize, data2: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => {
+                if data1 > MAILBOX_SLOTS {
+                    return ReturnCode::EINVAL;
+                }
+                self.data
+                    .enter(appid, |data, _| {
+                        data.depth = data1;
+                        data.senders = Default::default();
+                        ReturnCode::SUCCESS
+                    })
+                    .unwrap_or_else(|err| err.into())
+            }
+            2 => self.send(appid, data1, data2),
+            3 => self.reply(appid, data1, data2),
+            4 => self.release(appid, data1),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Setup shared buffers.
+    ///
+    /// ### `allow_num`
+    ///
+    /// - `0`: Receive buffer. Sharing a new buffer drops all messages in the
+    ///        old one.
+    /// - `1`: Send buffer.
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .data
+                .enter(appid, |data, _| {
+                    data.receive_buffer = slice;
+                    data.senders = Default::default();
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            1 => self
+                .data
+                .enter(appid, |data, _| {
+                    data.send_buffer = slice.map(ReadableAppSlice::from);
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Setup read-only buffers.
+    ///
+    /// ### `allow_num`
+    ///
+    /// - `1`: Send buffer, so that messages can be sent from flash.
+    fn allow_readonly(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<ReadOnlyAppSlice<u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            1 => self
+                .data
+                .enter(appid, |data, _| {
+                    data.send_buffer = slice.map(ReadableAppSlice::from);
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::{IPCData, Mailbox, Peer};
+    use crate::callback::AppId;
+    use crate::driver::Driver;
+    use crate::grant::Owned;
+    use crate::process::{Error, ProcessType};
+    use crate::returncode::ReturnCode;
+    use crate::sched::Kernel;
+    use crate::test_util;
+    use core::cell::Cell;
+    use core::ptr::NonNull;
+    use std::boxed::Box;
+
+    /// More processes than fit into the fixed size arrays IPC used to have.
+    const NUM_PROCS: usize = 32;
+
+    fn kernel() -> &'static Kernel {
+        Box::leak(Box::new(Kernel::new(&mut [])))
+    }
+
+    fn alloc(app: AppId) -> Result<Owned<Peer>, Error> {
+        let peer = NonNull::from(Box::leak(Box::new(Peer::default())));
+        Ok(Owned::new(peer, app))
+    }
+
+    fn no_alloc() -> Result<Owned<Peer>, Error> {
+        panic!("unexpected allocation");
+    }
+
+    fn len(data: &IPCData) -> usize {
+        let len = Cell::new(0);
+        data.position(|_| {
+            len.set(len.get() + 1);
+            false
+        });
+        len.get()
+    }
+
+    #[test]
+    fn many_peers() {
+        let kernel = kernel();
+        let owner = AppId::new(kernel, 0, 0);
+        let mut data = IPCData::default();
+
+        for index in 0..NUM_PROCS {
+            let app = AppId::new(kernel, 100 + index, index);
+            let peer = data.peer_or_insert(app, |_| false, || alloc(owner));
+            assert_eq!(peer.map(|peer| peer.app), Ok(Some(app)));
+        }
+        assert_eq!(len(&data), NUM_PROCS);
+
+        for index in 0..NUM_PROCS {
+            let app = AppId::new(kernel, 100 + index, index);
+            assert_eq!(data.peer(app).map(|peer| peer.app), Some(Some(app)));
+            let peer = data.peer_or_insert(app, |_| false, no_alloc);
+            assert_eq!(peer.map(|peer| peer.app), Ok(Some(app)));
+        }
+        assert_eq!(len(&data), NUM_PROCS);
+
+        assert!(data.peer(AppId::new(kernel, 100 + NUM_PROCS, 0)).is_none());
+    }
+
+    #[test]
+    fn restarted_process_is_a_new_peer() {
+        let kernel = kernel();
+        let owner = AppId::new(kernel, 0, 0);
+        let old = AppId::new(kernel, 1, 3);
+        let new = AppId::new(kernel, 2, 3);
+        let mut data = IPCData::default();
+
+        assert!(data.peer_or_insert(old, |_| false, || alloc(owner)).is_ok());
+        assert!(data.peer(new).is_none());
+
+        // While the old process is still around the entries must not be
+        // shared, even though both processes have the same index.
+        assert!(data.peer_or_insert(new, |_| false, || alloc(owner)).is_ok());
+        assert_eq!(len(&data), 2);
+        assert_eq!(data.peer(old).map(|peer| peer.app), Some(Some(old)));
+        assert_eq!(data.peer(new).map(|peer| peer.app), Some(Some(new)));
+    }
+
+    #[test]
+    fn reuses_entries_of_gone_processes() {
+        let kernel = kernel();
+        let owner = AppId::new(kernel, 0, 0);
+        let mut data = IPCData::default();
+
+        for index in 0..4 {
+            let app = AppId::new(kernel, 10 + index, index);
+            assert!(data.peer_or_insert(app, |_| false, || alloc(owner)).is_ok());
+        }
+
+        let gone = AppId::new(kernel, 12, 2);
+        let new = AppId::new(kernel, 20, 2);
+        let peer = data.peer_or_insert(new, |app| app == gone, no_alloc);
+        assert_eq!(peer.map(|peer| peer.app), Ok(Some(new)));
+        assert_eq!(len(&data), 4);
+        assert!(data.peer(gone).is_none());
+    }
+
+    /// Offsets into the memory of a test process of its mailbox buffers.
+    const RECEIVE_BUFFER: usize = 0x100;
+    const SEND_BUFFER: usize = 0x200;
+
+    /// Bytes in each slot of the test mailboxes.
+    const SLOT_LEN: usize = 8;
+
+    /// A process with a mailbox of `depth` slots and a send buffer holding
+    /// `message`.
+    fn mailbox_process(
+        kernel: &'static Kernel,
+        mailbox: &Mailbox,
+        index: usize,
+        depth: usize,
+        message: &[u8],
+    ) -> &'static dyn ProcessType {
+        let process = test_util::process(kernel, index);
+        let memory = process.mem_start();
+        unsafe {
+            let send_buffer = memory.add(SEND_BUFFER) as *mut u8;
+            core::ptr::copy_nonoverlapping(message.as_ptr(), send_buffer, message.len());
+            let receive = process.allow(memory.add(RECEIVE_BUFFER), depth * SLOT_LEN);
+            let send = process.allow(memory.add(SEND_BUFFER), message.len());
+            assert_eq!(
+                mailbox.allow(process.appid(), 0, receive.unwrap()),
+                ReturnCode::SUCCESS
+            );
+            assert_eq!(
+                mailbox.allow(process.appid(), 1, send.unwrap()),
+                ReturnCode::SUCCESS
+            );
+        }
+        assert_eq!(
+            mailbox.command(1, depth, 0, process.appid()),
+            ReturnCode::SUCCESS
+        );
+        process
+    }
+
+    /// The first `len` bytes of `slot` of the mailbox of `process`.
+    fn slot(process: &dyn ProcessType, slot: usize, len: usize) -> &'static [u8] {
+        unsafe {
+            let start = process.mem_start().add(RECEIVE_BUFFER + slot * SLOT_LEN);
+            core::slice::from_raw_parts(start, len)
+        }
+    }
+
+    fn mailbox(num_procs: usize) -> (&'static Kernel, &'static Mailbox) {
+        let kernel = test_util::kernel(num_procs);
+        let mailbox = Box::leak(Box::new(Mailbox::new(kernel, &test_util::TestCapability)));
+        (kernel, mailbox)
+    }
+
+    #[test]
+    fn full_mailbox_pushes_back() {
+        let (kernel, mailbox) = mailbox(3);
+        let receiver = mailbox_process(kernel, mailbox, 0, 2, b"");
+        let first = mailbox_process(kernel, mailbox, 1, 0, b"first");
+        let second = mailbox_process(kernel, mailbox, 2, 0, b"second");
+
+        // Messages go into free slots in order, until the mailbox is full.
+        let receiver_id = receiver.appid();
+        assert_eq!(
+            mailbox.deliver(first.appid(), receiver_id, 5),
+            ReturnCode::SUCCESS
+        );
+        assert_eq!(
+            mailbox.deliver(second.appid(), receiver_id, 6),
+            ReturnCode::SUCCESS
+        );
+        assert_eq!(slot(receiver, 0, 5), b"first");
+        assert_eq!(slot(receiver, 1, 6), b"second");
+        assert_eq!(
+            mailbox.deliver(first.appid(), receiver_id, 5),
+            ReturnCode::EBUSY
+        );
+
+        // Freeing a slot makes room for one more message.
+        assert_eq!(mailbox.release(receiver_id, 0), ReturnCode::SUCCESS);
+        assert_eq!(mailbox.release(receiver_id, 0), ReturnCode::EINVAL);
+        assert_eq!(
+            mailbox.deliver(second.appid(), receiver_id, 3),
+            ReturnCode::SUCCESS
+        );
+        assert_eq!(slot(receiver, 0, 3), b"sec");
+        assert_eq!(
+            mailbox.deliver(first.appid(), receiver_id, 5),
+            ReturnCode::EBUSY
+        );
+
+        // Messages that do not fit into a slot, or that are longer than the
+        // send buffer, are refused.
+        assert_eq!(mailbox.release(receiver_id, 1), ReturnCode::SUCCESS);
+        assert_eq!(
+            mailbox.deliver(first.appid(), receiver_id, 6),
+            ReturnCode::EINVAL
+        );
+        assert_eq!(
+            mailbox.deliver(receiver_id, first.appid(), 0),
+            ReturnCode::ERESERVE
+        );
+    }
+
+    #[test]
+    fn reply_goes_to_sender() {
+        let (kernel, mailbox) = mailbox(2);
+        let service = mailbox_process(kernel, mailbox, 0, 1, b"pong");
+        let client = mailbox_process(kernel, mailbox, 1, 1, b"ping");
+
+        assert_eq!(
+            mailbox.send(client.appid(), service.appid().id() + 1, 4),
+            ReturnCode::SUCCESS
+        );
+        assert_eq!(slot(service, 0, 4), b"ping");
+
+        // While the client's mailbox is full the reply fails and the slot
+        // stays in use, so that the reply can be sent again.
+        assert_eq!(
+            mailbox.deliver(service.appid(), client.appid(), 4),
+            ReturnCode::SUCCESS
+        );
+        assert_eq!(mailbox.reply(service.appid(), 0, 4), ReturnCode::EBUSY);
+        assert_eq!(mailbox.release(client.appid(), 0), ReturnCode::SUCCESS);
+
+        assert_eq!(mailbox.reply(service.appid(), 0, 4), ReturnCode::SUCCESS);
+        assert_eq!(slot(client, 0, 4), b"pong");
+        assert_eq!(mailbox.release(service.appid(), 0), ReturnCode::EINVAL);
+    }
+
+    #[test]
+    fn reply_to_restarted_sender_is_dropped() {
+        let (kernel, mailbox) = mailbox(2);
+        let service = mailbox_process(kernel, mailbox, 0, 1, b"pong");
+        let client = mailbox_process(kernel, mailbox, 1, 1, b"ping");
+        let old_client = client.appid();
+
+        assert_eq!(
+            mailbox.deliver(old_client, service.appid(), 4),
+            ReturnCode::SUCCESS
+        );
+        client.restart_now();
+        assert!(client.appid() != old_client);
+
+        // The restarted client is a new process that did not send the
+        // message, so the reply is dropped and the slot freed.
+        assert_eq!(mailbox.reply(service.appid(), 0, 4), ReturnCode::EINVAL);
+        assert_eq!(mailbox.release(service.appid(), 0), ReturnCode::EINVAL);
+        assert_eq!(
+            mailbox.deliver(client.appid(), service.appid(), 0),
+            ReturnCode::ERESERVE
+        );
+    }
 }
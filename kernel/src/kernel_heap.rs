@@ -0,0 +1,446 @@
+//! Allocator for the kernel-owned region of a process's memory.
+//!
+//! Grant regions and other kernel objects are allocated from the top of a
+//! process's memory downwards, moving the kernel memory break towards the
+//! app-owned memory. Every allocation is preceded by a small header recording
+//! the block it occupies so that it can be freed again. Freeing the lowest block
+//! moves the kernel memory break back up, and any other freed block is kept in
+//! an address-ordered free list and reused by later allocations. Adjacent free
+//! blocks are merged.
+//!
+//! ```text
+//!             ┌────────────────────  top (original kernel memory break)
+//!             │   allocated block
+//!             ├────────────────────
+//!             │   free block  ─┐
+//!             ├────────────────│───
+//!             │   allocated    │
+//!             ├────────────────│───
+//!             │   free block  <┘
+//!             ├────────────────────  kernel memory break
+//!             │   --unallocated--
+//! ```
+
+use core::cell::Cell;
+use core::cmp::max;
+use core::mem::size_of;
+use core::ptr::{self, NonNull};
+
+const WORD: usize = size_of::<usize>();
+
+/// Size of the header stored in front of every allocation, which holds the
+/// start and end address of the block the allocation occupies. This is also
+/// the smallest block that can be kept in the free list.
+const HEADER: usize = 2 * WORD;
+
+/// A free block, stored in the free memory itself. The block starts at the
+/// address of this struct.
+struct FreeBlock {
+    next: *mut FreeBlock,
+    end: usize,
+}
+
+/// Per-process kernel heap that reuses freed space.
+///
+/// The heap does not own the kernel memory break, instead it is passed in by
+/// the process so that the rest of the process code keeps seeing where kernel
+/// memory begins.
+pub(crate) struct KernelHeap {
+    /// Lowest free block, or null if there are none.
+    free_list: Cell<*mut FreeBlock>,
+}
+
+impl KernelHeap {
+    pub(crate) const fn new() -> KernelHeap {
+        KernelHeap {
+            free_list: Cell::new(ptr::null_mut()),
+        }
+    }
+
+    /// Forget all free blocks. Used when the kernel memory break is reset.
+    pub(crate) fn reset(&self) {
+        self.free_list.set(ptr::null_mut());
+    }
+
+    /// Allocate `size` bytes aligned to `align`, which must be a power of two.
+    ///
+    /// Freed blocks are reused first. Otherwise the block is taken from below
+    /// the kernel memory break `brk`, and `grow` is asked whether the break may
+    /// be moved down to the given address.
+    ///
+    /// ## Safety
+    ///
+    /// All memory between `brk` and the top of the heap must be owned by this
+    /// heap.
+    pub(crate) unsafe fn alloc<F>(
+        &self,
+        brk: &Cell<*const u8>,
+        size: usize,
+        align: usize,
+        grow: F,
+    ) -> Option<NonNull<u8>>
+    where
+        F: FnOnce(*const u8) -> bool,
+    {
+        let align = max(align, WORD);
+        let size = size.checked_add(WORD - 1)? & !(WORD - 1);
+
+        // First fit from the free list.
+        let mut prev: *mut *mut FreeBlock = self.free_list.as_ptr();
+        while !(*prev).is_null() {
+            let block = *prev;
+            let start = block as usize;
+            let end = (*block).end;
+            if let Some(data) = Self::place(start, end, size, align) {
+                *prev = (*block).next;
+
+                // Return the unused front of the block to the free list if
+                // it is large enough to be tracked.
+                let alloc_start = data - HEADER;
+                let block_start = if alloc_start - start >= HEADER {
+                    self.insert_free(start, alloc_start);
+                    alloc_start
+                } else {
+                    start
+                };
+
+                return Some(Self::write_header(data, block_start, end));
+            }
+            prev = &mut (*block).next;
+        }
+
+        // Nothing free fits, so take more memory from below the break.
+        let end = brk.get() as usize;
+        let data = end.checked_sub(size)? & !(align - 1);
+        let start = data.checked_sub(HEADER)?;
+        if !grow(start as *const u8) {
+            return None;
+        }
+        brk.set(start as *const u8);
+        Some(Self::write_header(data, start, end))
+    }
+
+    /// Free an allocation made with `alloc()`.
+    ///
+    /// Pointers outside of the heap, i.e. not between `brk` and `top`, are
+    /// ignored. So are pointers whose header does not describe a block inside
+    /// the heap that contains the allocation, or whose block overlaps a free
+    /// block, since the header is not protected from being overwritten.
+    ///
+    /// ## Safety
+    ///
+    /// `ptr` must either be outside of the heap or be a live allocation from
+    /// this heap.
+    pub(crate) unsafe fn free(&self, brk: &Cell<*const u8>, top: *const u8, ptr: *mut u8) {
+        let data = ptr as usize;
+        if data < brk.get() as usize + HEADER || data > top as usize {
+            return;
+        }
+
+        let header = (data - HEADER) as *const usize;
+        let start = *header;
+        let end = *header.add(1);
+        if start % WORD != 0
+            || end % WORD != 0
+            || start < brk.get() as usize
+            || start > data - HEADER
+            || end < data
+            || end > top as usize
+        {
+            // Not a header we wrote, so not something we allocated.
+            return;
+        }
+        if self.overlaps_free(start, end) {
+            // Freed twice, or the header was changed.
+            return;
+        }
+
+        if start == brk.get() as usize {
+            // This is the lowest block, so move the break up past it and any
+            // free blocks directly above it.
+            let mut new_brk = end;
+            while !self.free_list.get().is_null() && self.free_list.get() as usize == new_brk {
+                let block = self.free_list.get();
+                new_brk = (*block).end;
+                self.free_list.set((*block).next);
+            }
+            brk.set(new_brk as *const u8);
+        } else {
+            self.insert_free(start, end);
+        }
+    }
+
+    /// Whether the block `start..end` overlaps any block in the free list.
+    unsafe fn overlaps_free(&self, start: usize, end: usize) -> bool {
+        let mut block = self.free_list.get();
+        while !block.is_null() && (block as usize) < end {
+            if (*block).end > start {
+                return true;
+            }
+            block = (*block).next;
+        }
+        false
+    }
+
+    /// Find where an allocation of `size` bytes aligned to `align` goes in
+    /// the block `start..end`, placing it as high as possible. Returns the
+    /// address of the allocation if it fits together with its header.
+    fn place(start: usize, end: usize, size: usize, align: usize) -> Option<usize> {
+        let data = end.checked_sub(size)? & !(align - 1);
+        if data >= start.checked_add(HEADER)? {
+            Some(data)
+        } else {
+            None
+        }
+    }
+
+    /// Record the block an allocation occupies in front of it.
+    unsafe fn write_header(data: usize, start: usize, end: usize) -> NonNull<u8> {
+        let header = (data - HEADER) as *mut usize;
+        *header = start;
+        *header.add(1) = end;
+        NonNull::new_unchecked(data as *mut u8)
+    }
+
+    /// Add the block `start..end` to the address-ordered free list, merging it
+    /// with the blocks before and after it if they are adjacent.
+    unsafe fn insert_free(&self, start: usize, mut end: usize) {
+        let mut prev: *mut FreeBlock = ptr::null_mut();
+        let mut next = self.free_list.get();
+        while !next.is_null() && (next as usize) < start {
+            prev = next;
+            next = (*next).next;
+        }
+
+        // Merge with the following block.
+        let mut after = next;
+        if !next.is_null() && next as usize == end {
+            end = (*next).end;
+            after = (*next).next;
+        }
+
+        // Merge with the preceding block, or link in a new one.
+        if !prev.is_null() && (*prev).end == start {
+            (*prev).end = end;
+            (*prev).next = after;
+        } else {
+            let block = start as *mut FreeBlock;
+            (*block).end = end;
+            (*block).next = after;
+            if prev.is_null() {
+                self.free_list.set(block);
+            } else {
+                (*prev).next = block;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::KernelHeap;
+    use core::cell::Cell;
+
+    struct TestHeap {
+        memory: [usize; 64],
+    }
+
+    impl TestHeap {
+        fn new() -> TestHeap {
+            TestHeap { memory: [0; 64] }
+        }
+
+        fn bottom(&self) -> *const u8 {
+            self.memory.as_ptr() as *const u8
+        }
+
+        fn top(&self) -> *const u8 {
+            unsafe { self.bottom().add(self.memory.len() * super::WORD) }
+        }
+    }
+
+    fn alloc(
+        heap: &KernelHeap,
+        brk: &Cell<*const u8>,
+        bottom: *const u8,
+        size: usize,
+        align: usize,
+    ) -> Option<*mut u8> {
+        unsafe {
+            heap.alloc(brk, size, align, |new_brk| new_brk >= bottom)
+                .map(|p| p.as_ptr())
+        }
+    }
+
+    #[test]
+    fn repeated_alloc_free() {
+        let mem = TestHeap::new();
+        let heap = KernelHeap::new();
+        let brk = Cell::new(mem.top());
+
+        for _ in 0..1000 {
+            let a = alloc(&heap, &brk, mem.bottom(), 24, 4).unwrap();
+            let b = alloc(&heap, &brk, mem.bottom(), 8, 8).unwrap();
+            assert_eq!(b as usize % 8, 0);
+            unsafe {
+                heap.free(&brk, mem.top(), a);
+                heap.free(&brk, mem.top(), b);
+            }
+            assert_eq!(brk.get(), mem.top());
+        }
+    }
+
+    #[test]
+    fn reuses_freed_block() {
+        let mem = TestHeap::new();
+        let heap = KernelHeap::new();
+        let brk = Cell::new(mem.top());
+
+        let a = alloc(&heap, &brk, mem.bottom(), 16, 4).unwrap();
+        let b = alloc(&heap, &brk, mem.bottom(), 16, 4).unwrap();
+        let c = alloc(&heap, &brk, mem.bottom(), 16, 4).unwrap();
+        let low = brk.get();
+
+        // Freeing a block that is not the lowest keeps the break where it is,
+        // and the next allocation of the same size goes in its place.
+        unsafe { heap.free(&brk, mem.top(), b) };
+        assert_eq!(brk.get(), low);
+        let d = alloc(&heap, &brk, mem.bottom(), 16, 4).unwrap();
+        assert_eq!(d, b);
+        assert_eq!(brk.get(), low);
+
+        // Freeing the lowest block also releases free blocks above it.
+        unsafe {
+            heap.free(&brk, mem.top(), d);
+            heap.free(&brk, mem.top(), a);
+        }
+        assert_eq!(brk.get(), low);
+        unsafe { heap.free(&brk, mem.top(), c) };
+        assert_eq!(brk.get(), mem.top());
+    }
+
+    #[test]
+    fn exhaust_free_and_reallocate() {
+        let mem = TestHeap::new();
+        let heap = KernelHeap::new();
+        let brk = Cell::new(mem.top());
+
+        for _ in 0..10 {
+            let mut allocations = [None; 64];
+            let mut count = 0;
+            while let Some(p) = alloc(&heap, &brk, mem.bottom(), 12, 4) {
+                allocations[count] = Some(p);
+                count += 1;
+            }
+            assert!(count > 0);
+
+            // Free every other allocation first so the free list has to merge
+            // blocks back together.
+            for p in allocations.iter().step_by(2).filter_map(|p| *p) {
+                unsafe { heap.free(&brk, mem.top(), p) };
+            }
+            for p in allocations.iter().skip(1).step_by(2).filter_map(|p| *p) {
+                unsafe { heap.free(&brk, mem.top(), p) };
+            }
+            assert_eq!(brk.get(), mem.top());
+        }
+    }
+
+    #[test]
+    fn ignores_foreign_pointers() {
+        let mem = TestHeap::new();
+        let heap = KernelHeap::new();
+        let brk = Cell::new(mem.top());
+
+        let a = alloc(&heap, &brk, mem.bottom(), 16, 4).unwrap();
+        let low = brk.get();
+        unsafe { heap.free(&brk, mem.top(), mem.bottom() as *mut u8) };
+        assert_eq!(brk.get(), low);
+        unsafe { heap.free(&brk, mem.top(), a) };
+        assert_eq!(brk.get(), mem.top());
+    }
+
+    #[test]
+    fn ignores_corrupt_headers() {
+        let mut mem = TestHeap::new();
+        let heap = KernelHeap::new();
+        let brk = Cell::new(mem.top());
+
+        let a = alloc(&heap, &brk, mem.bottom(), 16, 4).unwrap();
+        let b = alloc(&heap, &brk, mem.bottom(), 16, 4).unwrap();
+        let low = brk.get();
+        let header =
+            (a as usize - super::HEADER) / super::WORD - mem.bottom() as usize / super::WORD;
+        let (start, end) = (mem.memory[header], mem.memory[header + 1]);
+
+        // Blocks reaching past the top of the heap, below the break, or not
+        // covering the allocation are not freed.
+        let corrupt = [
+            (start, mem.top() as usize + super::WORD),
+            (mem.bottom() as usize, end),
+            (start, a as usize - super::WORD),
+            (start + 1, end),
+        ];
+        for &(bad_start, bad_end) in corrupt.iter() {
+            mem.memory[header] = bad_start;
+            mem.memory[header + 1] = bad_end;
+            unsafe { heap.free(&brk, mem.top(), a) };
+            let c = alloc(&heap, &brk, mem.bottom(), 16, 4).unwrap();
+            assert!(c != a);
+            unsafe { heap.free(&brk, mem.top(), c) };
+            assert_eq!(brk.get(), low);
+        }
+
+        // A block freed twice is only freed once.
+        mem.memory[header] = start;
+        mem.memory[header + 1] = end;
+        unsafe {
+            heap.free(&brk, mem.top(), a);
+            heap.free(&brk, mem.top(), a);
+        }
+        let c = alloc(&heap, &brk, mem.bottom(), 16, 4).unwrap();
+        let d = alloc(&heap, &brk, mem.bottom(), 16, 4).unwrap();
+        assert_eq!(c, a);
+        assert!(d != a);
+        unsafe {
+            heap.free(&brk, mem.top(), b);
+            heap.free(&brk, mem.top(), c);
+            heap.free(&brk, mem.top(), d);
+        }
+        assert_eq!(brk.get(), mem.top());
+    }
+
+    #[test]
+    fn owned_grant_memory_is_reused() {
+        use crate::test_util;
+
+        let kernel = test_util::kernel(1);
+        let grant = kernel.create_grant::<usize>(&test_util::TestCapability);
+        let appid = test_util::process(kernel, 0).appid();
+
+        let (first, reused, again) = grant
+            .enter(appid, |_, allocator| {
+                let address = |owned: &crate::grant::Owned<[usize; 4]>| {
+                    &**owned as *const [usize; 4] as usize
+                };
+                let a = allocator.alloc::<[usize; 4]>().unwrap();
+                let b = allocator.alloc::<[usize; 4]>().unwrap();
+                let first = address(&a);
+
+                // `a` is above `b`, so dropping it leaves a hole that the
+                // next allocation fills.
+                drop(a);
+                let c = allocator.alloc::<[usize; 4]>().unwrap();
+                let reused = address(&c);
+
+                // Once everything is dropped the memory is available again.
+                drop(b);
+                drop(c);
+                let d = allocator.alloc::<[usize; 4]>().unwrap();
+                (first, reused, address(&d))
+            })
+            .unwrap();
+        assert_eq!(reused, first);
+        assert_eq!(again, first);
+    }
+}
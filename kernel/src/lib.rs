@@ -102,28 +102,49 @@ mod callback;
 mod config;
 mod driver;
 mod grant;
+mod kernel_heap;
 mod mem;
 mod memop;
 mod platform;
 mod process;
+mod process_loader;
+mod quota;
 mod returncode;
 mod sched;
+mod syscall_trace;
 mod tbfheader;
+#[cfg(test)]
+mod test_util;
+
+/// Major version of the system call ABI this kernel provides to processes.
+///
+/// Apps can list the range of versions they were built for in their TBF
+/// header, and the kernel will not load apps that do not include this version.
+pub const KERNEL_MAJOR_VERSION: u16 = 1;
+
+/// Minor version of the system call ABI this kernel provides to processes.
+pub const KERNEL_MINOR_VERSION: u16 = 8;
 
 pub use crate::callback::{AppId, Callback};
+pub use crate::config::DebugSwitch;
 pub use crate::driver::Driver;
 pub use crate::grant::Grant;
-pub use crate::mem::{AppSlice, Private, Shared};
+pub use crate::mem::{AppSlice, Private, ReadOnlyAppSlice, ReadableAppSlice, Shared};
 pub use crate::platform::scheduler_timer::{SchedulerTimer, VirtualSchedulerTimer};
 pub use crate::platform::watchdog;
 pub use crate::platform::{mpu, Chip, Platform};
 pub use crate::platform::{ClockInterface, NoClockControl, NO_CLOCK_CONTROL};
 pub use crate::returncode::ReturnCode;
 pub use crate::sched::cooperative::{CoopProcessNode, CooperativeSched};
+pub use crate::sched::edf::{EDFProcessNode, EDFSched};
 pub use crate::sched::mlfq::{MLFQProcessNode, MLFQSched};
 pub use crate::sched::priority::PrioritySched;
 pub use crate::sched::round_robin::{RoundRobinProcessNode, RoundRobinSched};
-pub use crate::sched::{Kernel, Scheduler};
+pub use crate::sched::{Kernel, Scheduler, MAX_FAULT_HANDLERS};
+pub use crate::syscall::SyscallReturn;
+pub use crate::syscall_trace::{
+    SyscallTraceBuffer, SyscallTraceRecord, SyscallTracer, SYSCALL_TRACE_RECORD_LEN,
+};
 
 // Export only select items from the process module. To remove the name conflict
 // this cannot be called `process`, so we use a shortened version. These
@@ -132,8 +153,16 @@ pub use crate::sched::{Kernel, Scheduler};
 /// Publicly available process-related objects.
 pub mod procs {
     pub use crate::process::{
-        load_processes, AlwaysRestart, Error, FaultResponse, FunctionCall, FunctionCallSource,
-        Process, ProcessLoadError, ProcessRestartPolicy, ProcessType, State, Task,
-        ThresholdRestart, ThresholdRestartThenPanic,
+        load_processes, AlwaysRestart, BackoffRestart, Error, FaultResponse, FunctionCall,
+        FunctionCallSource, NoCredentialsCheck, Process, ProcessCredentialsPolicy,
+        ProcessFaultHandler, ProcessLoadError, ProcessMemoryMap, ProcessRestartPolicy, ProcessSlot,
+        ProcessType, RestartBackoffEntry, RestartDecision, State, Task, ThresholdRestart,
+        ThresholdRestartThenPanic,
+    };
+    pub use crate::process_loader::ProcessLoader;
+    pub use crate::quota::{
+        ProcessQuota, ProcessQuotaEnforcer, ProcessQuotaPolicy, ProcessQuotas, QuotaAction,
+        QuotaResource, QuotaUsage, UniformQuota,
     };
+    pub use crate::tbfheader::{TbfHeaderV2Credentials, TbfHeaderV2CredentialsType};
 }
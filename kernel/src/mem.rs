@@ -6,6 +6,7 @@ use core::slice;
 
 use crate::callback::AppId;
 use crate::capabilities;
+use crate::platform::mpu;
 
 /// Type for specifying an AppSlice is hidden from the kernel.
 #[derive(Debug)]
@@ -98,19 +99,18 @@ impl<L, T> AppSlice<L, T> {
     }
 
     /// Provide access to one app's AppSlice to another app. This is used for
-    /// IPC.
-    pub(crate) unsafe fn expose_to(&self, appid: AppId) -> bool {
+    /// IPC. Returns the MPU region of the other app that covers the slice, which
+    /// can be passed to `ProcessType::remove_mpu_region()` to revoke access.
+    pub(crate) unsafe fn expose_to(&self, appid: AppId) -> Option<mpu::Region> {
         if appid != self.ptr.process {
             self.ptr
                 .process
                 .kernel
-                .process_map_or(false, appid, |process| {
-                    process
-                        .add_mpu_region(self.ptr() as *const u8, self.len(), self.len())
-                        .is_some()
+                .process_map_or(None, appid, |process| {
+                    process.add_mpu_region(self.ptr() as *const u8, self.len(), self.len())
                 })
         } else {
-            false
+            None
         }
     }
 
@@ -210,3 +210,156 @@ impl<L, T> AsMut<[T]> for AppSlice<L, T> {
             })
     }
 }
+
+/// Buffer of memory shared read-only from an app to the kernel.
+///
+/// This is the type created after an app calls the `readonly_allow` syscall.
+/// Unlike an `AppSlice` the buffer may be in the app's flash as well as in its
+/// RAM, so capsules can only read from it.
+pub struct ReadOnlyAppSlice<T> {
+    ptr: NonNull<T>,
+    len: usize,
+    process: AppId,
+}
+
+impl<T> ReadOnlyAppSlice<T> {
+    /// Safety: Trusts that `ptr` + `len` is a buffer in the memory region or
+    /// flash owned by `appid`.
+    pub(crate) unsafe fn new(ptr: NonNull<T>, len: usize, appid: AppId) -> ReadOnlyAppSlice<T> {
+        ReadOnlyAppSlice {
+            ptr: ptr,
+            len: len,
+            process: appid,
+        }
+    }
+
+    /// Safety: Trusts that `ptr` + `len` is a buffer in the memory region or
+    /// flash owned by `appid`.
+    ///
+    /// This constructor is public but protected with a capability to enable
+    /// external implementations of `ProcessType` to create
+    /// `ReadOnlyAppSlice`s.
+    pub unsafe fn new_external(
+        ptr: NonNull<T>,
+        len: usize,
+        appid: AppId,
+        _capability: &dyn capabilities::ExternalProcessCapability,
+    ) -> ReadOnlyAppSlice<T> {
+        ReadOnlyAppSlice {
+            ptr: ptr,
+            len: len,
+            process: appid,
+        }
+    }
+
+    /// Number of bytes in the `ReadOnlyAppSlice`.
+    ///
+    /// If the app died, has restarted, or its AppId identifier changed for any
+    /// other reason, return an accessible length of zero, consistent with the
+    /// [`AsRef`](struct.ReadOnlyAppSlice.html#impl-AsRef<[T]>)
+    /// implementation.
+    pub fn len(&self) -> usize {
+        self.process
+            .kernel
+            .process_map_or(0, self.process, |_| self.len)
+    }
+
+    /// Get the raw pointer to the buffer. This will be a pointer inside of the
+    /// app's memory region or flash.
+    pub fn ptr(&self) -> *const T {
+        self.ptr.as_ptr()
+    }
+
+    /// Returns an iterator over the slice
+    ///
+    /// See
+    /// [`std::slice::iter()`](https://doc.rust-lang.org/std/primitive.slice.html#method.iter).
+    ///
+    /// Like [`AppSlice::iter()`](struct.AppSlice.html#method.iter), the
+    /// iterator will be of zero length if the app is no longer running.
+    pub fn iter(&self) -> slice::Iter<T> {
+        self.as_ref().iter()
+    }
+
+    /// Iterate over `chunk_size` elements at a time, starting at the
+    /// beginning of the ReadOnlyAppSlice.
+    ///
+    /// See
+    /// [`std::slice::chunks()`](https://doc.rust-lang.org/std/primitive.slice.html#method.chunks).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is 0.
+    pub fn chunks(&self, size: usize) -> slice::Chunks<T> {
+        self.as_ref().chunks(size)
+    }
+}
+
+impl<T> AsRef<[T]> for ReadOnlyAppSlice<T> {
+    /// Get a slice reference over the userspace buffer
+    ///
+    /// This first checks whether the app died, restarted, or its
+    /// AppId identifier changed for any other reason. In this case, a
+    /// slice of length zero is returned.
+    fn as_ref(&self) -> &[T] {
+        self.process
+            .kernel
+            .process_map_or(&[], self.process, |_| unsafe {
+                slice::from_raw_parts(self.ptr.as_ref(), self.len)
+            })
+    }
+}
+
+/// A buffer an app shared with the kernel for the kernel to read, either with
+/// `allow` or with `readonly_allow`. Capsules that only read an input buffer
+/// can store this to accept both.
+pub enum ReadableAppSlice<T> {
+    Shared(AppSlice<Shared, T>),
+    ReadOnly(ReadOnlyAppSlice<T>),
+}
+
+impl<T> ReadableAppSlice<T> {
+    /// Number of bytes in the buffer, or zero if the app is no longer running.
+    pub fn len(&self) -> usize {
+        match self {
+            ReadableAppSlice::Shared(slice) => slice.len(),
+            ReadableAppSlice::ReadOnly(slice) => slice.len(),
+        }
+    }
+
+    /// Returns an iterator over the slice
+    pub fn iter(&self) -> slice::Iter<T> {
+        self.as_ref().iter()
+    }
+
+    /// Iterate over `chunk_size` elements at a time, starting at the
+    /// beginning of the buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is 0.
+    pub fn chunks(&self, size: usize) -> slice::Chunks<T> {
+        self.as_ref().chunks(size)
+    }
+}
+
+impl<T> AsRef<[T]> for ReadableAppSlice<T> {
+    fn as_ref(&self) -> &[T] {
+        match self {
+            ReadableAppSlice::Shared(slice) => slice.as_ref(),
+            ReadableAppSlice::ReadOnly(slice) => slice.as_ref(),
+        }
+    }
+}
+
+impl<T> From<AppSlice<Shared, T>> for ReadableAppSlice<T> {
+    fn from(slice: AppSlice<Shared, T>) -> ReadableAppSlice<T> {
+        ReadableAppSlice::Shared(slice)
+    }
+}
+
+impl<T> From<ReadOnlyAppSlice<T>> for ReadableAppSlice<T> {
+    fn from(slice: ReadOnlyAppSlice<T>) -> ReadableAppSlice<T> {
+        ReadableAppSlice::ReadOnly(slice)
+    }
+}
@@ -36,6 +36,9 @@ use crate::returncode::ReturnCode;
 ///   where the app has put the start of its heap. This is not strictly
 ///   necessary for correct operation, but allows for better debugging if the
 ///   app crashes.
+/// - `12`: Specify the size of the app stack, which starts at the address
+///   given with `10`. The kernel protects the memory below the stack with an
+///   MPU guard region, so that a stack overflow faults the app immediately.
 pub(crate) fn memop(process: &dyn ProcessType, op_type: usize, r1: usize) -> ReturnCode {
     match op_type {
         // Op Type 0: BRK
@@ -108,6 +111,13 @@ pub(crate) fn memop(process: &dyn ProcessType, op_type: usize, r1: usize) -> Ret
             ReturnCode::SUCCESS
         }
 
+        // Op Type 12: Specify the size of the app stack and protect the memory
+        // below it.
+        12 => match process.protect_stack(r1) {
+            Ok(()) => ReturnCode::SUCCESS,
+            Err(err) => err.into(),
+        },
+
         _ => ReturnCode::ENOSUPPORT,
     }
 }
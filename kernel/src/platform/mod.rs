@@ -51,8 +51,9 @@ pub trait Platform {
     /// calls.  If the system call is allowed for the provided process then
     /// return Ok(()).  Otherwise, return Err with a ReturnCode that will be
     /// returned to the calling application.  The default implementation allows
-    /// all system calls. This API should be considered unstable, and is likely
-    /// to change in the future.
+    /// all system calls. This filter runs before the kernel checks the
+    /// permissions a process declares in its TBF header. This API should be
+    /// considered unstable, and is likely to change in the future.
     fn filter_syscall(
         &self,
         _process: &dyn process::ProcessType,
@@ -12,6 +12,9 @@ pub enum Permissions {
     ReadExecuteOnly,
     ReadOnly,
     ExecuteOnly,
+    /// No access in user mode. Regions with these permissions are guards: they
+    /// may overlap other regions, and take precedence over them.
+    NoAccess,
 }
 
 /// MPU region.
@@ -128,7 +131,9 @@ pub trait MPU {
     /// bytes in size within the specified stretch of unallocated memory, and
     /// with the specified user mode permissions, and store it in `config`. The
     /// allocated region may not overlap any of the regions already stored in
-    /// `config`.
+    /// `config`, unless `permissions` is `NoAccess`. In that case the region
+    /// must take precedence over the regions it overlaps, and the memory it
+    /// covers must stay accessible in supervisor mode.
     ///
     /// # Arguments
     ///
@@ -158,6 +163,27 @@ pub trait MPU {
         }
     }
 
+    /// Removes an MPU region that was allocated with `allocate_region()`.
+    ///
+    /// An implementation must remove the region from `config`, so that the
+    /// memory it covered is no longer accessible in user mode once `config`
+    /// is written to the hardware.
+    ///
+    /// # Arguments
+    ///
+    /// - `region`: the region `allocate_region()` returned
+    /// - `config`: MPU region configuration
+    ///
+    /// # Return Value
+    ///
+    /// Returns an error if `region` is not stored in `config`, or if the
+    /// implementation cannot remove regions. The default implementation
+    /// cannot, so that access is never reported as revoked when it was not.
+    #[allow(unused_variables)]
+    fn remove_memory_region(&self, region: Region, config: &mut Self::MpuConfig) -> Result<(), ()> {
+        Err(())
+    }
+
     /// Chooses the location for a process's memory, and allocates an MPU region
     /// covering the app-owned part.
     ///
@@ -266,7 +292,16 @@ pub trait MPU {
 }
 
 /// Implement default MPU trait for unit.
-impl MPU for () {}
+/// Without an MPU there is no hardware state to remove a region from.
+impl MPU for () {
+    fn remove_memory_region(
+        &self,
+        _region: Region,
+        _config: &mut MpuConfigDefault,
+    ) -> Result<(), ()> {
+        Ok(())
+    }
+}
 
 /// The generic trait that particular kernel level memory protection unit
 /// implementations need to implement.
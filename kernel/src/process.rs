@@ -9,19 +9,25 @@ use core::{mem, ptr, slice, str};
 
 use crate::callback::{AppId, CallbackId};
 use crate::capabilities::ProcessManagementCapability;
-use crate::common::cells::{MapCell, NumericCellExt};
+use crate::common::cells::{MapCell, NumericCellExt, TakeCell};
 use crate::common::{Queue, RingBuffer};
-use crate::config;
 use crate::debug;
+use crate::hil::time::{self, Frequency};
 use crate::ipc;
-use crate::mem::{AppSlice, Shared};
+use crate::kernel_heap::KernelHeap;
+use crate::mem::{AppSlice, ReadOnlyAppSlice, Shared};
 use crate::platform::mpu::{self, MPU};
 use crate::platform::Chip;
+use crate::quota::QuotaUsage;
 use crate::returncode::ReturnCode;
 use crate::sched::Kernel;
-use crate::syscall::{self, Syscall, UserspaceKernelBoundary};
+use crate::syscall::{self, Syscall, SyscallReturn, UserspaceKernelBoundary};
 use crate::tbfheader;
-use core::cmp::max;
+use core::cmp::{max, min};
+
+/// The size of the MPU region that `protect_stack()` places below a process
+/// stack.
+const STACK_GUARD_SIZE: usize = 32;
 
 /// Errors that can occur when trying to load and create processes.
 pub enum ProcessLoadError {
@@ -56,6 +62,20 @@ pub enum ProcessLoadError {
         expected_address: u32,
     },
 
+    /// There is no empty slot left in the processes array to hold another
+    /// process.
+    NoFreeProcessSlot,
+
+    /// The TBF object is padding, a disabled app, an app built for a kernel
+    /// ABI version other than this one, an app whose short ID is in use by
+    /// another process, or an app the credentials policy rejected, so there is
+    /// no process to create from it.
+    NoProcessToLoad,
+
+    /// The flash of the TBF object overlaps with the flash of a process that
+    /// is already loaded.
+    FlashInUse,
+
     /// Process loading error due (likely) to a bug in the kernel. If you get
     /// this error please open a bug report.
     InternalError,
@@ -109,11 +129,58 @@ impl fmt::Debug for ProcessLoadError {
                 actual_address, expected_address
             ),
 
+            ProcessLoadError::NoFreeProcessSlot => write!(f, "No free slot in the processes array"),
+
+            ProcessLoadError::NoProcessToLoad => {
+                write!(f, "TBF object is not an app this kernel may load")
+            }
+
+            ProcessLoadError::FlashInUse => {
+                write!(f, "App flash overlaps with an already loaded process")
+            }
+
             ProcessLoadError::InternalError => write!(f, "Error in kernel. Likely a bug."),
         }
     }
 }
 
+/// Addresses describing the memory layout of a process.
+#[derive(Clone, Copy, Debug)]
+pub struct ProcessMemoryMap {
+    /// Start of the process's flash, including its TBF header.
+    pub flash_start: usize,
+    /// First address after the end of the process's flash.
+    pub flash_end: usize,
+    /// Start of the process's RAM.
+    pub memory_start: usize,
+    /// First address after the end of the process's RAM.
+    pub memory_end: usize,
+    /// End of the RAM the process can access.
+    pub app_break: usize,
+    /// Start of the RAM the kernel uses for grants and process state.
+    pub kernel_memory_break: usize,
+    /// The process's stack pointer.
+    pub stack_pointer: usize,
+}
+
+/// A handler boards can register with the kernel to be told about process
+/// faults, for example to record them for later analysis.
+pub trait ProcessFaultHandler {
+    /// Called when `process` faults, before the kernel restarts, stops, or
+    /// panics because of it. `context` holds the registers and fault reason
+    /// provided by the architecture.
+    ///
+    /// This is called from within the kernel's fault handling, so
+    /// implementations must not block and should only start any slow work
+    /// (such as writing to flash).
+    fn process_fault(
+        &self,
+        process: &dyn ProcessType,
+        context: &syscall::FaultContext,
+        memory_map: &ProcessMemoryMap,
+    );
+}
+
 /// Helper function to load processes from flash into an array of active
 /// processes. This is the default template for loading processes, but a board
 /// is able to create its own `load_processes()` function and use that instead.
@@ -121,9 +188,14 @@ impl fmt::Debug for ProcessLoadError {
 /// Processes are found in flash starting from the given address and iterating
 /// through Tock Binary Format (TBF) headers. Processes are given memory out of
 /// the `app_memory` buffer until either the memory is exhausted or the
-/// allocated number of processes are created. A reference to each process is
-/// stored in the provided `procs` array. How process faults are handled by the
-/// kernel must be provided and is assigned to every created process.
+/// kernel's processes array is full. A reference to each process is stored in
+/// the processes array the kernel was created with. How process faults are handled by the
+/// kernel must be provided and is assigned to every created process. Every
+/// enabled app is checked against `credentials_policy` before it is created,
+/// and apps it rejects are skipped.
+///
+/// Processes can also be added and removed after boot with a
+/// `ProcessLoader`, which uses the memory this function leaves unused.
 ///
 /// This function is made `pub` so that board files can use it, but loading
 /// processes from slices of flash an memory is fundamentally unsafe. Therefore,
@@ -137,11 +209,11 @@ pub fn load_processes<C: Chip>(
     chip: &'static C,
     app_flash: &'static [u8],
     app_memory: &'static mut [u8],
-    procs: &'static mut [Option<&'static dyn ProcessType>],
     fault_response: FaultResponse,
+    credentials_policy: &'static dyn ProcessCredentialsPolicy,
     _capability: &dyn ProcessManagementCapability,
 ) -> Result<(), ProcessLoadError> {
-    if config::CONFIG.debug_load_processes {
+    if kernel.debug_config().debug_load_processes() {
         debug!(
             "Loading processes from flash={:#010X}-{:#010X} into sram={:#010X}-{:#010X}",
             app_flash.as_ptr() as usize,
@@ -154,8 +226,8 @@ pub fn load_processes<C: Chip>(
     let mut remaining_flash = app_flash;
     let mut remaining_memory = app_memory;
 
-    // Try to discover up to `procs.len()` processes in flash.
-    for i in 0..procs.len() {
+    // Try to discover as many processes in flash as the kernel has slots for.
+    for i in 0..kernel.number_of_process_slots() {
         // Get the first eight bytes of flash to check if there is another
         // app.
         let test_header_slice = match remaining_flash.get(0..8) {
@@ -225,11 +297,12 @@ pub fn load_processes<C: Chip>(
                     version,
                     remaining_memory,
                     fault_response,
+                    credentials_policy,
                     i,
                 )?
             };
             process_option.map(|process| {
-                if config::CONFIG.debug_load_processes {
+                if kernel.debug_config().debug_load_processes() {
                     debug!(
                         "Loaded process[{}] from flash={:#010X}-{:#010X} into sram={:#010X}-{:#010X} = {:?}",
                         i,
@@ -242,7 +315,7 @@ pub fn load_processes<C: Chip>(
                 }
 
                 // Save the reference to this process in the processes array.
-                procs[i] = Some(process);
+                kernel.insert_process(i, process);
             });
             unused_memory
         } else {
@@ -255,6 +328,37 @@ pub fn load_processes<C: Chip>(
     Ok(())
 }
 
+/// Check that the kernel ABI version range an app declares in its TBF header
+/// includes the version of this kernel. Apps that do not declare a range are
+/// assumed to be compatible.
+fn is_kernel_version_compatible(tbf_header: &tbfheader::TbfHeader) -> bool {
+    tbf_header
+        .get_kernel_version()
+        .map_or(true, |(min_version, max_version)| {
+            let kernel_version = (crate::KERNEL_MAJOR_VERSION, crate::KERNEL_MINOR_VERSION);
+            kernel_version >= min_version && kernel_version <= max_version
+        })
+}
+
+/// Derive a short ID for an app from its package name.
+///
+/// This is the 32 bit FNV-1a hash of the name with the high bit set, so that
+/// derived IDs never collide with IDs assigned in TBF headers.
+fn short_id_from_name(name: &str) -> u32 {
+    let hash = name.bytes().fold(0x811c9dc5u32, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x01000193)
+    });
+    hash | 0x80000000
+}
+
+/// The short ID of the app with `tbf_header`: the ID from the header if it has
+/// one, otherwise the ID derived from its package name.
+fn short_id(tbf_header: &tbfheader::TbfHeader) -> u32 {
+    tbf_header
+        .get_short_id()
+        .unwrap_or_else(|| short_id_from_name(tbf_header.get_package_name().unwrap_or("")))
+}
+
 /// This trait is implemented by process structs.
 pub trait ProcessType {
     /// Returns the process's identifier
@@ -272,7 +376,8 @@ pub trait ProcessType {
     /// execute any new tasks.
     fn enqueue_task(&self, task: Task) -> bool;
 
-    /// Returns whether this process is ready to execute.
+    /// Returns whether this process is ready to execute. A process waiting for
+    /// an upcall is only ready once that upcall is queued.
     fn ready(&self) -> bool;
 
     /// Remove the scheduled operation from the front of the queue and return it
@@ -296,6 +401,25 @@ pub trait ProcessType {
     /// running.
     fn set_yielded_state(&self);
 
+    /// Move this process from the running state to the yielded state until the
+    /// upcall `callback_id` is scheduled for it. Other tasks stay queued until
+    /// the wait ends, and do not count as work for the kernel until then.
+    ///
+    /// This will fail (i.e. not do anything) if the process was not previously
+    /// running.
+    fn wait_for_upcall(&self, callback_id: CallbackId);
+
+    /// The upcall this process is waiting for, if it is waiting for one.
+    fn waiting_for_upcall(&self) -> Option<CallbackId>;
+
+    /// If the process is waiting for an upcall and it has been scheduled,
+    /// remove it from the task queue, end the wait and return the arguments of
+    /// the upcall to the process as the return values of its system call. The
+    /// process is then running again.
+    ///
+    /// Returns whether the wait ended.
+    fn finish_wait(&self) -> bool;
+
     /// Move this process from running or yielded state into the stopped state.
     ///
     /// This will fail (i.e. not do anything) if the process was not either
@@ -310,14 +434,85 @@ pub trait ProcessType {
 
     /// Put this process in the fault state. This will trigger the
     /// `FaultResponse` for this process to occur.
+    ///
+    /// This is for faults the kernel decides on itself, for example when the
+    /// process exceeds a quota. Use `set_hardware_fault_state()` when the
+    /// process caused a hardware fault.
     fn set_fault_state(&self);
 
+    /// Put this process in the fault state after it caused a hardware fault,
+    /// so that fault handlers also see the fault status the architecture
+    /// recorded.
+    fn set_hardware_fault_state(&self);
+
+    /// Stop and clear a process's state.
+    ///
+    /// This will end the process, but does not reset it such that it could be
+    /// restarted and run again. This function instead frees grants and any
+    /// queued tasks for this process, but leaves the debug information about
+    /// the process and other state intact.
+    fn terminate(&self);
+
+    /// End the process because it called the exit syscall with
+    /// `completion_code`. The process is terminated, and if `restart` is true
+    /// it is then restarted as if it had faulted, so its restart policy
+    /// decides whether and when it starts again. A process whose fault
+    /// response is not `FaultResponse::Restart` is not restarted.
+    fn exit(&self, completion_code: usize, restart: bool);
+
+    /// Get the completion code the process passed when it last exited, if it
+    /// has exited.
+    fn get_completion_code(&self) -> Option<usize>;
+
     /// Returns how many times this process has been restarted.
     fn get_restart_count(&self) -> usize;
 
+    /// Restart a process that is in the `PendingRestart` state because its
+    /// restart policy delayed its restart. Does nothing if the process is in
+    /// any other state.
+    ///
+    /// Returns `true` if the process was started again.
+    fn finish_restart(&self) -> bool;
+
+    /// Terminate the process, whatever state it is in, and start it again
+    /// from its entry point regardless of its fault response. If it cannot be
+    /// started again it is left in the `StoppedFaulted` state.
+    ///
+    /// This must not be called while handling a system call of the process
+    /// itself, since the kernel would then set the return value of the system
+    /// call in the restarted process.
+    fn restart_now(&self);
+
     /// Get the name of the process. Used for IPC.
     fn get_process_name(&self) -> &'static str;
 
+    /// Whether the process was loaded with credentials that the board's
+    /// `ProcessCredentialsPolicy` verified. The credentials cover the TBF
+    /// header, so header fields such as the package name only identify the
+    /// app when this is true; otherwise any app can claim them.
+    fn has_verified_credentials(&self) -> bool;
+
+    /// Get the persistent short ID of the process.
+    ///
+    /// Unlike the identifier in `AppId`, the short ID stays the same across
+    /// restarts and reboots, so capsules can use it to key state that must
+    /// persist. It is the ID from the app's TBF header if it has one, and
+    /// otherwise a hash of the package name with the high bit set. Short IDs
+    /// are unique: an app whose short ID is already in use by a loaded process
+    /// is not loaded.
+    fn short_id(&self) -> u32;
+
+    /// Get the period and the execution budget per period, both in
+    /// microseconds, the process requested in its TBF header if it is a
+    /// periodic real-time task.
+    fn get_periodic_parameters(&self) -> Option<(u32, u32)>;
+
+    /// Get the scheduling priority of the process for fixed-priority
+    /// schedulers. Lower values are higher priorities. It is the priority
+    /// from the app's TBF header if it has one, and otherwise the lowest
+    /// priority, `u32::MAX`.
+    fn priority(&self) -> u32;
+
     // memop operations
 
     /// Change the location of the program break and reallocate the MPU region
@@ -352,6 +547,11 @@ pub trait ProcessType {
     /// The lowest address of the grant region for the process.
     fn kernel_memory_break(&self) -> *const u8;
 
+    /// Copy the memory the process can access, starting `offset` bytes after
+    /// `mem_start()` and ending at the app break, into `buffer`. Returns how
+    /// many bytes were copied.
+    fn read_app_memory(&self, offset: usize, buffer: &mut [u8]) -> usize;
+
     /// How many writeable flash regions defined in the TBF header for this
     /// process.
     fn number_writeable_flash_regions(&self) -> usize;
@@ -360,6 +560,15 @@ pub trait ProcessType {
     /// writeable flash region.
     fn get_writeable_flash_region(&self, region_index: usize) -> (u32, u32);
 
+    /// Check whether the permissions in the TBF header for this process allow
+    /// it to make this system call. Processes without a permissions entry in
+    /// their header may make any system call.
+    ///
+    /// The permissions are only as trustworthy as the header. Boards that rely
+    /// on them must load processes with a `ProcessCredentialsPolicy` that
+    /// rejects apps without valid credentials, which cover the header.
+    fn is_syscall_permitted(&self, syscall: &Syscall) -> bool;
+
     /// Debug function to update the kernel on where the stack starts for this
     /// process. Processes are not required to call this through the memop
     /// system call, but it aids in debugging the process.
@@ -369,6 +578,16 @@ pub trait ProcessType {
     /// Also optional.
     fn update_heap_start_pointer(&self, heap_pointer: *const u8);
 
+    /// Tell the kernel how large the stack that starts at the address passed
+    /// to `update_stack_start_pointer()` is, so that the kernel can protect
+    /// the memory below it with an MPU guard region. A stack overflow then
+    /// faults the process immediately, instead of corrupting the memory below
+    /// the stack.
+    ///
+    /// No guard region is needed if the stack ends at the start of the process
+    /// memory, because the memory below it is not accessible to the process.
+    fn protect_stack(&self, stack_size: usize) -> Result<(), Error>;
+
     // additional memop like functions
 
     /// Creates an `AppSlice` from the given offset and size in process memory.
@@ -393,6 +612,22 @@ pub trait ProcessType {
         size: usize,
     ) -> Result<Option<AppSlice<Shared, u8>>, ReturnCode>;
 
+    /// Creates a `ReadOnlyAppSlice` from the given address and size. Unlike
+    /// `allow()`, the buffer may be in the process's flash as well as in the
+    /// memory the process owns.
+    ///
+    /// ## Returns
+    ///
+    /// If the buffer is null this returns `None`, signaling the capsule to
+    /// delete the entry. If the buffer is within the process's accessible
+    /// memory or its flash, returns a `ReadOnlyAppSlice` wrapping that buffer.
+    /// Otherwise, returns an error `ReturnCode`.
+    fn allow_readonly(
+        &self,
+        buf_start_addr: *const u8,
+        size: usize,
+    ) -> Result<Option<ReadOnlyAppSlice<u8>>, ReturnCode>;
+
     /// Get the first address of process's flash that isn't protected by the
     /// kernel. The protected range of flash contains the TBF header and
     /// potentially other state the kernel is storing on behalf of the process,
@@ -420,15 +655,32 @@ pub trait ProcessType {
         min_region_size: usize,
     ) -> Option<mpu::Region>;
 
+    /// Remove an MPU region that was allocated with `add_mpu_region()`, so
+    /// that the process can no longer access that memory.
+    ///
+    /// Returns `Err` if the region was not allocated for this process.
+    fn remove_mpu_region(&self, region: mpu::Region) -> Result<(), ()>;
+
+    /// Remove every MPU region allocated with `add_mpu_region()` that
+    /// overlaps the memory from `start` up to `end`. This is used to take away
+    /// access to memory another process shared before that memory is reused.
+    ///
+    /// Returns `Err` if any of those regions could not be removed.
+    fn remove_mpu_regions_in(&self, start: *const u8, end: *const u8) -> Result<(), ()>;
+
     // grants
 
     /// Create new memory in the grant region, and check that the MPU region
     /// covering program memory does not extend past the kernel memory break.
+    /// Memory freed with `free()` is reused before the grant region grows.
     ///
     /// This will return `None` and fail if the process is inactive.
     fn alloc(&self, size: usize, align: usize) -> Option<NonNull<u8>>;
 
-    unsafe fn free(&self, _: *mut u8);
+    /// Free memory allocated with `alloc()` so it can be reused. Pointers
+    /// that were not returned by `alloc()`, such as pointers into app-owned
+    /// memory, are ignored.
+    unsafe fn free(&self, ptr: *mut u8);
 
     /// Get the grant pointer for this grant number.
     ///
@@ -455,6 +707,13 @@ pub trait ProcessType {
     /// the process will not run again).
     unsafe fn set_syscall_return_value(&self, return_value: isize);
 
+    /// Set the return value the process should see when it begins executing
+    /// again after the syscall, which can have more than one value.
+    ///
+    /// It is not valid to call this function when the process is inactive (i.e.
+    /// the process will not run again).
+    unsafe fn set_syscall_return(&self, return_value: SyscallReturn);
+
     /// Set the function that is to be executed when the process is resumed.
     ///
     /// It is not valid to call this function when the process is inactive (i.e.
@@ -489,9 +748,82 @@ pub trait ProcessType {
     /// Increment the number of times the process has exceeded its timeslice.
     fn debug_timeslice_expired(&self);
 
+    /// Record that the scheduler ran the process, for `execution_time_us` if
+    /// the scheduler gave it a timeslice and so the time is known.
+    fn debug_scheduled(&self, execution_time_us: Option<u32>);
+
+    /// Returns how many times the process has been scheduled since the kernel
+    /// started.
+    fn debug_scheduled_count(&self) -> usize;
+
+    /// Returns the total CPU time in microseconds the process has used since
+    /// the kernel started. Only runs with a timeslice are measured, so this is
+    /// 0 under schedulers that do not use timeslices.
+    fn debug_cpu_time_us(&self) -> u64;
+
+    /// Returns the longest time in microseconds the process ran for at once,
+    /// among the runs that were measured.
+    fn debug_longest_run_us(&self) -> u32;
+
+    /// Returns what the process has used of its quotas in the current quota
+    /// window.
+    fn debug_quota_usage(&self) -> QuotaUsage;
+
+    /// Update what the process has used of its quotas. Called by the quota
+    /// enforcer.
+    fn debug_set_quota_usage(&self, usage: QuotaUsage);
+
+    /// Returns how many times the process used up its execution budget for a
+    /// period before it finished the work of that period.
+    fn debug_budget_overrun_count(&self) -> usize;
+
+    /// Increment the number of times the process has used up its execution
+    /// budget. Called by schedulers that enforce budgets.
+    fn debug_budget_overrun(&self);
+
     /// Increment the number of times the process called a syscall and record
     /// the last syscall that was called.
     fn debug_syscall_called(&self, last_syscall: Syscall);
+
+    /// Turn printing the system calls and callbacks of this process to the
+    /// debug output on or off.
+    fn debug_set_trace_syscalls(&self, enabled: bool);
+
+    /// Whether the system calls and callbacks of this process are printed to
+    /// the debug output.
+    fn debug_trace_syscalls(&self) -> bool;
+}
+
+/// One entry of the processes array the kernel owns.
+///
+/// Schedulers keep a reference to the slot of each process they schedule.
+/// Processes can be added to and removed from the slots while the kernel
+/// runs (see `ProcessLoader`), so the process in a slot must be read again
+/// each time it is used.
+#[repr(transparent)]
+pub struct ProcessSlot {
+    proc: Cell<Option<&'static dyn ProcessType>>,
+}
+
+impl ProcessSlot {
+    /// View the cells of the processes array as slots.
+    pub(crate) fn from_cells(
+        cells: &'static [Cell<Option<&'static dyn ProcessType>>],
+    ) -> &'static [ProcessSlot] {
+        // Safety: `ProcessSlot` is a transparent wrapper around the cell.
+        unsafe {
+            &*(cells as *const [Cell<Option<&'static dyn ProcessType>>] as *const [ProcessSlot])
+        }
+    }
+
+    /// Get the process in this slot, if there is one.
+    pub fn get(&self) -> Option<&'static dyn ProcessType> {
+        self.proc.get()
+    }
+
+    pub(crate) fn set(&self, process: Option<&'static dyn ProcessType>) {
+        self.proc.set(process);
+    }
 }
 
 /// Generic trait for implementing process restart policies.
@@ -503,6 +835,32 @@ pub trait ProcessRestartPolicy {
     ///
     /// Returns `true` if the process should be restarted, `false` otherwise.
     fn should_restart(&self, process: &dyn ProcessType) -> bool;
+
+    /// Decide whether to restart the `process` now, later, or not at all.
+    ///
+    /// Policies that return `RestartDecision::Later` are responsible for
+    /// calling `finish_restart()` on the process when it should restart. By
+    /// default the decision is made by `should_restart()` and restarts happen
+    /// right away.
+    fn restart_decision(&self, process: &dyn ProcessType) -> RestartDecision {
+        if self.should_restart(process) {
+            RestartDecision::Now
+        } else {
+            RestartDecision::Never
+        }
+    }
+}
+
+/// When a faulted process should be restarted.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RestartDecision {
+    /// Restart the process right away.
+    Now,
+    /// Leave the process in the `PendingRestart` state until the restart
+    /// policy restarts it.
+    Later,
+    /// Do not restart the process.
+    Never,
 }
 
 /// Implementation of `ProcessRestartPolicy` that uses a threshold to decide
@@ -547,6 +905,246 @@ impl ProcessRestartPolicy for ThresholdRestartThenPanic {
     }
 }
 
+/// Per-process state kept by `BackoffRestart`. Boards provide an array of
+/// these with one entry per process slot.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RestartBackoffEntry {
+    /// How many times in a row the process faulted before staying up for the
+    /// stable uptime.
+    failures: u32,
+    /// When the process was last restarted, in alarm ticks.
+    started: Option<u32>,
+    /// When the pending restart of the process is due, in alarm ticks.
+    restart_at: Option<u32>,
+}
+
+impl RestartBackoffEntry {
+    pub const fn new() -> RestartBackoffEntry {
+        RestartBackoffEntry {
+            failures: 0,
+            started: None,
+            restart_at: None,
+        }
+    }
+}
+
+/// Implementation of `ProcessRestartPolicy` that restarts faulted apps after
+/// a delay that doubles each time the app faults again, from `initial_delay_ms`
+/// up to `max_delay_ms`. An app that stays up for `stable_uptime_ms` after a
+/// restart is considered healthy again, and its next restart uses the initial
+/// delay. This keeps a crash-looping app from using up the CPU.
+///
+/// Like `ThresholdRestart`, an app that has been restarted more than
+/// `threshold` times is not restarted again. Pass `usize::MAX` to keep
+/// restarting it forever.
+///
+/// ```rust,ignore
+/// static mut RESTART_BACKOFF: [kernel::procs::RestartBackoffEntry; NUM_PROCS] =
+///     [kernel::procs::RestartBackoffEntry::new(); NUM_PROCS];
+///
+/// let restart_alarm = static_init!(
+///     VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+///     VirtualMuxAlarm::new(mux_alarm)
+/// );
+/// let restart_policy = static_init!(
+///     kernel::procs::BackoffRestart<'static, VirtualMuxAlarm<'static, sam4l::ast::Ast>>,
+///     kernel::procs::BackoffRestart::new(
+///         board_kernel,
+///         restart_alarm,
+///         100,
+///         60_000,
+///         10_000,
+///         usize::MAX,
+///         &mut RESTART_BACKOFF
+///     )
+/// );
+/// restart_alarm.set_client(restart_policy);
+/// let fault_response = kernel::procs::FaultResponse::Restart(restart_policy);
+/// ```
+pub struct BackoffRestart<'a, A: time::Alarm<'a>> {
+    kernel: &'static Kernel,
+    alarm: &'a A,
+    initial_delay_ms: u32,
+    max_delay_ms: u32,
+    stable_uptime_ms: u32,
+    threshold: usize,
+    entries: TakeCell<'a, [RestartBackoffEntry]>,
+}
+
+impl<'a, A: time::Alarm<'a>> BackoffRestart<'a, A> {
+    pub fn new(
+        kernel: &'static Kernel,
+        alarm: &'a A,
+        initial_delay_ms: u32,
+        max_delay_ms: u32,
+        stable_uptime_ms: u32,
+        threshold: usize,
+        entries: &'a mut [RestartBackoffEntry],
+    ) -> BackoffRestart<'a, A> {
+        BackoffRestart {
+            kernel,
+            alarm,
+            initial_delay_ms,
+            max_delay_ms,
+            stable_uptime_ms,
+            threshold,
+            entries: TakeCell::new(entries),
+        }
+    }
+
+    fn ms_to_ticks(ms: u32) -> u32 {
+        // Keep delays within half the range of the alarm so that they can be
+        // compared across wrap-around.
+        let ticks = ms as u64 * A::Frequency::frequency() as u64 / 1000;
+        min(ticks, i32::MAX as u64) as u32
+    }
+
+    /// Whether the alarm time `a` comes before `b`.
+    fn before(a: u32, b: u32) -> bool {
+        (b.wrapping_sub(a) as i32) > 0
+    }
+
+    /// Set the alarm for the next pending restart, if there is one.
+    fn arm(&self, now: u32, entries: &[RestartBackoffEntry]) {
+        let next = entries
+            .iter()
+            .filter_map(|entry| entry.restart_at)
+            .min_by_key(|restart_at| restart_at.wrapping_sub(now) as i32);
+        match next {
+            Some(restart_at) => self.alarm.set_alarm(restart_at),
+            None => self.alarm.disable(),
+        }
+    }
+}
+
+impl<'a, A: time::Alarm<'a>> ProcessRestartPolicy for BackoffRestart<'a, A> {
+    fn should_restart(&self, process: &dyn ProcessType) -> bool {
+        process.get_restart_count() <= self.threshold
+    }
+
+    fn restart_decision(&self, process: &dyn ProcessType) -> RestartDecision {
+        if !self.should_restart(process) {
+            return RestartDecision::Never;
+        }
+        let now = self.alarm.now();
+        let index = process.appid().index;
+        self.entries.map_or(RestartDecision::Now, |entries| {
+            let entry = match entries.get_mut(index) {
+                Some(entry) => entry,
+                None => return RestartDecision::Now,
+            };
+
+            let stable = entry.started.map_or(true, |started| {
+                now.wrapping_sub(started) >= Self::ms_to_ticks(self.stable_uptime_ms)
+            });
+            if stable {
+                entry.failures = 0;
+            }
+            let delay_ms = min(
+                (self.initial_delay_ms as u64) << min(entry.failures, 32),
+                self.max_delay_ms as u64,
+            ) as u32;
+            entry.failures = entry.failures.saturating_add(1);
+
+            if delay_ms == 0 {
+                entry.started = Some(now);
+                return RestartDecision::Now;
+            }
+            entry.restart_at = Some(now.wrapping_add(Self::ms_to_ticks(delay_ms)));
+            self.arm(now, entries);
+            RestartDecision::Later
+        })
+    }
+}
+
+impl<'a, A: time::Alarm<'a>> time::AlarmClient for BackoffRestart<'a, A> {
+    fn fired(&self) {
+        let now = self.alarm.now();
+        self.kernel.process_each(|process| {
+            let index = process.appid().index;
+            let due = self.entries.map_or(false, |entries| {
+                entries
+                    .get_mut(index)
+                    .map_or(false, |entry| match entry.restart_at {
+                        Some(restart_at) if !Self::before(now, restart_at) => {
+                            entry.restart_at = None;
+                            true
+                        }
+                        _ => false,
+                    })
+            });
+            // Only count uptime from a restart that actually happened.
+            if due && process.finish_restart() {
+                self.entries.map(|entries| {
+                    entries
+                        .get_mut(index)
+                        .map(|entry| entry.started = Some(now));
+                });
+            }
+        });
+        self.entries.map(|entries| self.arm(now, entries));
+    }
+}
+
+/// Generic trait for implementing process credentials checking policies.
+///
+/// This policy allows a board to specify which apps the kernel is willing to
+/// run. Before an enabled app is loaded, the kernel passes the credentials
+/// from its TBF header (if there are any) together with the parts of the TBF
+/// object they cover to the policy. The kernel does not implement any
+/// cryptography itself, so the policy is responsible for recomputing the
+/// hash, MAC, or signature and for holding any keys this requires.
+pub trait ProcessCredentialsPolicy {
+    /// Decide whether the process named `process_name` may be loaded.
+    ///
+    /// `credentials` is `None` if the app was not signed. `covered` holds the
+    /// bytes of the TBF object covered by the credentials: the whole object,
+    /// header included, except for the header checksum and the credentials
+    /// TLV. The pieces must be hashed in order, as if they were one buffer.
+    ///
+    /// Returns `true` if the process should be loaded, `false` otherwise.
+    fn check_credentials(
+        &self,
+        process_name: &'static str,
+        credentials: Option<tbfheader::TbfHeaderV2Credentials>,
+        covered: &[&'static [u8]],
+    ) -> bool;
+
+    /// Whether `check_credentials()` verifies the credentials of the apps it
+    /// loads, so that an app loaded with credentials is known to be
+    /// authentic. Policies that load apps without checking their credentials
+    /// must return `false`.
+    fn verifies_credentials(&self) -> bool {
+        true
+    }
+}
+
+/// Implementation of `ProcessCredentialsPolicy` that loads every app without
+/// looking at its credentials. This matches the behavior of the kernel before
+/// credentials were supported.
+pub struct NoCredentialsCheck {}
+
+impl NoCredentialsCheck {
+    pub const fn new() -> NoCredentialsCheck {
+        NoCredentialsCheck {}
+    }
+}
+
+impl ProcessCredentialsPolicy for NoCredentialsCheck {
+    fn check_credentials(
+        &self,
+        _process_name: &'static str,
+        _credentials: Option<tbfheader::TbfHeaderV2Credentials>,
+        _covered: &[&'static [u8]],
+    ) -> bool {
+        true
+    }
+
+    fn verifies_credentials(&self) -> bool {
+        false
+    }
+}
+
 /// Implementation of `ProcessRestartPolicy` that unconditionally restarts the
 /// app.
 pub struct AlwaysRestart {}
@@ -625,6 +1223,16 @@ pub enum State {
     /// The process has caused a fault.
     Fault,
 
+    /// The process faulted or exited asking to be restarted, and its restart
+    /// policy decided to restart it, but not yet. The process has been terminated, and the policy will restart
+    /// it later with `finish_restart()`.
+    PendingRestart,
+
+    /// The process exited on its own with the exit syscall and was not
+    /// restarted, either because it did not ask to be or because its restart
+    /// policy declined.
+    Terminated,
+
     /// The process has never actually been executed. This of course happens
     /// when the board first boots and the kernel has not switched to any
     /// processes yet. It can also happen if an process is terminated and all
@@ -740,6 +1348,28 @@ struct ProcessDebug {
     /// How many times this process has been paused because it exceeded its
     /// timeslice.
     timeslice_expiration_count: usize,
+
+    /// How many times the process has used up its execution budget for a
+    /// period, for schedulers that give processes a budget.
+    budget_overrun_count: usize,
+
+    /// How many times the scheduler has run this process. Unlike the counts
+    /// above, this and the CPU time below are kept across restarts.
+    scheduled_count: usize,
+
+    /// Total CPU time the process has used in the runs that were measured,
+    /// in microseconds.
+    cpu_time_us: u64,
+
+    /// The longest measured run of the process, in microseconds.
+    longest_run_us: u32,
+
+    /// What the process has used of its quotas in the current window.
+    quota_usage: QuotaUsage,
+
+    /// Whether to print the system calls and callbacks of this process to the
+    /// debug output, even if syscall tracing is off for the whole kernel.
+    trace_syscalls: bool,
 }
 
 /// A type for userspace processes in Tock.
@@ -797,6 +1427,10 @@ pub struct Process<'a, C: 'static + Chip> {
     /// the kernel_memory break to without having to recalculate it.
     original_kernel_memory_break: *const u8,
 
+    /// Allocator for the kernel-owned memory between `kernel_memory_break`
+    /// and `original_kernel_memory_break`, which holds grant regions.
+    kernel_heap: KernelHeap,
+
     /// Pointer to the end of process RAM that has been sbrk'd to the process.
     app_break: Cell<*const u8>,
     original_app_break: *const u8,
@@ -841,6 +1475,15 @@ pub struct Process<'a, C: 'static + Chip> {
     /// MPU regions are saved as a pointer-size pair.
     mpu_regions: [Cell<Option<mpu::Region>>; 6],
 
+    /// The lowest address of the process stack. This is the start of the
+    /// process memory unless the process told us about a stack that ends
+    /// higher up.
+    stack_bottom: Cell<*const u8>,
+
+    /// The MPU region that protects the memory below the process stack, if
+    /// there is one.
+    stack_guard: Cell<Option<mpu::Region>>,
+
     /// Essentially a list of callbacks that want to call functions in the
     /// process.
     tasks: MapCell<RingBuffer<'a, Task>>,
@@ -850,9 +1493,20 @@ pub struct Process<'a, C: 'static + Chip> {
     /// determine if the process should be restarted or not.
     restart_count: Cell<usize>,
 
+    /// The completion code the process passed when it last exited.
+    completion_code: Cell<Option<usize>>,
+
+    /// The upcall the process is blocked on with the command-and-wait system
+    /// call, if any.
+    waiting_for: Cell<Option<CallbackId>>,
+
     /// Name of the app.
     process_name: &'static str,
 
+    /// Whether the app was loaded with credentials the credentials policy
+    /// verified.
+    verified_credentials: bool,
+
     /// Values kept so that we can print useful debug messages when apps fault.
     debug: MapCell<ProcessDebug>,
 }
@@ -869,6 +1523,7 @@ impl<C: Chip> ProcessType for Process<'_, C> {
             return false;
         }
 
+        let counts_as_work = self.counts_as_work(&task);
         let ret = self.tasks.map_or(false, |tasks| tasks.enqueue(task));
 
         // Make a note that we lost this callback if the enqueue function
@@ -877,7 +1532,7 @@ impl<C: Chip> ProcessType for Process<'_, C> {
             self.debug.map(|debug| {
                 debug.dropped_callback_count += 1;
             });
-        } else {
+        } else if counts_as_work {
             self.kernel.increment_work();
         }
 
@@ -885,8 +1540,14 @@ impl<C: Chip> ProcessType for Process<'_, C> {
     }
 
     fn ready(&self) -> bool {
-        self.tasks.map_or(false, |ring_buf| ring_buf.has_elements())
-            || self.state.get() == State::Running
+        match self.waiting_for.get() {
+            // Only the upcall the process waits for can make it run.
+            Some(_) => self.tasks_counted_as_work() > 0,
+            None => {
+                self.tasks.map_or(false, |ring_buf| ring_buf.has_elements())
+                    || self.state.get() == State::Running
+            }
+        }
     }
 
     fn remove_pending_callbacks(&self, callback_id: CallbackId) {
@@ -901,14 +1562,16 @@ impl<C: Chip> ProcessType for Process<'_, C> {
                         if id != callback_id {
                             true
                         } else {
-                            self.kernel.decrement_work();
+                            if self.counts_as_work(task) {
+                                self.kernel.decrement_work();
+                            }
                             false
                         }
                     }
                 },
                 _ => true,
             });
-            if config::CONFIG.trace_syscalls {
+            if self.kernel.trace_syscalls_of(self) {
                 let count_after = tasks.len();
                 debug!(
                     "[{:?}] remove_pending_callbacks[{:#x}:{}] = {} callback(s) removed",
@@ -932,6 +1595,60 @@ impl<C: Chip> ProcessType for Process<'_, C> {
         }
     }
 
+    fn wait_for_upcall(&self, callback_id: CallbackId) {
+        if self.state.get() == State::Running {
+            self.set_waiting_for(Some(callback_id));
+            self.set_yielded_state();
+        }
+    }
+
+    fn waiting_for_upcall(&self) -> Option<CallbackId> {
+        self.waiting_for.get()
+    }
+
+    fn finish_wait(&self) -> bool {
+        let callback_id = match self.waiting_for.get() {
+            Some(callback_id) => callback_id,
+            None => return false,
+        };
+        if self.state.get() != State::Yielded {
+            return false;
+        }
+
+        // Take the first upcall for `callback_id` out of the queue, keeping
+        // everything else in order.
+        let mut upcall = None;
+        self.tasks.map(|tasks| {
+            tasks.retain(|task| match task {
+                Task::FunctionCall(function_call) if upcall.is_none() => {
+                    match function_call.source {
+                        FunctionCallSource::Driver(id) if id == callback_id => {
+                            upcall = Some(*function_call);
+                            false
+                        }
+                        _ => true,
+                    }
+                }
+                _ => true,
+            });
+        });
+
+        upcall.map_or(false, |upcall| {
+            self.kernel.decrement_work();
+            self.set_waiting_for(None);
+            unsafe {
+                self.set_syscall_return(SyscallReturn::Success(
+                    upcall.argument0,
+                    upcall.argument1,
+                    upcall.argument2,
+                ));
+            }
+            self.kernel.increment_work();
+            self.state.set(State::Running);
+            true
+        })
+    }
+
     fn stop(&self) {
         match self.state.get() {
             State::Running => self.state.set(State::StoppedRunning),
@@ -949,37 +1666,83 @@ impl<C: Chip> ProcessType for Process<'_, C> {
     }
 
     fn set_fault_state(&self) {
-        self.state.set(State::Fault);
+        self.fault(false);
+    }
 
-        match self.fault_response {
-            FaultResponse::Panic => {
-                // process faulted. Panic and print status
-                panic!("Process {} had a fault", self.process_name);
-            }
-            FaultResponse::Restart(_) => {
-                self.restart(State::StoppedFaulted);
-            }
-            FaultResponse::Stop => {
-                // This looks a lot like restart, except we just leave the app
-                // how it faulted and mark it as `StoppedFaulted`. By clearing
-                // all of the app's todo work it will not be scheduled, and
-                // clearing all of the grant regions will cause capsules to drop
-                // this app as well.
-                self.terminate();
-            }
-        }
+    fn set_hardware_fault_state(&self) {
+        self.fault(true);
     }
 
     fn get_restart_count(&self) -> usize {
         self.restart_count.get()
     }
 
+    fn exit(&self, completion_code: usize, restart: bool) {
+        self.completion_code.set(Some(completion_code));
+        if restart {
+            // Going through the restart policy keeps an app that exits and
+            // restarts in a loop from using up the CPU, the same as one that
+            // faults in a loop.
+            self.restart(State::Terminated);
+        } else {
+            self.terminate();
+            self.state.set(State::Terminated);
+        }
+    }
+
+    fn get_completion_code(&self) -> Option<usize> {
+        self.completion_code.get()
+    }
+
+    fn finish_restart(&self) -> bool {
+        if self.state.get() != State::PendingRestart {
+            return false;
+        }
+        // If the process cannot be started again it is left faulted.
+        self.state.set(State::StoppedFaulted);
+        self.start_again();
+        self.state.get() != State::StoppedFaulted
+    }
+
+    fn restart_now(&self) {
+        self.terminate();
+        self.start_again();
+    }
+
+    fn terminate(&self) {
+        // A running process counts as outstanding work for the kernel, so
+        // that must be removed as well.
+        if self.state.get() == State::Running {
+            self.kernel.decrement_work();
+        }
+
+        // Remove the tasks that were scheduled for the app from the
+        // amount of work queue.
+        for _ in 0..self.tasks_counted_as_work() {
+            self.kernel.decrement_work();
+        }
+
+        // And remove those tasks
+        self.tasks.map(|tasks| {
+            tasks.empty();
+        });
+        self.waiting_for.set(None);
+
+        // Clear any grant regions this app has setup with any capsules.
+        unsafe {
+            self.grant_ptrs_reset();
+        }
+
+        // Mark the app as stopped so the scheduler won't try to run it.
+        self.state.set(State::StoppedFaulted);
+    }
+
     fn dequeue_task(&self) -> Option<Task> {
-        self.tasks.map_or(None, |tasks| {
-            tasks.dequeue().map(|cb| {
+        self.tasks.map_or(None, |tasks| tasks.dequeue()).map(|cb| {
+            if self.counts_as_work(&cb) {
                 self.kernel.decrement_work();
-                cb
-            })
+            }
+            cb
         })
     }
 
@@ -1007,6 +1770,15 @@ impl<C: Chip> ProcessType for Process<'_, C> {
         self.kernel_memory_break.get()
     }
 
+    fn read_app_memory(&self, offset: usize, buffer: &mut [u8]) -> usize {
+        let app_size = self.app_break.get() as usize - self.mem_start() as usize;
+        let length = min(app_size.saturating_sub(offset), buffer.len());
+        if length > 0 {
+            buffer[..length].copy_from_slice(&self.memory[offset..offset + length]);
+        }
+        length
+    }
+
     fn number_writeable_flash_regions(&self) -> usize {
         self.header.number_writeable_flash_regions()
     }
@@ -1015,6 +1787,29 @@ impl<C: Chip> ProcessType for Process<'_, C> {
         self.header.get_writeable_flash_region(region_index)
     }
 
+    fn is_syscall_permitted(&self, syscall: &Syscall) -> bool {
+        match *syscall {
+            Syscall::YIELD | Syscall::MEMOP { .. } | Syscall::EXIT { .. } => true,
+            Syscall::SUBSCRIBE { driver_number, .. }
+            | Syscall::ALLOW { driver_number, .. }
+            | Syscall::READONLY_ALLOW { driver_number, .. } => {
+                self.header.is_driver_permitted(driver_number, None)
+            }
+            Syscall::COMMAND {
+                driver_number,
+                subdriver_number,
+                ..
+            }
+            | Syscall::COMMAND_WAIT {
+                driver_number,
+                subdriver_number,
+                ..
+            } => self
+                .header
+                .is_driver_permitted(driver_number, Some(subdriver_number)),
+        }
+    }
+
     fn update_stack_start_pointer(&self, stack_pointer: *const u8) {
         if stack_pointer >= self.mem_start() && stack_pointer < self.mem_end() {
             self.debug.map(|debug| {
@@ -1027,6 +1822,79 @@ impl<C: Chip> ProcessType for Process<'_, C> {
         }
     }
 
+    fn protect_stack(&self, stack_size: usize) -> Result<(), Error> {
+        // Do not modify an inactive process.
+        if !self.is_active() {
+            return Err(Error::InactiveApp);
+        }
+
+        let stack_start = self
+            .debug
+            .map_or(None, |debug| debug.app_stack_start_pointer)
+            .ok_or(Error::AddressOutOfBounds)?;
+        let stack_bottom = (stack_start as usize)
+            .checked_sub(stack_size)
+            .filter(|bottom| *bottom >= self.mem_start() as usize)
+            .ok_or(Error::AddressOutOfBounds)? as *const u8;
+
+        // The guard of a stack the process told us about before stays in
+        // place until the new guard is allocated, so that a failure leaves
+        // the process with its old, still protected stack. The new guard
+        // takes the slot of the old one.
+        let old_guard = self.stack_guard.get();
+        let guard = if stack_bottom == self.mem_start() {
+            None
+        } else {
+            if old_guard.is_none() && self.mpu_regions.iter().all(|r| r.get().is_some()) {
+                // Not enough room in Process struct to store the MPU region.
+                return Err(Error::OutOfMemory);
+            }
+
+            // Allocate the guard region in the memory just below the stack. If
+            // the alignment rules of the MPU do not allow the region to end
+            // right at the stack, we give it room to move down.
+            let guard = [STACK_GUARD_SIZE, 2 * STACK_GUARD_SIZE]
+                .iter()
+                .find_map(|area_size| {
+                    let area_start = max(
+                        self.mem_start() as usize,
+                        (stack_bottom as usize).saturating_sub(*area_size),
+                    );
+                    self.mpu_config.map_or(None, |config| {
+                        self.chip.mpu().allocate_region(
+                            area_start as *const u8,
+                            stack_bottom as usize - area_start,
+                            STACK_GUARD_SIZE,
+                            mpu::Permissions::NoAccess,
+                            config,
+                        )
+                    })
+                })
+                .ok_or(Error::OutOfMemory)?;
+            Some(guard)
+        };
+
+        if let Some(old_guard) = old_guard {
+            if self.remove_mpu_region(old_guard).is_err() {
+                if let Some(guard) = guard {
+                    let _ = self
+                        .mpu_config
+                        .map(|config| self.chip.mpu().remove_memory_region(guard, config));
+                }
+                return Err(Error::KernelError);
+            }
+        }
+        if let Some(guard) = guard {
+            self.mpu_regions
+                .iter()
+                .find(|region| region.get().is_none())
+                .map(|region| region.set(Some(guard)));
+        }
+        self.stack_guard.set(guard);
+        self.stack_bottom.set(stack_bottom);
+        Ok(())
+    }
+
     fn update_heap_start_pointer(&self, heap_pointer: *const u8) {
         if heap_pointer >= self.mem_start() && heap_pointer < self.mem_end() {
             self.debug.map(|debug| {
@@ -1072,6 +1940,36 @@ impl<C: Chip> ProcessType for Process<'_, C> {
         })
     }
 
+    fn remove_mpu_region(&self, region: mpu::Region) -> Result<(), ()> {
+        let slot = self
+            .mpu_regions
+            .iter()
+            .find(|r| {
+                r.get().map_or(false, |r| {
+                    r.start_address() == region.start_address() && r.size() == region.size()
+                })
+            })
+            .ok_or(())?;
+        self.mpu_config
+            .map_or(Err(()), |config| {
+                self.chip.mpu().remove_memory_region(region, config)
+            })
+            .map(|()| slot.set(None))
+    }
+
+    fn remove_mpu_regions_in(&self, start: *const u8, end: *const u8) -> Result<(), ()> {
+        let mut result = Ok(());
+        for region in self.mpu_regions.iter() {
+            if let Some(r) = region.get() {
+                let r_end = unsafe { r.start_address().add(r.size()) };
+                if r.start_address() < end && r_end > start && self.remove_mpu_region(r).is_err() {
+                    result = Err(());
+                }
+            }
+        }
+        result
+    }
+
     fn sbrk(&self, increment: isize) -> Result<*const u8, Error> {
         // Do not modify an inactive process.
         if !self.is_active() {
@@ -1147,51 +2045,72 @@ impl<C: Chip> ProcessType for Process<'_, C> {
         }
     }
 
+    fn allow_readonly(
+        &self,
+        buf_start_addr: *const u8,
+        size: usize,
+    ) -> Result<Option<ReadOnlyAppSlice<u8>>, ReturnCode> {
+        if !self.is_active() {
+            // Do not modify an inactive process.
+            return Err(ReturnCode::FAIL);
+        }
+
+        match NonNull::new(buf_start_addr as *mut u8) {
+            None => Ok(None),
+            Some(buf_start) => {
+                if self.in_app_owned_memory(buf_start_addr, size) {
+                    // A buffer in RAM must stay app-owned while it is shared,
+                    // so it moves the watermark just like a writeable buffer.
+                    let buf_end_addr = buf_start_addr.wrapping_add(size);
+                    let new_water_mark = max(self.allow_high_water_mark.get(), buf_end_addr);
+                    self.allow_high_water_mark.set(new_water_mark);
+                } else if !self.in_app_flash(buf_start_addr, size) {
+                    return Err(ReturnCode::EINVAL);
+                }
+
+                // The kernel only ever reads through a `ReadOnlyAppSlice`, so
+                // aliasing it with other shared buffers is harmless.
+                let slice = unsafe { ReadOnlyAppSlice::new(buf_start, size, self.appid()) };
+                Ok(Some(slice))
+            }
+        }
+    }
+
     fn alloc(&self, size: usize, align: usize) -> Option<NonNull<u8>> {
         // Do not modify an inactive process.
         if !self.is_active() {
             return None;
         }
 
-        self.mpu_config.and_then(|mut config| {
-            // First, compute the candidate new pointer. Note that at this
-            // point we have not yet checked whether there is space for
-            // this allocation or that it meets alignment requirements.
-            let new_break_unaligned = self
-                .kernel_memory_break
-                .get()
-                .wrapping_offset(-(size as isize));
-
-            // The alignment must be a power of two, 2^a. The expression
-            // `!(align - 1)` then returns a mask with leading ones,
-            // followed by `a` trailing zeros.
-            let alignment_mask = !(align - 1);
-            let new_break = (new_break_unaligned as usize & alignment_mask) as *const u8;
-
-            // Verify there is space for this allocation
-            if new_break < self.app_break.get() {
-                None
-            // Verify it didn't wrap around
-            } else if new_break > self.kernel_memory_break.get() {
-                None
-            } else if let Err(_) = self.chip.mpu().update_app_memory_region(
-                self.app_break.get(),
-                new_break,
-                mpu::Permissions::ReadWriteOnly,
-                &mut config,
-            ) {
-                None
-            } else {
-                self.kernel_memory_break.set(new_break);
-                unsafe {
-                    // Two unsafe steps here, both okay as we just made this pointer
-                    Some(NonNull::new_unchecked(new_break as *mut u8))
-                }
-            }
+        self.mpu_config.and_then(|mut config| unsafe {
+            // The heap only asks to move the kernel memory break when it
+            // cannot reuse freed memory. Verify there is space for the new
+            // break and that the MPU region covering app memory can be shrunk
+            // to end before it.
+            self.kernel_heap
+                .alloc(&self.kernel_memory_break, size, align, |new_break| {
+                    new_break >= self.app_break.get()
+                        && self
+                            .chip
+                            .mpu()
+                            .update_app_memory_region(
+                                self.app_break.get(),
+                                new_break,
+                                mpu::Permissions::ReadWriteOnly,
+                                &mut config,
+                            )
+                            .is_ok()
+                })
         })
     }
 
-    unsafe fn free(&self, _: *mut u8) {}
+    unsafe fn free(&self, ptr: *mut u8) {
+        self.kernel_heap.free(
+            &self.kernel_memory_break,
+            self.original_kernel_memory_break,
+            ptr,
+        );
+    }
 
     // This is safe today, as MPU constraints ensure that `mem_end` will always
     // be aligned on at least a word boundary. While this is unlikely to
@@ -1235,6 +2154,22 @@ impl<C: Chip> ProcessType for Process<'_, C> {
         self.process_name
     }
 
+    fn has_verified_credentials(&self) -> bool {
+        self.verified_credentials
+    }
+
+    fn short_id(&self) -> u32 {
+        short_id(&self.header)
+    }
+
+    fn get_periodic_parameters(&self) -> Option<(u32, u32)> {
+        self.header.get_periodic_parameters()
+    }
+
+    fn priority(&self) -> u32 {
+        self.header.get_priority().unwrap_or(u32::MAX)
+    }
+
     unsafe fn set_syscall_return_value(&self, return_value: isize) {
         self.stored_state.map(|stored_state| {
             self.chip
@@ -1243,6 +2178,16 @@ impl<C: Chip> ProcessType for Process<'_, C> {
         });
     }
 
+    unsafe fn set_syscall_return(&self, return_value: SyscallReturn) {
+        self.stored_state.map(|stored_state| {
+            self.chip.userspace_kernel_boundary().set_syscall_return(
+                self.sp(),
+                stored_state,
+                return_value,
+            );
+        });
+    }
+
     unsafe fn set_process_function(&self, callback: FunctionCall) {
         // First we need to get how much memory is available for this app's
         // stack. Since the stack is at the bottom of the process's memory
@@ -1348,6 +2293,45 @@ impl<C: Chip> ProcessType for Process<'_, C> {
             .map(|debug| debug.timeslice_expiration_count += 1);
     }
 
+    fn debug_scheduled(&self, execution_time_us: Option<u32>) {
+        self.debug.map(|debug| {
+            debug.scheduled_count += 1;
+            if let Some(execution_time_us) = execution_time_us {
+                debug.cpu_time_us += execution_time_us as u64;
+                debug.longest_run_us = max(debug.longest_run_us, execution_time_us);
+            }
+        });
+    }
+
+    fn debug_scheduled_count(&self) -> usize {
+        self.debug.map_or(0, |debug| debug.scheduled_count)
+    }
+
+    fn debug_cpu_time_us(&self) -> u64 {
+        self.debug.map_or(0, |debug| debug.cpu_time_us)
+    }
+
+    fn debug_longest_run_us(&self) -> u32 {
+        self.debug.map_or(0, |debug| debug.longest_run_us)
+    }
+
+    fn debug_quota_usage(&self) -> QuotaUsage {
+        self.debug
+            .map_or(QuotaUsage::default(), |debug| debug.quota_usage)
+    }
+
+    fn debug_set_quota_usage(&self, usage: QuotaUsage) {
+        self.debug.map(|debug| debug.quota_usage = usage);
+    }
+
+    fn debug_budget_overrun_count(&self) -> usize {
+        self.debug.map_or(0, |debug| debug.budget_overrun_count)
+    }
+
+    fn debug_budget_overrun(&self) {
+        self.debug.map(|debug| debug.budget_overrun_count += 1);
+    }
+
     fn debug_syscall_called(&self, last_syscall: Syscall) {
         self.debug.map(|debug| {
             debug.syscall_count += 1;
@@ -1355,6 +2339,14 @@ impl<C: Chip> ProcessType for Process<'_, C> {
         });
     }
 
+    fn debug_set_trace_syscalls(&self, enabled: bool) {
+        self.debug.map(|debug| debug.trace_syscalls = enabled);
+    }
+
+    fn debug_trace_syscalls(&self) -> bool {
+        self.debug.map_or(false, |debug| debug.trace_syscalls)
+    }
+
     unsafe fn print_memory_map(&self, writer: &mut dyn Write) {
         // Flash
         let flash_end = self.flash.as_ptr().add(self.flash.len()) as usize;
@@ -1471,7 +2463,7 @@ impl<C: Chip> ProcessType for Process<'_, C> {
         match sram_stack_start {
             Some(sram_stack_start) => {
                 let sram_stack_size = sram_stack_start - sram_stack_bottom;
-                let sram_stack_allocated = sram_stack_start - sram_start;
+                let sram_stack_allocated = sram_stack_start - self.stack_bottom.get() as usize;
 
                 let _ = writer.write_fmt(format_args!(
                     "\
@@ -1517,6 +2509,24 @@ impl<C: Chip> ProcessType for Process<'_, C> {
     unsafe fn print_full_process(&self, writer: &mut dyn Write) {
         self.print_memory_map(writer);
 
+        // The stack pointer ends up below the stack when the process overflows
+        // its stack, whether that faulted on the stack guard or outside of the
+        // process memory.
+        if self.current_stack_pointer.get() < self.stack_bottom.get() {
+            let _ = writer.write_fmt(format_args!(
+                "\
+                 \r\n Stack overflow: the stack pointer {:#010X} is below the bottom\
+                 \r\n of the stack at {:#010X}{}.\r\n",
+                self.current_stack_pointer.get() as usize,
+                self.stack_bottom.get() as usize,
+                if self.stack_guard.get().is_some() {
+                    ", in the stack guard region"
+                } else {
+                    ""
+                },
+            ));
+        }
+
         self.stored_state.map(|stored_state| {
             self.chip
                 .userspace_kernel_boundary()
@@ -1570,6 +2580,99 @@ fn exceeded_check(size: usize, allocated: usize) -> &'static str {
 }
 
 impl<C: 'static + Chip> Process<'_, C> {
+    /// Whether the queued `task` counts as work for the kernel. While the
+    /// process waits for an upcall only that upcall can make it run, so other
+    /// tasks do not count until the wait ends.
+    fn counts_as_work(&self, task: &Task) -> bool {
+        match (self.waiting_for.get(), task) {
+            (None, _) => true,
+            (Some(callback_id), Task::FunctionCall(function_call)) => match function_call.source {
+                FunctionCallSource::Driver(id) => id == callback_id,
+                FunctionCallSource::Kernel => false,
+            },
+            (Some(_), Task::IPC(_)) => false,
+        }
+    }
+
+    /// How many of the queued tasks count as work for the kernel.
+    fn tasks_counted_as_work(&self) -> usize {
+        let mut count = 0;
+        self.tasks.map(|tasks| {
+            tasks.retain(|task| {
+                if self.counts_as_work(task) {
+                    count += 1;
+                }
+                true
+            })
+        });
+        count
+    }
+
+    /// Start or end waiting for an upcall, and update the kernel's work count
+    /// for the queued tasks that start or stop counting as work.
+    fn set_waiting_for(&self, waiting_for: Option<CallbackId>) {
+        let counted_before = self.tasks_counted_as_work();
+        self.waiting_for.set(waiting_for);
+        let counted_after = self.tasks_counted_as_work();
+        for _ in counted_after..counted_before {
+            self.kernel.decrement_work();
+        }
+        for _ in counted_before..counted_after {
+            self.kernel.increment_work();
+        }
+    }
+
+    /// Put the process in the fault state, let the board's fault handlers
+    /// record the fault, and then apply the process's `FaultResponse`.
+    /// `hardware_fault` is `false` if the kernel faulted the process itself.
+    fn fault(&self, hardware_fault: bool) {
+        self.state.set(State::Fault);
+
+        // Let the board's fault handlers see the process before the fault
+        // response changes its state.
+        if self.kernel.has_fault_handlers() {
+            let mut context = syscall::FaultContext::default();
+            context.hardware_fault = hardware_fault;
+            self.stored_state.map(|stored_state| unsafe {
+                self.chip.userspace_kernel_boundary().store_fault_context(
+                    self.sp(),
+                    stored_state,
+                    &mut context,
+                );
+            });
+            let memory_map = ProcessMemoryMap {
+                flash_start: self.flash_start() as usize,
+                flash_end: self.flash_end() as usize,
+                memory_start: self.mem_start() as usize,
+                memory_end: self.mem_end() as usize,
+                app_break: self.app_break.get() as usize,
+                kernel_memory_break: self.kernel_memory_break.get() as usize,
+                stack_pointer: self.sp() as usize,
+            };
+            self.kernel.fault_handler_each(|fault_handler| {
+                fault_handler.process_fault(self, &context, &memory_map)
+            });
+        }
+
+        match self.fault_response {
+            FaultResponse::Panic => {
+                // process faulted. Panic and print status
+                panic!("Process {} had a fault", self.process_name);
+            }
+            FaultResponse::Restart(_) => {
+                self.restart(State::StoppedFaulted);
+            }
+            FaultResponse::Stop => {
+                // This looks a lot like restart, except we just leave the app
+                // how it faulted and mark it as `StoppedFaulted`. By clearing
+                // all of the app's todo work it will not be scheduled, and
+                // clearing all of the grant regions will cause capsules to drop
+                // this app as well.
+                self.terminate();
+            }
+        }
+    }
+
     pub(crate) unsafe fn create(
         kernel: &'static Kernel,
         chip: &'static C,
@@ -1578,6 +2681,7 @@ impl<C: 'static + Chip> Process<'_, C> {
         app_version: u16,
         remaining_memory: &'static mut [u8],
         fault_response: FaultResponse,
+        credentials_policy: &'static dyn ProcessCredentialsPolicy,
         index: usize,
     ) -> Result<(Option<&'static dyn ProcessType>, &'static mut [u8]), ProcessLoadError> {
         // Get a slice for just the app header.
@@ -1611,7 +2715,7 @@ impl<C: 'static + Chip> Process<'_, C> {
         // isn't enabled, then we can skip it and do not create a `Process`
         // object.
         if !tbf_header.is_app() || !tbf_header.enabled() {
-            if config::CONFIG.debug_load_processes {
+            if kernel.debug_config().debug_load_processes() {
                 if !tbf_header.is_app() {
                     debug!(
                         "Padding in flash={:#010X}-{:#010X}",
@@ -1632,6 +2736,63 @@ impl<C: 'static + Chip> Process<'_, C> {
             return Ok((None, remaining_memory));
         }
 
+        // Skip apps built for a different kernel ABI, so that the apps after
+        // them can still be loaded.
+        if !is_kernel_version_compatible(&tbf_header) {
+            if kernel.debug_config().debug_load_processes() {
+                debug!(
+                    "[!] flash={:#010X}-{:#010X} process={:?} - built for kernel versions {:?}, kernel is {}.{}",
+                    app_flash.as_ptr() as usize,
+                    app_flash.as_ptr() as usize + app_flash.len() - 1,
+                    process_name,
+                    tbf_header.get_kernel_version(),
+                    crate::KERNEL_MAJOR_VERSION,
+                    crate::KERNEL_MINOR_VERSION
+                );
+            }
+            return Ok((None, remaining_memory));
+        }
+
+        // Short IDs identify apps to capsules, so two processes may not share
+        // one. The app loaded first keeps it.
+        if let Some(other) = kernel.lookup_app_by_short_id(short_id(&tbf_header)) {
+            if kernel.debug_config().debug_load_processes() {
+                debug!(
+                    "[!] flash={:#010X}-{:#010X} process={:?} - short ID {:#010X} is in use by process[{}]",
+                    app_flash.as_ptr() as usize,
+                    app_flash.as_ptr() as usize + app_flash.len() - 1,
+                    process_name,
+                    short_id(&tbf_header),
+                    other.index
+                );
+            }
+            return Ok((None, remaining_memory));
+        }
+
+        // Check the app against the board's credentials policy before we
+        // commit any resources to it. The credentials cover the header as
+        // well, so that the fields in it cannot be changed either.
+        let covered = tbf_header
+            .get_credentials_covered(app_flash)
+            .ok_or(ProcessLoadError::NotEnoughFlash)?;
+        let credentials = tbf_header.get_credentials();
+        let verified_credentials =
+            credentials.is_some() && credentials_policy.verifies_credentials();
+        if !credentials_policy.check_credentials(process_name.unwrap_or(""), credentials, &covered)
+        {
+            // Skip the app rather than failing, so that one app with bad
+            // credentials does not stop the apps after it from loading.
+            if kernel.debug_config().debug_load_processes() {
+                debug!(
+                    "[!] flash={:#010X}-{:#010X} process={:?} - credentials rejected",
+                    app_flash.as_ptr() as usize,
+                    app_flash.as_ptr() as usize + app_flash.len() - 1,
+                    process_name
+                );
+            }
+            return Ok((None, remaining_memory));
+        }
+
         // Otherwise, actually load the app.
         let mut min_app_ram_size = tbf_header.get_minimum_app_ram_size() as usize;
         let init_fn = app_flash
@@ -1653,7 +2814,7 @@ impl<C: 'static + Chip> Process<'_, C> {
             )
             .is_none()
         {
-            if config::CONFIG.debug_load_processes {
+            if kernel.debug_config().debug_load_processes() {
                 debug!(
                     "[!] flash={:#010X}-{:#010X} process={:?} - couldn't allocate MPU region for flash",
                     app_flash.as_ptr() as usize,
@@ -1754,7 +2915,7 @@ impl<C: 'static + Chip> Process<'_, C> {
             Some((memory_start, memory_size)) => (memory_start, memory_size),
             None => {
                 // Failed to load process. Insufficient memory.
-                if config::CONFIG.debug_load_processes {
+                if kernel.debug_config().debug_load_processes() {
                     debug!(
                         "[!] flash={:#010X}-{:#010X} process={:?} - couldn't allocate memory region of size >= {:#X}",
                         app_flash.as_ptr() as usize,
@@ -1879,6 +3040,7 @@ impl<C: 'static + Chip> Process<'_, C> {
         process.header = tbf_header;
         process.kernel_memory_break = Cell::new(kernel_memory_break);
         process.original_kernel_memory_break = kernel_memory_break;
+        process.kernel_heap = KernelHeap::new();
         process.app_break = Cell::new(initial_sbrk_pointer);
         process.original_app_break = initial_sbrk_pointer;
         process.current_stack_pointer = Cell::new(initial_stack_pointer);
@@ -1890,6 +3052,8 @@ impl<C: 'static + Chip> Process<'_, C> {
         process.state = Cell::new(State::Unstarted);
         process.fault_response = fault_response;
         process.restart_count = Cell::new(0);
+        process.completion_code = Cell::new(None);
+        process.waiting_for = Cell::new(None);
 
         process.mpu_config = MapCell::new(mpu_config);
         process.mpu_regions = [
@@ -1900,8 +3064,11 @@ impl<C: 'static + Chip> Process<'_, C> {
             Cell::new(None),
             Cell::new(None),
         ];
+        process.stack_bottom = Cell::new(process.memory.as_ptr());
+        process.stack_guard = Cell::new(None);
         process.tasks = MapCell::new(tasks);
         process.process_name = process_name.unwrap_or("");
+        process.verified_credentials = verified_credentials;
 
         process.debug = MapCell::new(ProcessDebug {
             fixed_address_flash: fixed_address_flash,
@@ -1913,6 +3080,12 @@ impl<C: 'static + Chip> Process<'_, C> {
             last_syscall: None,
             dropped_callback_count: 0,
             timeslice_expiration_count: 0,
+            budget_overrun_count: 0,
+            scheduled_count: 0,
+            cpu_time_us: 0,
+            longest_run_us: 0,
+            quota_usage: QuotaUsage::default(),
+            trace_syscalls: false,
         });
 
         let flash_protected_size = process.header.get_protected_size() as usize;
@@ -1944,7 +3117,7 @@ impl<C: 'static + Chip> Process<'_, C> {
                 process.debug_set_max_stack_depth();
             }
             _ => {
-                if config::CONFIG.debug_load_processes {
+                if kernel.debug_config().debug_load_processes() {
                     debug!(
                         "[!] flash={:#010X}-{:#010X} process={:?} - couldn't initialize process",
                         app_flash.as_ptr() as usize,
@@ -1980,7 +3153,8 @@ impl<C: 'static + Chip> Process<'_, C> {
     ///    the syscall state for the process fails to initialize.
     ///
     /// After `restart()` runs the process will either be queued to run its
-    /// `_start` function, or it will be left in `failure_state`.
+    /// `_start` function, be left in `PendingRestart` for its restart policy to
+    /// restart later, or it will be left in `failure_state`.
     fn restart(&self, failure_state: State) {
         // Start with the generic terminate operations. This frees state for
         // this process and removes any pending tasks from the scheduler's
@@ -2001,8 +3175,13 @@ impl<C: 'static + Chip> Process<'_, C> {
                 // leave it in the stopped faulted state by returning
                 // immediately. This has the same effect as using the
                 // `FaultResponse::Stop` policy.
-                if !restart_policy.should_restart(self) {
-                    return;
+                match restart_policy.restart_decision(self) {
+                    RestartDecision::Now => {}
+                    RestartDecision::Later => {
+                        self.state.set(State::PendingRestart);
+                        return;
+                    }
+                    RestartDecision::Never => return,
                 }
             }
 
@@ -2014,6 +3193,13 @@ impl<C: 'static + Chip> Process<'_, C> {
             }
         }
 
+        self.start_again();
+    }
+
+    /// Reset the state of a terminated process and queue its entry point so
+    /// that it starts over. If this fails the process is left in the state it
+    /// is in.
+    fn start_again(&self) {
         // We need a new process identifier for this process since the restarted
         // version is in effect a new process. This is also necessary to
         // invalidate any stored `AppId`s that point to the old version of the
@@ -2030,6 +3216,8 @@ impl<C: 'static + Chip> Process<'_, C> {
             debug.last_syscall = None;
             debug.dropped_callback_count = 0;
             debug.timeslice_expiration_count = 0;
+            debug.budget_overrun_count = 0;
+            debug.quota_usage = QuotaUsage::default();
         });
 
         // We are going to start this process over again, so need the init_fn
@@ -2045,11 +3233,19 @@ impl<C: 'static + Chip> Process<'_, C> {
         // to use saved values.
         self.kernel_memory_break
             .set(self.original_kernel_memory_break);
+        self.kernel_heap.reset();
         self.app_break.set(self.original_app_break);
         self.current_stack_pointer.set(self.original_stack_pointer);
         self.allow_high_water_mark
             .set(self.original_allow_high_water_mark);
 
+        // The stack guard belongs to the previous execution, which might have
+        // put its stack somewhere else.
+        if let Some(guard) = self.stack_guard.take() {
+            let _ = self.remove_mpu_region(guard);
+        }
+        self.stack_bottom.set(self.mem_start());
+
         // Handle any architecture-specific requirements for a process when it
         // first starts (as it would when it is new).
         let new_stack_pointer_res = self.stored_state.map_or(Err(()), |stored_state| unsafe {
@@ -2101,34 +3297,6 @@ impl<C: 'static + Chip> Process<'_, C> {
         self.kernel.increment_work();
     }
 
-    /// Stop and clear a process's state.
-    ///
-    /// This will end the process, but does not reset it such that it could be
-    /// restarted and run again. This function instead frees grants and any
-    /// queued tasks for this process, but leaves the debug information about
-    /// the process and other state intact.
-    fn terminate(&self) {
-        // Remove the tasks that were scheduled for the app from the
-        // amount of work queue.
-        let tasks_len = self.tasks.map_or(0, |tasks| tasks.len());
-        for _ in 0..tasks_len {
-            self.kernel.decrement_work();
-        }
-
-        // And remove those tasks
-        self.tasks.map(|tasks| {
-            tasks.empty();
-        });
-
-        // Clear any grant regions this app has setup with any capsules.
-        unsafe {
-            self.grant_ptrs_reset();
-        }
-
-        // Mark the app as stopped so the scheduler won't try to run it.
-        self.state.set(State::StoppedFaulted);
-    }
-
     /// Get the current stack pointer as a pointer.
     // This is currently safe as the the userspace/kernel boundary
     // implementations of both Risc-V and ARM would fault on context switch if
@@ -2155,6 +3323,17 @@ impl<C: 'static + Chip> Process<'_, C> {
             && buf_end_addr <= self.app_break.get()
     }
 
+    /// Checks if the buffer represented by the passed in base pointer and size
+    /// are within the process's flash, excluding the protected region that
+    /// holds the TBF header.
+    fn in_app_flash(&self, buf_start_addr: *const u8, size: usize) -> bool {
+        let buf_end_addr = buf_start_addr.wrapping_add(size);
+
+        buf_end_addr >= buf_start_addr
+            && buf_start_addr >= self.flash_non_protected_start()
+            && buf_end_addr <= self.flash_end()
+    }
+
     /// Reset all `grant_ptr`s to NULL.
     // This is safe today, as MPU constraints ensure that `mem_end` will always
     // be aligned on at least a word boundary. While this is unlikely to
@@ -2191,6 +3370,434 @@ impl<C: 'static + Chip> Process<'_, C> {
     /// explicitly exits.
     fn is_active(&self) -> bool {
         let current_state = self.state.get();
-        current_state != State::StoppedFaulted && current_state != State::Fault
+        current_state != State::StoppedFaulted
+            && current_state != State::Fault
+            && current_state != State::PendingRestart
+            && current_state != State::Terminated
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        BackoffRestart, FaultResponse, FunctionCall, FunctionCallSource, NoCredentialsCheck,
+        ProcessCredentialsPolicy, RestartBackoffEntry, State, Task,
+    };
+    use crate::callback::CallbackId;
+    use crate::hil::time::{Alarm, AlarmClient};
+    use crate::sched::Kernel;
+    use crate::syscall::Syscall;
+    use crate::tbfheader::{self, TbfHeaderV2Credentials, TbfHeaderV2CredentialsType};
+    use crate::test_util::{self, TestAlarm};
+
+    extern crate std;
+    use std::boxed::Box;
+    use std::vec;
+    use std::vec::Vec;
+
+    const PACKAGE_NAME: u16 = 3;
+    const CREDENTIALS: u16 = 128;
+
+    /// FNV-1a, standing in for a cryptographic hash.
+    fn hash(covered: &[&[u8]]) -> [u8; 32] {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in covered.iter().flat_map(|piece| piece.iter()) {
+            hash = (hash ^ *byte as u64).wrapping_mul(0x100000001b3);
+        }
+        let mut data = [0; 32];
+        data[..8].copy_from_slice(&hash.to_le_bytes());
+        data
+    }
+
+    /// Loads apps whose SHA-256 credentials hold the hash of the covered
+    /// bytes.
+    struct HashPolicy;
+
+    impl ProcessCredentialsPolicy for HashPolicy {
+        fn check_credentials(
+            &self,
+            _process_name: &'static str,
+            credentials: Option<TbfHeaderV2Credentials>,
+            covered: &[&'static [u8]],
+        ) -> bool {
+            credentials.map_or(false, |credentials| {
+                credentials.format() == TbfHeaderV2CredentialsType::Sha256
+                    && credentials.data() == &hash(covered)[..]
+            })
+        }
+    }
+
+    /// A TBF object named `name` with SHA-256 credentials holding `data`.
+    fn tbf_object(name: &[u8], data: &[u8; 32]) -> &'static [u8] {
+        let mut credentials = 1u32.to_le_bytes().to_vec();
+        credentials.extend_from_slice(data);
+        test_util::tbf_object_with(&[(PACKAGE_NAME, name), (CREDENTIALS, &credentials)])
+    }
+
+    /// The hash the credentials of an app named `name` must hold.
+    fn sign(name: &[u8]) -> [u8; 32] {
+        let tbf = tbf_object(name, &[0; 32]);
+        let header_size = u16::from_le_bytes([tbf[2], tbf[3]]) as usize;
+        let header = tbfheader::parse_tbf_header(&tbf[..header_size], 2).unwrap();
+        let covered: Vec<&[u8]> = header
+            .get_credentials_covered(tbf)
+            .unwrap()
+            .iter()
+            .cloned()
+            .collect();
+        hash(&covered)
+    }
+
+    fn upcall(driver_num: usize) -> Task {
+        Task::FunctionCall(FunctionCall {
+            source: FunctionCallSource::Driver(CallbackId {
+                driver_num,
+                subscribe_num: 0,
+            }),
+            argument0: driver_num,
+            argument1: 0,
+            argument2: 0,
+            argument3: 0,
+            pc: 0,
+        })
+    }
+
+    /// Start the process so that it is running, as if it had been switched
+    /// to.
+    #[test]
+    fn waiting_process_is_blocked_by_other_upcalls() {
+        let kernel = test_util::kernel(1);
+        let process = test_util::process(kernel, 0);
+        test_util::run(process);
+
+        let awaited = CallbackId {
+            driver_num: 1,
+            subscribe_num: 0,
+        };
+        assert!(process.enqueue_task(upcall(2)));
+        process.wait_for_upcall(awaited);
+
+        // Another upcall is queued, but the process cannot run until the one
+        // it waits for arrives, so the kernel has nothing to do.
+        assert!(!process.ready());
+        assert!(kernel.processes_blocked());
+        assert!(process.enqueue_task(upcall(3)));
+        assert!(!process.ready());
+        assert!(kernel.processes_blocked());
+        assert!(!process.finish_wait());
+
+        assert!(process.enqueue_task(upcall(1)));
+        assert!(process.ready());
+        assert!(!kernel.processes_blocked());
+        assert!(process.finish_wait());
+        assert!(process.get_state() == State::Running);
+
+        // The other upcalls count as work again once the wait is over.
+        process.set_yielded_state();
+        assert!(process.ready());
+        assert!(process.dequeue_task().is_some());
+        assert!(process.dequeue_task().is_some());
+        assert!(kernel.processes_blocked());
+    }
+
+    #[test]
+    fn terminating_waiting_process_clears_its_work() {
+        let kernel = test_util::kernel(1);
+        let process = test_util::process(kernel, 0);
+        test_util::run(process);
+
+        assert!(process.enqueue_task(upcall(2)));
+        process.wait_for_upcall(CallbackId {
+            driver_num: 1,
+            subscribe_num: 0,
+        });
+        assert!(process.enqueue_task(upcall(1)));
+        process.terminate();
+        assert!(kernel.processes_blocked());
+    }
+
+    #[test]
+    fn credentials_policy_decides_which_apps_load() {
+        let kernel = test_util::kernel(4);
+        let policy = &HashPolicy;
+
+        let signed = tbf_object(b"signed", &sign(b"signed"));
+        let process = test_util::create(kernel, 0, signed, policy).unwrap();
+        assert_eq!(process.unwrap().get_process_name(), "signed");
+        assert!(process.unwrap().has_verified_credentials());
+
+        // Changing the header invalidates the credentials.
+        let renamed = tbf_object(b"signer", &sign(b"signed"));
+        assert!(test_util::create(kernel, 1, renamed, policy)
+            .unwrap()
+            .is_none());
+
+        // So does changing the binary.
+        let patched = tbf_object(b"signed", &sign(b"signed"));
+        let patched = {
+            let mut patched = patched.to_vec();
+            *patched.last_mut().unwrap() ^= 1;
+            std::boxed::Box::leak(patched.into_boxed_slice())
+        };
+        assert!(test_util::create(kernel, 2, patched, policy)
+            .unwrap()
+            .is_none());
+
+        let unsigned = test_util::tbf_object();
+        assert!(test_util::create(kernel, 3, unsigned, policy)
+            .unwrap()
+            .is_none());
+        assert_eq!(kernel.get_process_iter().count(), 1);
+    }
+
+    #[test]
+    fn credentials_are_only_verified_by_checking_policies() {
+        let kernel = test_util::kernel(1);
+        let signed = tbf_object(b"signed", &sign(b"signed"));
+        let process = test_util::create(kernel, 0, signed, &NoCredentialsCheck {})
+            .unwrap()
+            .unwrap();
+        assert!(!process.has_verified_credentials());
+    }
+
+    #[test]
+    fn permissions_restrict_syscalls() {
+        let kernel = test_util::kernel(1);
+        let mut permissions = 1u32.to_le_bytes().to_vec(); // driver 1
+        permissions.extend_from_slice(&0u32.to_le_bytes()); // commands 0 to 63
+        permissions.extend_from_slice(&0b10u64.to_le_bytes()); // command 1
+        let tbf = test_util::tbf_object_with(&[(6, &permissions)]);
+        let process = test_util::create(kernel, 0, tbf, &NoCredentialsCheck {})
+            .unwrap()
+            .unwrap();
+
+        let command = |driver_number, subdriver_number| Syscall::COMMAND {
+            driver_number,
+            subdriver_number,
+            arg0: 0,
+            arg1: 0,
+        };
+        let subscribe = |driver_number| Syscall::SUBSCRIBE {
+            driver_number,
+            subdriver_number: 0,
+            callback_ptr: core::ptr::null_mut(),
+            appdata: 0,
+        };
+        assert!(process.is_syscall_permitted(&command(1, 1)));
+        assert!(!process.is_syscall_permitted(&command(1, 0)));
+        assert!(!process.is_syscall_permitted(&command(2, 1)));
+        assert!(process.is_syscall_permitted(&subscribe(1)));
+        assert!(!process.is_syscall_permitted(&subscribe(2)));
+
+        // Yield, memop and exit do not belong to a driver.
+        assert!(process.is_syscall_permitted(&Syscall::YIELD));
+        assert!(process.is_syscall_permitted(&Syscall::MEMOP {
+            operand: 0,
+            arg0: 0
+        }));
+    }
+
+    #[test]
+    fn apps_for_other_kernel_versions_are_skipped() {
+        let kernel = test_util::kernel(2);
+        let version = |min: (u16, u16), max: (u16, u16)| {
+            let mut value = Vec::new();
+            for part in [min.0, min.1, max.0, max.1].iter() {
+                value.extend_from_slice(&part.to_le_bytes());
+            }
+            test_util::tbf_object_with(&[(8, &value)])
+        };
+        let this = (crate::KERNEL_MAJOR_VERSION, crate::KERNEL_MINOR_VERSION);
+        let newer = (crate::KERNEL_MAJOR_VERSION + 1, 0);
+
+        let tbf = version(newer, newer);
+        assert!(test_util::create(kernel, 0, tbf, &NoCredentialsCheck {})
+            .unwrap()
+            .is_none());
+        let tbf = version((0, 0), this);
+        assert!(test_util::create(kernel, 1, tbf, &NoCredentialsCheck {})
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn duplicate_short_ids_are_rejected() {
+        let kernel = test_util::kernel(4);
+        let app = |name: &[u8], short_id: u32| {
+            test_util::tbf_object_with(&[(PACKAGE_NAME, name), (10, &short_id.to_le_bytes())])
+        };
+
+        let first = test_util::create(kernel, 0, app(b"first", 7), &NoCredentialsCheck {})
+            .unwrap()
+            .unwrap();
+        assert!(
+            test_util::create(kernel, 1, app(b"second", 7), &NoCredentialsCheck {})
+                .unwrap()
+                .is_none()
+        );
+        assert!(kernel.lookup_app_by_short_id(7) == Some(first.appid()));
+
+        // IDs derived from the package name must be unique as well.
+        let named = || test_util::tbf_object_with(&[(PACKAGE_NAME, b"named")]);
+        assert!(
+            test_util::create(kernel, 2, named(), &NoCredentialsCheck {})
+                .unwrap()
+                .is_some()
+        );
+        assert!(
+            test_util::create(kernel, 3, named(), &NoCredentialsCheck {})
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    /// A backoff policy for `num_procs` processes with delays from 100 ms up
+    /// to 350 ms and a stable uptime of one second.
+    fn backoff(
+        kernel: &'static Kernel,
+        num_procs: usize,
+        threshold: usize,
+    ) -> (
+        &'static TestAlarm,
+        &'static BackoffRestart<'static, TestAlarm>,
+    ) {
+        let alarm = test_util::alarm();
+        let entries = Box::leak(vec![RestartBackoffEntry::new(); num_procs].into_boxed_slice());
+        let policy = Box::leak(Box::new(BackoffRestart::new(
+            kernel, alarm, 100, 350, 1_000, threshold, entries,
+        )));
+        (alarm, policy)
+    }
+
+    /// Let the alarm of `policy` fire at the time it was set to.
+    fn fire(alarm: &TestAlarm, policy: &BackoffRestart<'static, TestAlarm>) {
+        assert!(alarm.is_enabled());
+        alarm.now.set(alarm.get_alarm());
+        policy.fired();
+    }
+
+    #[test]
+    fn backoff_compares_times_across_wrap_around() {
+        type Backoff = BackoffRestart<'static, TestAlarm>;
+        assert!(Backoff::before(u32::MAX - 5, 5));
+        assert!(!Backoff::before(5, u32::MAX - 5));
+        assert!(!Backoff::before(5, 5));
+        // The test alarm runs at 1 MHz.
+        assert_eq!(Backoff::ms_to_ticks(3), 3_000);
+        assert_eq!(Backoff::ms_to_ticks(u32::MAX), i32::MAX as u32);
+    }
+
+    #[test]
+    fn backoff_delay_doubles_until_the_app_is_stable() {
+        let kernel = test_util::kernel(1);
+        let (alarm, policy) = backoff(kernel, 1, usize::MAX);
+        let process = test_util::process_with(kernel, 0, FaultResponse::Restart(policy));
+
+        for delay_ms in [100, 200, 350, 350].iter() {
+            process.set_fault_state();
+            assert!(process.get_state() == State::PendingRestart);
+            let restart_at = alarm.now.get() + delay_ms * 1_000;
+            assert_eq!(alarm.get_alarm(), restart_at);
+
+            // Nothing happens before the restart is due.
+            alarm.now.set(restart_at - 1);
+            policy.fired();
+            assert!(process.get_state() == State::PendingRestart);
+
+            fire(alarm, policy);
+            assert!(process.get_state() == State::Unstarted);
+            assert!(!alarm.is_enabled());
+        }
+
+        // Once the app stays up for the stable uptime the delay starts over.
+        alarm.now.set(alarm.now.get() + 1_000_000);
+        process.set_fault_state();
+        assert_eq!(alarm.get_alarm(), alarm.now.get() + 100_000);
+    }
+
+    #[test]
+    fn backoff_arms_the_earliest_restart_across_wrap_around() {
+        let kernel = test_util::kernel(2);
+        let (alarm, policy) = backoff(kernel, 2, usize::MAX);
+        let first = test_util::process_with(kernel, 0, FaultResponse::Restart(policy));
+        let second = test_util::process_with(kernel, 1, FaultResponse::Restart(policy));
+
+        alarm.now.set(u32::MAX - 150_000);
+        first.set_fault_state();
+        alarm.now.set(u32::MAX - 60_000);
+        second.set_fault_state();
+        // The second restart wraps around and is numerically smaller, but
+        // comes later.
+        assert_eq!(alarm.get_alarm(), u32::MAX - 50_000);
+
+        fire(alarm, policy);
+        assert!(first.get_state() == State::Unstarted);
+        assert!(second.get_state() == State::PendingRestart);
+        assert_eq!(alarm.get_alarm(), 39_999);
+
+        fire(alarm, policy);
+        assert!(second.get_state() == State::Unstarted);
+        assert!(!alarm.is_enabled());
+    }
+
+    #[test]
+    fn backoff_stops_restarting_after_the_threshold() {
+        let kernel = test_util::kernel(1);
+        let (alarm, policy) = backoff(kernel, 1, 1);
+        let process = test_util::process_with(kernel, 0, FaultResponse::Restart(policy));
+
+        for _ in 0..2 {
+            process.set_fault_state();
+            fire(alarm, policy);
+            assert!(process.get_state() == State::Unstarted);
+        }
+        process.set_fault_state();
+        assert!(process.get_state() == State::StoppedFaulted);
+        assert!(!alarm.is_enabled());
+    }
+
+    #[test]
+    fn exit_terminates_and_records_the_completion_code() {
+        let kernel = test_util::kernel(1);
+        let process = test_util::process(kernel, 0);
+        test_util::run(process);
+        assert_eq!(process.get_completion_code(), None);
+
+        process.exit(3, false);
+        assert!(process.get_state() == State::Terminated);
+        assert_eq!(process.get_completion_code(), Some(3));
+        assert!(process.dequeue_task().is_none());
+    }
+
+    #[test]
+    fn exit_restart_without_restart_policy_stays_terminated() {
+        let kernel = test_util::kernel(1);
+        let process = test_util::process(kernel, 0);
+        test_util::run(process);
+
+        process.exit(0, true);
+        assert!(process.get_state() == State::Terminated);
+        assert_eq!(process.get_restart_count(), 0);
+    }
+
+    #[test]
+    fn exit_restart_goes_through_the_restart_policy() {
+        let kernel = test_util::kernel(1);
+        let (alarm, policy) = backoff(kernel, 1, usize::MAX);
+        let process = test_util::process_with(kernel, 0, FaultResponse::Restart(policy));
+        test_util::run(process);
+
+        process.exit(1, true);
+        assert!(process.get_state() == State::PendingRestart);
+        assert_eq!(alarm.get_alarm(), 100_000);
+        fire(alarm, policy);
+        assert!(process.get_state() == State::Unstarted);
+        assert_eq!(process.get_restart_count(), 1);
+        assert_eq!(process.get_completion_code(), Some(1));
+
+        // Exiting again right away backs off further.
+        test_util::run(process);
+        process.exit(2, true);
+        assert_eq!(alarm.get_alarm(), 300_000);
     }
 }
@@ -0,0 +1,438 @@
+//! Loading and unloading processes after the kernel has booted.
+//!
+//! `load_processes()` scans flash once at boot and gives each process memory
+//! from the app memory region. A `ProcessLoader` picks up where it left off: it
+//! takes the remainder of the app memory region and can create a process from
+//! a TBF object that was written to app flash after boot, placing it in an
+//! empty slot of the kernel's processes array. It can also terminate and
+//! unload a process, returning the process's memory so it can be reused.
+//!
+//! A removed process's memory is only reused once no other process can access
+//! it any more. Memory the process shared over IPC is revoked from the other
+//! processes when the process is removed; if that fails, the memory is held
+//! back and revoking is tried again the next time a process is installed.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust,ignore
+//! let process_loader = static_init!(
+//!     kernel::procs::ProcessLoader<sam4l::chip::Sam4l>,
+//!     kernel::procs::ProcessLoader::new(
+//!         board_kernel,
+//!         chip,
+//!         app_flash,
+//!         app_memory,
+//!         FAULT_RESPONSE,
+//!         &kernel::procs::NoCredentialsCheck {},
+//!         &process_management_capability,
+//!     )
+//! );
+//! ```
+//!
+//! `app_flash` and `app_memory` must be the same that were passed to
+//! `load_processes()`, and `new()` must be called after `load_processes()` has
+//! run.
+
+use core::cell::Cell;
+use core::convert::TryInto;
+use core::slice;
+
+use crate::callback::AppId;
+use crate::capabilities::ProcessManagementCapability;
+use crate::debug;
+use crate::platform::Chip;
+use crate::process::{FaultResponse, Process, ProcessCredentialsPolicy, ProcessLoadError};
+use crate::returncode::ReturnCode;
+use crate::sched::Kernel;
+use crate::tbfheader;
+
+/// How many separate regions of free app memory the loader keeps track of.
+/// Adjacent free regions are merged, so this only needs to be larger than the
+/// number of gaps between processes.
+const MAX_FREE_REGIONS: usize = 8;
+
+/// A set of regions of app memory, as start address and length.
+#[derive(Default)]
+struct MemoryRegions {
+    regions: [Cell<Option<(usize, usize)>>; MAX_FREE_REGIONS],
+}
+
+impl MemoryRegions {
+    /// Add a region, merging it with any regions it is adjacent to. Returns
+    /// `false` if there is no room to keep track of another region.
+    fn add(&self, mut start: usize, mut len: usize) -> bool {
+        if len == 0 {
+            return true;
+        }
+
+        // Merging can make the region adjacent to another region, so repeat
+        // until nothing else merges.
+        let mut merged = true;
+        while merged {
+            merged = false;
+            for region in self.regions.iter() {
+                if let Some((r_start, r_len)) = region.get() {
+                    if r_start + r_len == start || start + len == r_start {
+                        start = core::cmp::min(start, r_start);
+                        len += r_len;
+                        region.set(None);
+                        merged = true;
+                    }
+                }
+            }
+        }
+
+        match self.regions.iter().find(|region| region.get().is_none()) {
+            Some(region) => {
+                region.set(Some((start, len)));
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Creates processes from TBF objects in flash and removes them again while
+/// the kernel is running.
+pub struct ProcessLoader<C: 'static + Chip> {
+    kernel: &'static Kernel,
+    chip: &'static C,
+    app_flash: &'static [u8],
+    /// Regions of app memory not used by any process.
+    free_regions: MemoryRegions,
+    /// Memory of removed processes that other processes may still be able to
+    /// access, and so must not be reused yet.
+    quarantined_regions: MemoryRegions,
+    fault_response: FaultResponse,
+    credentials_policy: &'static dyn ProcessCredentialsPolicy,
+}
+
+impl<C: 'static + Chip> ProcessLoader<C> {
+    /// Create a loader for processes in `app_flash` that uses whatever part
+    /// of `app_memory` the processes loaded at boot did not use.
+    ///
+    /// This must be called after `load_processes()` with the same flash and
+    /// memory.
+    pub fn new(
+        kernel: &'static Kernel,
+        chip: &'static C,
+        app_flash: &'static [u8],
+        app_memory: &'static mut [u8],
+        fault_response: FaultResponse,
+        credentials_policy: &'static dyn ProcessCredentialsPolicy,
+        _capability: &dyn ProcessManagementCapability,
+    ) -> ProcessLoader<C> {
+        // `load_processes()` allocates memory to processes back-to-back, so
+        // everything after the end of the last process is unused.
+        let memory_start = app_memory.as_ptr() as usize;
+        let memory_end = memory_start + app_memory.len();
+        let used_end = kernel
+            .get_process_iter()
+            .map(|proc| proc.mem_end() as usize)
+            .filter(|&end| end > memory_start && end <= memory_end)
+            .max()
+            .unwrap_or(memory_start);
+
+        let loader = ProcessLoader {
+            kernel,
+            chip,
+            app_flash,
+            free_regions: MemoryRegions::default(),
+            quarantined_regions: MemoryRegions::default(),
+            fault_response,
+            credentials_policy,
+        };
+        loader.free_memory(used_end, memory_end - used_end);
+        loader
+    }
+
+    /// Create a process from the TBF object starting `offset` bytes into the
+    /// app flash region, and start running it.
+    ///
+    /// The process is placed in the first empty slot of the processes array
+    /// and given memory from the first free region that is large enough.
+    /// Returns the `AppId` of the new process.
+    pub fn install(&self, offset: usize) -> Result<AppId, ProcessLoadError> {
+        self.release_quarantined_memory();
+
+        let remaining_flash = self
+            .app_flash
+            .get(offset..)
+            .ok_or(ProcessLoadError::NotEnoughFlash)?;
+        let test_header_slice = remaining_flash
+            .get(0..8)
+            .ok_or(ProcessLoadError::NotEnoughFlash)?;
+
+        let (version, header_length, entry_length) = match tbfheader::parse_tbf_header_lengths(
+            test_header_slice
+                .try_into()
+                .or(Err(ProcessLoadError::InternalError))?,
+        ) {
+            Ok((v, hl, el)) => (v, hl, el),
+            Err(tbfheader::InitialTbfParseError::UnableToParse) => {
+                // There is no TBF object of a version this kernel knows at
+                // `offset`.
+                let version = u16::from_le_bytes([test_header_slice[0], test_header_slice[1]]);
+                return Err(ProcessLoadError::TbfHeaderParseFailure(
+                    tbfheader::TbfParseError::UnsupportedVersion(version),
+                ));
+            }
+            Err(tbfheader::InitialTbfParseError::InvalidHeader(_)) => {
+                // The header length does not fit the TBF object.
+                return Err(ProcessLoadError::TbfHeaderParseFailure(
+                    tbfheader::TbfParseError::NotEnoughFlash,
+                ));
+            }
+        };
+        let entry_flash = remaining_flash
+            .get(0..entry_length as usize)
+            .ok_or(ProcessLoadError::NotEnoughFlash)?;
+
+        // Do not load a second copy of a process that is already
+        // running from this flash.
+        let flash_start = entry_flash.as_ptr() as usize;
+        let flash_end = flash_start + entry_flash.len();
+        if self.kernel.get_process_iter().any(|proc| {
+            (proc.flash_start() as usize) < flash_end && (proc.flash_end() as usize) > flash_start
+        }) {
+            return Err(ProcessLoadError::FlashInUse);
+        }
+
+        let index = self
+            .kernel
+            .free_process_slot()
+            .ok_or(ProcessLoadError::NoFreeProcessSlot)?;
+
+        // Try each free region in turn until one is large enough (and
+        // at a suitable address) for the process.
+        let mut result = Err(ProcessLoadError::NotEnoughMemory);
+        for region in self.free_regions.regions.iter() {
+            let (start, len) = match region.take() {
+                Some(r) => r,
+                None => continue,
+            };
+
+            // The region came out of the `app_memory` slice this loader
+            // was created with and is not in use by any process, so we
+            // can hand it out again.
+            let memory = unsafe { slice::from_raw_parts_mut(start as *mut u8, len) };
+            match unsafe {
+                Process::create(
+                    self.kernel,
+                    self.chip,
+                    entry_flash,
+                    header_length as usize,
+                    version,
+                    memory,
+                    self.fault_response,
+                    self.credentials_policy,
+                    index,
+                )
+            } {
+                Ok((Some(process), unused_memory)) => {
+                    // The process may not start at the beginning of the
+                    // region because of alignment, so keep any memory in
+                    // front of it as well as the unused remainder.
+                    let mem_start = process.mem_start() as usize;
+                    self.free_memory(start, mem_start - start);
+                    self.free_memory(unused_memory.as_ptr() as usize, unused_memory.len());
+
+                    if self.kernel.debug_config().debug_load_processes() {
+                        debug!(
+                            "Installed process[{}] from flash={:#010X}-{:#010X} into sram={:#010X}-{:#010X} = {:?}",
+                            index,
+                            flash_start,
+                            flash_end - 1,
+                            mem_start,
+                            process.mem_end() as usize - 1,
+                            process.get_process_name()
+                        );
+                    }
+
+                    if !self.kernel.insert_process(index, process) {
+                        return Err(ProcessLoadError::InternalError);
+                    }
+                    return Ok(process.appid());
+                }
+                Ok((None, _)) => {
+                    self.free_memory(start, len);
+                    return Err(ProcessLoadError::NoProcessToLoad);
+                }
+                Err(err @ ProcessLoadError::NotEnoughMemory)
+                | Err(err @ ProcessLoadError::MemoryAddressMismatch { .. }) => {
+                    // This region does not work, try the next one.
+                    self.free_memory(start, len);
+                    result = Err(err);
+                }
+                Err(err) => {
+                    self.free_memory(start, len);
+                    return Err(err);
+                }
+            }
+        }
+        result
+    }
+
+    /// Terminate the process identified by `appid`, remove it from the
+    /// processes array, and return its memory to the loader.
+    ///
+    /// The process is taken out of the processes array before it is
+    /// terminated, so schedulers and capsules can no longer reach it. Its
+    /// memory is reused once other processes it shared memory with over IPC
+    /// no longer have access to it.
+    ///
+    /// This must not be called on behalf of the process being removed (for
+    /// example from its own system call) since the kernel is still using the
+    /// process at that point.
+    pub fn remove(&self, appid: AppId) -> Result<(), ReturnCode> {
+        let process = self
+            .kernel
+            .remove_process(appid)
+            .ok_or(ReturnCode::EINVAL)?;
+        process.terminate();
+
+        let mem_start = process.mem_start() as usize;
+        let mem_end = process.mem_end() as usize;
+        if self.kernel.debug_config().debug_load_processes() {
+            debug!(
+                "Removed process[{}] from sram={:#010X}-{:#010X} = {:?}",
+                appid.index,
+                mem_start,
+                mem_end - 1,
+                process.get_process_name()
+            );
+        }
+
+        // The `Process` struct itself lives in this memory, so `process`
+        // must not be used after this point.
+        if self
+            .kernel
+            .revoke_memory_access(mem_start as *const u8, mem_end as *const u8)
+        {
+            self.free_memory(mem_start, mem_end - mem_start);
+        } else {
+            self.quarantine_memory(mem_start, mem_end - mem_start);
+        }
+        Ok(())
+    }
+
+    /// Return a region of app memory to the free regions.
+    fn free_memory(&self, start: usize, len: usize) {
+        if !self.free_regions.add(start, len) {
+            // Every slot is in use. Dropping the region only means this
+            // memory cannot be used for new processes.
+            if self.kernel.debug_config().debug_load_processes() {
+                debug!(
+                    "Too many free app memory regions, dropping sram={:#010X}-{:#010X}",
+                    start,
+                    start + len - 1
+                );
+            }
+        }
+    }
+
+    /// Hold back a region of app memory that other processes may still be
+    /// able to access.
+    fn quarantine_memory(&self, start: usize, len: usize) {
+        if self.kernel.debug_config().debug_load_processes() {
+            debug!(
+                "Not reusing sram={:#010X}-{:#010X} while other processes can access it",
+                start,
+                start + len - 1
+            );
+        }
+        if !self.quarantined_regions.add(start, len) {
+            // Dropping the region means the memory is never reused, which is
+            // safe.
+            if self.kernel.debug_config().debug_load_processes() {
+                debug!(
+                    "Too many quarantined app memory regions, dropping sram={:#010X}-{:#010X}",
+                    start,
+                    start + len - 1
+                );
+            }
+        }
+    }
+
+    /// Move quarantined memory that no process can access any more to the
+    /// free regions.
+    fn release_quarantined_memory(&self) {
+        for region in self.quarantined_regions.regions.iter() {
+            if let Some((start, len)) = region.get() {
+                if self
+                    .kernel
+                    .revoke_memory_access(start as *const u8, (start + len) as *const u8)
+                {
+                    region.set(None);
+                    self.free_memory(start, len);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MemoryRegions, MAX_FREE_REGIONS};
+
+    fn regions(memory: &MemoryRegions) -> [Option<(usize, usize)>; MAX_FREE_REGIONS] {
+        let mut regions = [None; MAX_FREE_REGIONS];
+        for (i, region) in memory.regions.iter().enumerate() {
+            regions[i] = region.get();
+        }
+        regions.sort();
+        regions
+    }
+
+    #[test]
+    fn merges_adjacent_regions() {
+        let memory = MemoryRegions::default();
+        assert!(memory.add(0x1000, 0x100));
+        assert!(memory.add(0x1100, 0x100));
+        assert!(memory.add(0x0f00, 0x100));
+        let mut expected = [None; MAX_FREE_REGIONS];
+        expected[MAX_FREE_REGIONS - 1] = Some((0x0f00, 0x300));
+        assert_eq!(regions(&memory), expected);
+    }
+
+    #[test]
+    fn fills_gap_between_regions() {
+        let memory = MemoryRegions::default();
+        assert!(memory.add(0x1000, 0x100));
+        assert!(memory.add(0x1200, 0x100));
+        assert!(memory.add(0x1400, 0x100));
+        assert_eq!(regions(&memory).iter().filter(|r| r.is_some()).count(), 3);
+
+        assert!(memory.add(0x1100, 0x100));
+        let mut expected = [None; MAX_FREE_REGIONS];
+        expected[MAX_FREE_REGIONS - 2] = Some((0x1000, 0x300));
+        expected[MAX_FREE_REGIONS - 1] = Some((0x1400, 0x100));
+        assert_eq!(regions(&memory), expected);
+
+        assert!(memory.add(0x1300, 0x100));
+        let mut expected = [None; MAX_FREE_REGIONS];
+        expected[MAX_FREE_REGIONS - 1] = Some((0x1000, 0x500));
+        assert_eq!(regions(&memory), expected);
+    }
+
+    #[test]
+    fn ignores_empty_regions() {
+        let memory = MemoryRegions::default();
+        assert!(memory.add(0x1000, 0));
+        assert_eq!(regions(&memory), [None; MAX_FREE_REGIONS]);
+    }
+
+    #[test]
+    fn full_when_regions_do_not_merge() {
+        let memory = MemoryRegions::default();
+        for i in 0..MAX_FREE_REGIONS {
+            assert!(memory.add(0x1000 + i * 0x200, 0x100));
+        }
+        assert!(!memory.add(0x4000, 0x100));
+
+        // A region that merges still fits.
+        assert!(memory.add(0x1100, 0x100));
+        assert!(memory.add(0x4000, 0x100));
+    }
+}
@@ -0,0 +1,419 @@
+//! Per-process quotas on CPU time, system calls and upcalls.
+//!
+//! A board registers a `ProcessQuotaEnforcer` with
+//! `Kernel::set_quota_enforcer()`, and the kernel then charges every process
+//! for the CPU time it uses, the system calls it makes and the upcalls the
+//! kernel delivers to it. When a process goes over its quota the enforcer
+//! tells the kernel what to do with it.
+//!
+//! `ProcessQuotas` is an enforcer that counts usage in fixed windows of time.
+//! The quotas themselves and what happens to a process that exceeds them come
+//! from a `ProcessQuotaPolicy`, so boards can give different processes
+//! different limits. A process can be
+//!
+//! - throttled: stopped until the current window ends, then resumed,
+//! - stopped: stopped until it is started again, for example from the process
+//!   console, or
+//! - faulted: handled according to its `FaultResponse`, like any other fault.
+//!
+//! CPU time is measured with the scheduler timer, so it is only charged for
+//! runs the scheduler gives a timeslice. Under `PrioritySched`,
+//! `CooperativeSched`, or any other scheduler that runs processes without a
+//! timeslice, the CPU time quota is never reached; use the system call quota
+//! to limit busy processes there instead.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust,ignore
+//! let quota_policy = static_init!(
+//!     kernel::procs::UniformQuota,
+//!     kernel::procs::UniformQuota::new(
+//!         kernel::procs::ProcessQuota {
+//!             cpu_us: Some(200_000),
+//!             syscalls: Some(1000),
+//!             upcalls: None,
+//!         },
+//!         kernel::procs::QuotaAction::Throttle,
+//!     )
+//! );
+//! let quota_alarm = static_init!(
+//!     VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+//!     VirtualMuxAlarm::new(mux_alarm)
+//! );
+//! let quotas = static_init!(
+//!     kernel::procs::ProcessQuotas<'static, VirtualMuxAlarm<'static, sam4l::ast::Ast>>,
+//!     kernel::procs::ProcessQuotas::new(board_kernel, quota_alarm, 1_000_000, quota_policy)
+//! );
+//! quota_alarm.set_client(quotas);
+//! board_kernel.set_quota_enforcer(quotas, &process_management_capability);
+//! ```
+
+use crate::hil::time::{self, Frequency};
+use crate::process::ProcessType;
+use crate::sched::Kernel;
+
+/// The resources a process can have a quota on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuotaResource {
+    /// CPU time in microseconds. Only measured for processes the scheduler
+    /// runs with a timeslice.
+    CpuTime,
+    /// System calls made by the process.
+    Syscalls,
+    /// Upcalls (callbacks, IPC notifications and the like) delivered to the
+    /// process. Upcalls are counted when the kernel hands them to the process
+    /// rather than when they are queued, since that is when the kernel can
+    /// safely stop or fault the process.
+    Upcalls,
+}
+
+/// What to do with a process that went over a quota.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuotaAction {
+    /// Stop the process until its quota is refreshed.
+    Throttle,
+    /// Stop the process until it is explicitly resumed.
+    Stop,
+    /// Fault the process.
+    Fault,
+}
+
+/// How much of each resource a process may use in one window. `None` means
+/// there is no limit.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProcessQuota {
+    /// CPU time in microseconds. Has no effect under schedulers that do not
+    /// use timeslices.
+    pub cpu_us: Option<u32>,
+    pub syscalls: Option<u32>,
+    pub upcalls: Option<u32>,
+}
+
+impl ProcessQuota {
+    /// A quota that does not limit anything.
+    pub const UNLIMITED: ProcessQuota = ProcessQuota {
+        cpu_us: None,
+        syscalls: None,
+        upcalls: None,
+    };
+
+    fn limit(&self, resource: QuotaResource) -> Option<u32> {
+        match resource {
+            QuotaResource::CpuTime => self.cpu_us,
+            QuotaResource::Syscalls => self.syscalls,
+            QuotaResource::Upcalls => self.upcalls,
+        }
+    }
+}
+
+/// What a process has used in the current quota window. Kept by the process,
+/// next to its other debugging counters.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct QuotaUsage {
+    pub cpu_us: u32,
+    pub syscalls: u32,
+    pub upcalls: u32,
+    /// Whether the process was throttled and should be resumed when the
+    /// window ends.
+    pub throttled: bool,
+}
+
+impl QuotaUsage {
+    fn used(&mut self, resource: QuotaResource) -> &mut u32 {
+        match resource {
+            QuotaResource::CpuTime => &mut self.cpu_us,
+            QuotaResource::Syscalls => &mut self.syscalls,
+            QuotaResource::Upcalls => &mut self.upcalls,
+        }
+    }
+}
+
+/// Charges processes for the resources they use. The kernel calls `charge()`
+/// when it is safe to stop or fault the process, and applies the action it
+/// returns.
+pub trait ProcessQuotaEnforcer {
+    /// Charge `amount` of `resource` to `process`. Returns the action to take
+    /// if this put the process over its quota.
+    fn charge(
+        &self,
+        process: &dyn ProcessType,
+        resource: QuotaResource,
+        amount: u32,
+    ) -> Option<QuotaAction>;
+}
+
+/// Decides the quotas of processes and what happens when they exceed them.
+pub trait ProcessQuotaPolicy {
+    /// The quota of `process` for each window.
+    fn quota(&self, process: &dyn ProcessType) -> ProcessQuota;
+
+    /// What to do with `process`, which just went over its quota of
+    /// `resource`.
+    fn action(&self, process: &dyn ProcessType, resource: QuotaResource) -> QuotaAction;
+}
+
+/// Implementation of `ProcessQuotaPolicy` that gives every process the same
+/// quota and handles every overrun the same way.
+pub struct UniformQuota {
+    quota: ProcessQuota,
+    action: QuotaAction,
+}
+
+impl UniformQuota {
+    pub const fn new(quota: ProcessQuota, action: QuotaAction) -> UniformQuota {
+        UniformQuota { quota, action }
+    }
+}
+
+impl ProcessQuotaPolicy for UniformQuota {
+    fn quota(&self, _process: &dyn ProcessType) -> ProcessQuota {
+        self.quota
+    }
+
+    fn action(&self, _process: &dyn ProcessType, _resource: QuotaResource) -> QuotaAction {
+        self.action
+    }
+}
+
+/// Implementation of `ProcessQuotaEnforcer` that counts usage in windows of
+/// `window_us` microseconds. A window starts with the first charge after the
+/// previous one ended, so the alarm does not wake the chip while processes
+/// are idle. When a window ends all usage is cleared and throttled processes
+/// are resumed.
+pub struct ProcessQuotas<'a, A: time::Alarm<'a>> {
+    kernel: &'static Kernel,
+    alarm: &'a A,
+    window_us: u32,
+    policy: &'a dyn ProcessQuotaPolicy,
+}
+
+impl<'a, A: time::Alarm<'a>> ProcessQuotas<'a, A> {
+    pub fn new(
+        kernel: &'static Kernel,
+        alarm: &'a A,
+        window_us: u32,
+        policy: &'a dyn ProcessQuotaPolicy,
+    ) -> ProcessQuotas<'a, A> {
+        ProcessQuotas {
+            kernel,
+            alarm,
+            window_us,
+            policy,
+        }
+    }
+
+    fn start_window(&self) {
+        let ticks = self.window_us as u64 * A::Frequency::frequency() as u64 / 1_000_000;
+        self.alarm
+            .set_alarm(self.alarm.now().wrapping_add(ticks as u32));
+    }
+}
+
+impl<'a, A: time::Alarm<'a>> ProcessQuotaEnforcer for ProcessQuotas<'a, A> {
+    fn charge(
+        &self,
+        process: &dyn ProcessType,
+        resource: QuotaResource,
+        amount: u32,
+    ) -> Option<QuotaAction> {
+        if !self.alarm.is_enabled() {
+            self.start_window();
+        }
+
+        let limit = self.policy.quota(process).limit(resource)?;
+        let mut usage = process.debug_quota_usage();
+        let used = usage.used(resource);
+        let was_over = *used > limit;
+        *used = used.saturating_add(amount);
+        // Only act when the process crosses its quota, so that a process that
+        // was resumed by hand keeps running for the rest of the window.
+        let action = if *used > limit && !was_over {
+            let action = self.policy.action(process, resource);
+            if action == QuotaAction::Throttle {
+                usage.throttled = true;
+            }
+            Some(action)
+        } else {
+            None
+        };
+        process.debug_set_quota_usage(usage);
+        action
+    }
+}
+
+impl<'a, A: time::Alarm<'a>> time::AlarmClient for ProcessQuotas<'a, A> {
+    fn fired(&self) {
+        self.kernel.process_each(|process| {
+            if process.debug_quota_usage().throttled {
+                process.resume();
+            }
+            process.debug_set_quota_usage(QuotaUsage::default());
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::{
+        ProcessQuota, ProcessQuotaPolicy, ProcessQuotas, QuotaAction, QuotaResource, QuotaUsage,
+    };
+    use crate::hil::time::{Alarm, AlarmClient};
+    use crate::process::{ProcessType, State};
+    use crate::sched::Kernel;
+    use crate::test_util::{self, TestAlarm, TestCapability};
+    use std::boxed::Box;
+
+    /// Allows two system calls per window. Processes 0, 1 and 2 are
+    /// throttled, stopped and faulted when they make more.
+    struct TestPolicy;
+
+    impl ProcessQuotaPolicy for TestPolicy {
+        fn quota(&self, _process: &dyn ProcessType) -> ProcessQuota {
+            ProcessQuota {
+                syscalls: Some(2),
+                ..ProcessQuota::UNLIMITED
+            }
+        }
+
+        fn action(&self, process: &dyn ProcessType, _resource: QuotaResource) -> QuotaAction {
+            [QuotaAction::Throttle, QuotaAction::Stop, QuotaAction::Fault][process.appid().index]
+        }
+    }
+
+    /// A kernel with `num_procs` running processes whose quotas are enforced
+    /// in windows of one second.
+    fn quotas(
+        num_procs: usize,
+    ) -> (
+        &'static Kernel,
+        &'static TestAlarm,
+        &'static ProcessQuotas<'static, TestAlarm>,
+    ) {
+        let kernel = test_util::kernel(num_procs);
+        let alarm = test_util::alarm();
+        let quotas: &'static ProcessQuotas<'static, TestAlarm> = Box::leak(Box::new(
+            ProcessQuotas::new(kernel, alarm, 1_000_000, &TestPolicy),
+        ));
+        kernel.set_quota_enforcer(quotas, &TestCapability);
+        for index in 0..num_procs {
+            test_util::run(test_util::process(kernel, index));
+        }
+        (kernel, alarm, quotas)
+    }
+
+    /// Charge `process` for `count` system calls.
+    fn syscalls(kernel: &Kernel, process: &dyn ProcessType, count: usize) {
+        for _ in 0..count {
+            kernel.charge_quota(process, QuotaResource::Syscalls, 1);
+        }
+    }
+
+    /// End the current window the way the alarm would.
+    fn end_window(alarm: &TestAlarm, quotas: &ProcessQuotas<'static, TestAlarm>) {
+        assert!(alarm.is_enabled());
+        alarm.enabled.set(false);
+        alarm.now.set(alarm.get_alarm());
+        quotas.fired();
+    }
+
+    fn usage(kernel: &Kernel, index: usize) -> QuotaUsage {
+        kernel
+            .get_process_iter()
+            .nth(index)
+            .unwrap()
+            .debug_quota_usage()
+    }
+
+    #[test]
+    fn window_starts_with_the_first_charge_and_clears_usage() {
+        let (kernel, alarm, quotas) = quotas(1);
+        let process = kernel.get_process_iter().next().unwrap();
+        alarm.now.set(5);
+        assert!(!alarm.is_enabled());
+
+        syscalls(kernel, process, 2);
+        assert_eq!(alarm.get_alarm(), 1_000_005);
+        assert_eq!(usage(kernel, 0).syscalls, 2);
+
+        // Usage is cleared, and the next window waits for the next charge.
+        end_window(alarm, quotas);
+        assert_eq!(usage(kernel, 0).syscalls, 0);
+        assert!(!alarm.is_enabled());
+        assert!(process.get_state() == State::Running);
+    }
+
+    #[test]
+    fn throttle_and_stop_both_stop_the_process() {
+        let (kernel, _, _) = quotas(2);
+        for (index, process) in kernel.get_process_iter().enumerate() {
+            syscalls(kernel, process, 2);
+            assert!(process.get_state() == State::Running);
+            syscalls(kernel, process, 1);
+            assert!(process.get_state() == State::StoppedRunning);
+            assert_eq!(usage(kernel, index).throttled, index == 0);
+        }
+    }
+
+    #[test]
+    fn only_throttled_processes_resume_when_the_window_ends() {
+        let (kernel, alarm, quotas) = quotas(2);
+        for process in kernel.get_process_iter() {
+            syscalls(kernel, process, 3);
+        }
+
+        end_window(alarm, quotas);
+        let mut processes = kernel.get_process_iter();
+        assert!(processes.next().unwrap().get_state() == State::Running);
+        assert!(processes.next().unwrap().get_state() == State::StoppedRunning);
+        assert!(!usage(kernel, 0).throttled);
+    }
+
+    #[test]
+    fn resumed_process_keeps_running_for_the_rest_of_the_window() {
+        let (kernel, _, _) = quotas(2);
+        let process = kernel.get_process_iter().nth(1).unwrap();
+        syscalls(kernel, process, 3);
+        process.resume();
+
+        syscalls(kernel, process, 5);
+        assert!(process.get_state() == State::Running);
+    }
+
+    #[test]
+    fn fault_action_faults_the_process() {
+        let (kernel, _, _) = quotas(3);
+        let process = kernel.get_process_iter().nth(2).unwrap();
+        syscalls(kernel, process, 3);
+        assert!(process.get_state() == State::StoppedFaulted);
+    }
+
+    #[test]
+    fn exited_processes_are_not_charged() {
+        let (kernel, _, _) = quotas(2);
+        let process = kernel.get_process_iter().nth(1).unwrap();
+        process.exit(0, false);
+
+        syscalls(kernel, process, 3);
+        assert!(process.get_state() == State::Terminated);
+        assert_eq!(usage(kernel, 1).syscalls, 0);
+    }
+
+    #[test]
+    fn restarted_process_starts_with_fresh_usage() {
+        let (kernel, alarm, quotas) = quotas(1);
+        let process = kernel.get_process_iter().next().unwrap();
+        syscalls(kernel, process, 3);
+        assert!(usage(kernel, 0).throttled);
+
+        // The new process is not throttled, so the end of the window leaves
+        // it alone.
+        process.restart_now();
+        assert_eq!(usage(kernel, 0).syscalls, 0);
+        assert!(!usage(kernel, 0).throttled);
+        end_window(alarm, quotas);
+        assert!(process.get_state() == State::Unstarted);
+    }
+}
@@ -4,6 +4,7 @@
 //! different scheduler implementations.
 
 pub(crate) mod cooperative;
+pub(crate) mod edf;
 pub(crate) mod mlfq;
 pub(crate) mod priority;
 pub(crate) mod round_robin;
@@ -13,7 +14,7 @@ use core::ptr::NonNull;
 
 use crate::callback::{AppId, Callback, CallbackId};
 use crate::capabilities;
-use crate::common::cells::NumericCellExt;
+use crate::common::cells::{NumericCellExt, OptionalCell};
 use crate::common::dynamic_deferred_call::DynamicDeferredCall;
 use crate::config;
 use crate::debug;
@@ -25,14 +26,19 @@ use crate::platform::scheduler_timer::SchedulerTimer;
 use crate::platform::watchdog::WatchDog;
 use crate::platform::{Chip, Platform};
 use crate::process::{self, Task};
+use crate::quota::{ProcessQuotaEnforcer, QuotaAction, QuotaResource};
 use crate::returncode::ReturnCode;
 use crate::syscall::{ContextSwitchReason, Syscall};
+use crate::syscall_trace::SyscallTracer;
 
 /// Threshold in microseconds to consider a process's timeslice to be exhausted.
 /// That is, Tock will skip re-scheduling a process if its remaining timeslice
 /// is less than this threshold.
 pub(crate) const MIN_QUANTA_THRESHOLD_US: u32 = 500;
 
+/// The most fault handlers a board can register with the kernel.
+pub const MAX_FAULT_HANDLERS: usize = 4;
+
 /// Trait which any scheduler must implement.
 pub trait Scheduler<C: Chip> {
     /// Decide which process to run next.
@@ -124,8 +130,9 @@ pub struct Kernel {
     /// outstanding callbacks and processes in the Running state.
     work: Cell<usize>,
 
-    /// This holds a pointer to the static array of Process pointers.
-    processes: &'static [Option<&'static dyn process::ProcessType>],
+    /// This holds a pointer to the static array of Process pointers. Processes
+    /// are only added to and removed from the array through the kernel.
+    processes: &'static [process::ProcessSlot],
 
     /// A counter which keeps track of how many process identifiers have been
     /// created. This is used to create new unique identifiers for processes.
@@ -141,6 +148,23 @@ pub struct Kernel {
     /// created and the data structures for grants have already been
     /// established.
     grants_finalized: Cell<bool>,
+
+    /// Handlers the board registered to be told about process faults, in the
+    /// order they were registered.
+    fault_handlers: [OptionalCell<&'static dyn process::ProcessFaultHandler>; MAX_FAULT_HANDLERS],
+
+    /// Optional tracer the board registered to record system calls.
+    syscall_tracer: OptionalCell<&'static dyn SyscallTracer>,
+
+    /// Optional enforcer of per-process resource quotas.
+    quota_enforcer: OptionalCell<&'static dyn ProcessQuotaEnforcer>,
+
+    /// Debugging switches that can be changed at runtime.
+    debug_config: config::DebugConfig,
+
+    /// The start and end address of the kernel stack, if the board passed
+    /// it in.
+    kernel_stack: OptionalCell<(usize, usize)>,
 }
 
 /// Enum used to inform scheduler why a process stopped executing (aka why
@@ -169,14 +193,172 @@ pub enum StoppedExecutingReason {
 }
 
 impl Kernel {
-    pub fn new(processes: &'static [Option<&'static dyn process::ProcessType>]) -> Kernel {
+    /// Create the kernel. The kernel takes over the processes array, and
+    /// processes are placed in it by `load_processes()` and `ProcessLoader`.
+    pub fn new(processes: &'static mut [Option<&'static dyn process::ProcessType>]) -> Kernel {
         Kernel {
             work: Cell::new(0),
-            processes,
+            processes: process::ProcessSlot::from_cells(
+                Cell::from_mut(processes).as_slice_of_cells(),
+            ),
             process_identifier_max: Cell::new(0),
             grant_counter: Cell::new(0),
             grants_finalized: Cell::new(false),
+            fault_handlers: [
+                OptionalCell::empty(),
+                OptionalCell::empty(),
+                OptionalCell::empty(),
+                OptionalCell::empty(),
+            ],
+            syscall_tracer: OptionalCell::empty(),
+            quota_enforcer: OptionalCell::empty(),
+            debug_config: config::DebugConfig::new(),
+            kernel_stack: OptionalCell::empty(),
+        }
+    }
+
+    /// Register a handler that is called whenever a process faults, before
+    /// the kernel handles the fault according to the process's
+    /// `FaultResponse`. Boards can register up to `MAX_FAULT_HANDLERS`
+    /// handlers, which are called in the order they were registered.
+    ///
+    /// Returns `false` if no more handlers can be registered.
+    pub fn add_fault_handler(
+        &self,
+        fault_handler: &'static dyn process::ProcessFaultHandler,
+        _capability: &dyn capabilities::ProcessManagementCapability,
+    ) -> bool {
+        match self.fault_handlers.iter().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                slot.set(fault_handler);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether the board registered any fault handlers.
+    pub(crate) fn has_fault_handlers(&self) -> bool {
+        self.fault_handlers[0].is_some()
+    }
+
+    /// Call `closure` with each fault handler the board registered.
+    pub(crate) fn fault_handler_each<F>(&self, closure: F)
+    where
+        F: Fn(&'static dyn process::ProcessFaultHandler),
+    {
+        for slot in self.fault_handlers.iter() {
+            slot.map(|fault_handler| closure(*fault_handler));
+        }
+    }
+
+    /// Register a tracer that records every system call processes make.
+    pub fn set_syscall_tracer(
+        &self,
+        tracer: &'static dyn SyscallTracer,
+        _capability: &dyn capabilities::ProcessManagementCapability,
+    ) {
+        self.syscall_tracer.set(tracer);
+    }
+
+    /// Tell the kernel where its stack is, so that `KernelInfo` can report how
+    /// deep it has grown. The chip must have painted the stack with
+    /// `introspection::paint_stack()` at startup.
+    pub fn set_kernel_stack(
+        &self,
+        stack: &'static [u8],
+        _capability: &dyn capabilities::ProcessManagementCapability,
+    ) {
+        let start = stack.as_ptr() as usize;
+        self.kernel_stack.set((start, start + stack.len()));
+    }
+
+    /// The start and end address of the kernel stack, if the board set it.
+    pub(crate) fn kernel_stack(&self) -> Option<(usize, usize)> {
+        self.kernel_stack.map(|stack| *stack)
+    }
+
+    /// Get the system call tracer registered by the board, for example to
+    /// drain the records it has collected.
+    pub fn get_syscall_tracer(
+        &self,
+        _capability: &dyn capabilities::ProcessManagementCapability,
+    ) -> Option<&'static dyn SyscallTracer> {
+        self.syscall_tracer.map(|tracer| *tracer)
+    }
+
+    /// Register an enforcer that limits the CPU time, system calls and upcalls
+    /// of processes.
+    pub fn set_quota_enforcer(
+        &self,
+        enforcer: &'static dyn ProcessQuotaEnforcer,
+        _capability: &dyn capabilities::ProcessManagementCapability,
+    ) {
+        self.quota_enforcer.set(enforcer);
+    }
+
+    /// Charge `amount` of `resource` to `process`, and stop or fault the
+    /// process if that puts it over its quota. Processes that are no longer
+    /// active, for example because they exited, are not charged, so that the
+    /// quota action cannot bring them back.
+    pub(crate) fn charge_quota(
+        &self,
+        process: &dyn process::ProcessType,
+        resource: QuotaResource,
+        amount: u32,
+    ) {
+        match process.get_state() {
+            process::State::Fault
+            | process::State::StoppedFaulted
+            | process::State::PendingRestart
+            | process::State::Terminated => return,
+            _ => {}
         }
+        self.quota_enforcer.map(
+            |enforcer| match enforcer.charge(process, resource, amount) {
+                Some(QuotaAction::Throttle) | Some(QuotaAction::Stop) => process.stop(),
+                Some(QuotaAction::Fault) => process.set_fault_state(),
+                None => {}
+            },
+        );
+    }
+
+    /// Turn a kernel debugging switch on or off.
+    pub fn set_debug_switch(
+        &self,
+        switch: config::DebugSwitch,
+        enabled: bool,
+        _capability: &dyn capabilities::ProcessManagementCapability,
+    ) {
+        self.debug_config.set(switch, enabled);
+    }
+
+    /// Whether a kernel debugging switch is on.
+    pub fn get_debug_switch(&self, switch: config::DebugSwitch) -> bool {
+        self.debug_config.get(switch)
+    }
+
+    /// The current state of the debugging switches.
+    pub(crate) fn debug_config(&self) -> &config::DebugConfig {
+        &self.debug_config
+    }
+
+    /// Whether the system calls and callbacks of `process` should be printed
+    /// to the debug output.
+    pub(crate) fn trace_syscalls_of(&self, process: &dyn process::ProcessType) -> bool {
+        self.debug_config.trace_syscalls() || process.debug_trace_syscalls()
+    }
+
+    /// Pass a system call and its result to the registered tracer, if any.
+    fn trace_syscall(
+        &self,
+        process: &dyn process::ProcessType,
+        syscall: &Syscall,
+        return_value: isize,
+        dispatched: bool,
+    ) {
+        self.syscall_tracer
+            .map(|tracer| tracer.record(process.appid(), syscall, return_value, dispatched));
     }
 
     /// Something was scheduled for a process, so there is more work to do.
@@ -221,7 +403,7 @@ impl Kernel {
 
     /// Helper function for determining if we should service processes or go to
     /// sleep.
-    fn processes_blocked(&self) -> bool {
+    pub(crate) fn processes_blocked(&self) -> bool {
         self.work.get() == 0
     }
 
@@ -252,7 +434,7 @@ impl Kernel {
             .map_or(None, |process_entry| {
                 // Check if there is any process state here, or if the entry is
                 // `None`.
-                process_entry.map_or(None, |process| {
+                process_entry.get().map_or(None, |process| {
                     // Check that the process stored here matches the identifier
                     // in the `appid`.
                     if process.appid() == appid {
@@ -272,9 +454,9 @@ impl Kernel {
         F: Fn(&dyn process::ProcessType),
     {
         for process in self.processes.iter() {
-            match process {
+            match process.get() {
                 Some(p) => {
-                    closure(*p);
+                    closure(p);
                 }
                 None => {}
             }
@@ -285,13 +467,11 @@ impl Kernel {
     pub(crate) fn get_process_iter(
         &self,
     ) -> core::iter::FilterMap<
-        core::slice::Iter<Option<&dyn process::ProcessType>>,
-        fn(&Option<&'static dyn process::ProcessType>) -> Option<&'static dyn process::ProcessType>,
+        core::slice::Iter<process::ProcessSlot>,
+        fn(&process::ProcessSlot) -> Option<&'static dyn process::ProcessType>,
     > {
-        fn keep_some(
-            &x: &Option<&'static dyn process::ProcessType>,
-        ) -> Option<&'static dyn process::ProcessType> {
-            x
+        fn keep_some(x: &process::ProcessSlot) -> Option<&'static dyn process::ProcessType> {
+            x.get()
         }
         self.processes.iter().filter_map(keep_some)
     }
@@ -310,9 +490,9 @@ impl Kernel {
         F: Fn(&dyn process::ProcessType),
     {
         for process in self.processes.iter() {
-            match process {
+            match process.get() {
                 Some(p) => {
-                    closure(*p);
+                    closure(p);
                 }
                 None => {}
             }
@@ -328,9 +508,9 @@ impl Kernel {
         F: Fn(&dyn process::ProcessType) -> ReturnCode,
     {
         for process in self.processes.iter() {
-            match process {
+            match process.get() {
                 Some(p) => {
-                    let ret = closure(*p);
+                    let ret = closure(p);
                     if ret != ReturnCode::FAIL {
                         return ret;
                     }
@@ -346,8 +526,8 @@ impl Kernel {
     /// as from userspace) and needs to be expanded to a full `AppId` for use
     /// with other APIs.
     pub(crate) fn lookup_app_by_identifier(&self, identifier: usize) -> Option<AppId> {
-        self.processes.iter().find_map(|&p| {
-            p.map_or(None, |p2| {
+        self.processes.iter().find_map(|p| {
+            p.get().map_or(None, |p2| {
                 if p2.appid().id() == identifier {
                     Some(p2.appid())
                 } else {
@@ -357,6 +537,22 @@ impl Kernel {
         })
     }
 
+    /// Retrieve the `AppId` of the running app with the given persistent short
+    /// ID. This lets capsules find an app again after it restarted or the
+    /// board rebooted. Processes are not loaded if their short ID is in use, so
+    /// there is at most one such app.
+    pub fn lookup_app_by_short_id(&self, short_id: u32) -> Option<AppId> {
+        self.processes.iter().find_map(|p| {
+            p.get().and_then(|process| {
+                if process.short_id() == short_id {
+                    Some(process.appid())
+                } else {
+                    None
+                }
+            })
+        })
+    }
+
     /// Checks if the provided `AppId` is still valid given the processes stored
     /// in the processes array. Returns `true` if the AppId still refers to
     /// a valid process, and `false` if not.
@@ -365,10 +561,73 @@ impl Kernel {
     /// verify that the referenced app is still at the correct index.
     pub(crate) fn appid_is_valid(&self, appid: &AppId) -> bool {
         self.processes.get(appid.index).map_or(false, |p| {
-            p.map_or(false, |process| process.appid().id() == appid.id())
+            p.get()
+                .map_or(false, |process| process.appid().id() == appid.id())
         })
     }
 
+    /// Get the slots of the processes array. Schedulers keep references to
+    /// the slots so that they see processes being added and removed.
+    pub fn process_slots(
+        &self,
+        _capability: &dyn capabilities::ProcessManagementCapability,
+    ) -> &'static [process::ProcessSlot] {
+        self.processes
+    }
+
+    /// How many processes the processes array can hold.
+    pub(crate) fn number_of_process_slots(&self) -> usize {
+        self.processes.len()
+    }
+
+    /// Find the index of an empty slot in the processes array.
+    pub(crate) fn free_process_slot(&self) -> Option<usize> {
+        self.processes.iter().position(|slot| slot.get().is_none())
+    }
+
+    /// Place `process` in the empty slot at `index` of the processes array.
+    /// Returns `false` if the slot does not exist or is already in use.
+    pub(crate) fn insert_process(
+        &self,
+        index: usize,
+        process: &'static dyn process::ProcessType,
+    ) -> bool {
+        match self.processes.get(index) {
+            Some(slot) if slot.get().is_none() => {
+                slot.set(Some(process));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Take the process identified by `appid` out of the processes array, so
+    /// that schedulers, grants, and `AppId` lookups no longer find it. The
+    /// process must already be terminated.
+    pub(crate) fn remove_process(&self, appid: AppId) -> Option<&'static dyn process::ProcessType> {
+        self.processes.get(appid.index).and_then(|slot| {
+            slot.get()
+                .filter(|process| process.appid() == appid)
+                .map(|process| {
+                    slot.set(None);
+                    process
+                })
+        })
+    }
+
+    /// Take away every process's access to the memory from `start` up to
+    /// `end` that was given to it with `ProcessType::add_mpu_region()`.
+    /// Returns `false` if some process may still be able to access it.
+    pub(crate) fn revoke_memory_access(&self, start: *const u8, end: *const u8) -> bool {
+        let revoked = Cell::new(true);
+        self.process_each(|process| {
+            if process.remove_mpu_regions_in(start, end).is_err() {
+                revoked.set(false);
+            }
+        });
+        revoked.get()
+    }
+
     /// Create a new grant. This is used in board initialization to setup grants
     /// that capsules use to interact with processes.
     ///
@@ -444,7 +703,7 @@ impl Kernel {
     /// apps.
     pub fn hardfault_all_apps<C: capabilities::ProcessManagementCapability>(&self, _c: &C) {
         for p in self.processes.iter() {
-            p.map(|process| {
+            p.get().map(|process| {
                 process.set_fault_state();
             });
         }
@@ -489,6 +748,18 @@ impl Kernel {
                                         ipc,
                                         timeslice_us,
                                     );
+                                    process.debug_scheduled(time_executed);
+                                    // A process that restarted itself is a new
+                                    // process, which has not used this time.
+                                    if process.appid() == appid {
+                                        time_executed.map(|time_executed| {
+                                            self.charge_quota(
+                                                process,
+                                                QuotaResource::CpuTime,
+                                                time_executed,
+                                            )
+                                        });
+                                    }
                                     scheduler.result(reason, time_executed);
                                 });
                             }
@@ -580,6 +851,14 @@ impl Kernel {
         // inform the scheduler.
         let mut return_reason = StoppedExecutingReason::NoWorkLeft;
 
+        // Whether the process made a system call that has not been charged to
+        // its quota yet. System calls are charged once they have been handled,
+        // so that a process stopped for going over its quota is not left
+        // without a return value. This holds the `AppId` the process had when
+        // it made the call, since a process that restarted itself with the
+        // exit syscall is a new process that did not make it.
+        let mut syscall_to_charge: Option<AppId> = None;
+
         // Since the timeslice counts both the process's execution time and the
         // time spent in the kernel on behalf of the process (setting it up and
         // handling its syscalls), we intend to keep running the process until
@@ -587,6 +866,10 @@ impl Kernel {
         // no longer wants to execute this process or if it exceeds its
         // timeslice.
         loop {
+            if syscall_to_charge.take() == Some(process.appid()) {
+                self.charge_quota(process, QuotaResource::Syscalls, 1);
+            }
+
             if scheduler_timer.has_expired()
                 || scheduler_timer.get_remaining_us() <= MIN_QUANTA_THRESHOLD_US
             {
@@ -621,10 +904,11 @@ impl Kernel {
                     match context_switch_reason {
                         Some(ContextSwitchReason::Fault) => {
                             // Let process deal with it as appropriate.
-                            process.set_fault_state();
+                            process.set_hardware_fault_state();
                         }
                         Some(ContextSwitchReason::SyscallFired { syscall }) => {
                             process.debug_syscall_called(syscall);
+                            syscall_to_charge = Some(process.appid());
 
                             // Enforce platform-specific syscall filtering here.
                             //
@@ -642,16 +926,39 @@ impl Kernel {
                             // decide how to handle the error.
                             if syscall != Syscall::YIELD {
                                 if let Err(response) = platform.filter_syscall(process, &syscall) {
+                                    self.trace_syscall(process, &syscall, response.into(), false);
                                     process.set_syscall_return_value(response.into());
                                     continue;
                                 }
                             }
 
+                            // Enforce the permissions the process declared in
+                            // its TBF header. Like platform filtering, a denied
+                            // syscall returns an error to the process without
+                            // dispatching to the driver.
+                            if !process.is_syscall_permitted(&syscall) {
+                                if self.trace_syscalls_of(process) {
+                                    debug!(
+                                        "[{:?}] {:?} denied by TBF permissions",
+                                        process.appid(),
+                                        syscall
+                                    );
+                                }
+                                self.trace_syscall(
+                                    process,
+                                    &syscall,
+                                    ReturnCode::ENOSUPPORT.into(),
+                                    false,
+                                );
+                                process.set_syscall_return_value(ReturnCode::ENOSUPPORT.into());
+                                continue;
+                            }
+
                             // Handle each of the syscalls.
                             match syscall {
                                 Syscall::MEMOP { operand, arg0 } => {
                                     let res = memop::memop(process, operand, arg0);
-                                    if config::CONFIG.trace_syscalls {
+                                    if self.trace_syscalls_of(process) {
                                         debug!(
                                             "[{:?}] memop({}, {:#x}) = {:#x} = {:?}",
                                             process.appid(),
@@ -661,12 +968,38 @@ impl Kernel {
                                             res
                                         );
                                     }
+                                    self.trace_syscall(process, &syscall, res.into(), true);
                                     process.set_syscall_return_value(res.into());
                                 }
+                                Syscall::EXIT {
+                                    which,
+                                    completion_code,
+                                } => {
+                                    if self.trace_syscalls_of(process) {
+                                        debug!(
+                                            "[{:?}] exit({}, {:#x})",
+                                            process.appid(),
+                                            which,
+                                            completion_code
+                                        );
+                                    }
+                                    match which {
+                                        0 | 1 => {
+                                            self.trace_syscall(process, &syscall, 0, true);
+                                            process.exit(completion_code, which == 1);
+                                        }
+                                        _ => {
+                                            let res = ReturnCode::EINVAL;
+                                            self.trace_syscall(process, &syscall, res.into(), true);
+                                            process.set_syscall_return_value(res.into());
+                                        }
+                                    }
+                                }
                                 Syscall::YIELD => {
-                                    if config::CONFIG.trace_syscalls {
+                                    if self.trace_syscalls_of(process) {
                                         debug!("[{:?}] yield", process.appid());
                                     }
+                                    self.trace_syscall(process, &syscall, 0, true);
                                     process.set_yielded_state();
 
                                     // There might be already enqueued callbacks
@@ -713,7 +1046,7 @@ impl Kernel {
                                                 None => ReturnCode::ENODEVICE,
                                             },
                                         );
-                                    if config::CONFIG.trace_syscalls {
+                                    if self.trace_syscalls_of(process) {
                                         debug!(
                                             "[{:?}] subscribe({:#x}, {}, @{:#x}, {:#x}) = {:#x} = {:?}",
                                             process.appid(),
@@ -725,6 +1058,7 @@ impl Kernel {
                                             res
                                         );
                                     }
+                                    self.trace_syscall(process, &syscall, res.into(), true);
                                     process.set_syscall_return_value(res.into());
                                 }
                                 Syscall::COMMAND {
@@ -737,16 +1071,16 @@ impl Kernel {
                                         platform.with_driver(
                                             driver_number,
                                             |driver| match driver {
-                                                Some(d) => d.command(
+                                                Some(d) => d.command_with_values(
                                                     subdriver_number,
                                                     arg0,
                                                     arg1,
                                                     process.appid(),
                                                 ),
-                                                None => ReturnCode::ENODEVICE,
+                                                None => ReturnCode::ENODEVICE.into(),
                                             },
                                         );
-                                    if config::CONFIG.trace_syscalls {
+                                    if self.trace_syscalls_of(process) {
                                         debug!(
                                             "[{:?}] cmd({:#x}, {}, {:#x}, {:#x}) = {:#x} = {:?}",
                                             process.appid(),
@@ -754,11 +1088,59 @@ impl Kernel {
                                             subdriver_number,
                                             arg0,
                                             arg1,
-                                            usize::from(res),
+                                            res.return_code() as usize,
                                             res
                                         );
                                     }
-                                    process.set_syscall_return_value(res.into());
+                                    self.trace_syscall(process, &syscall, res.return_code(), true);
+                                    process.set_syscall_return(res);
+                                }
+                                Syscall::COMMAND_WAIT {
+                                    driver_number,
+                                    subdriver_number,
+                                    upcall_number,
+                                    arg0,
+                                    arg1,
+                                } => {
+                                    let res =
+                                        platform.with_driver(
+                                            driver_number,
+                                            |driver| match driver {
+                                                Some(d) => d.command_with_values(
+                                                    subdriver_number,
+                                                    arg0,
+                                                    arg1,
+                                                    process.appid(),
+                                                ),
+                                                None => ReturnCode::ENODEVICE.into(),
+                                            },
+                                        );
+                                    if self.trace_syscalls_of(process) {
+                                        debug!(
+                                            "[{:?}] cmd_wait({:#x}, {}, {}, {:#x}, {:#x}) = {:#x} = {:?}",
+                                            process.appid(),
+                                            driver_number,
+                                            subdriver_number,
+                                            upcall_number,
+                                            arg0,
+                                            arg1,
+                                            res.return_code() as usize,
+                                            res
+                                        );
+                                    }
+                                    self.trace_syscall(process, &syscall, res.return_code(), true);
+                                    if res.is_success() {
+                                        // The command started, so block the
+                                        // process until its upcall arrives. If
+                                        // the capsule already scheduled it, the
+                                        // wait ends right away.
+                                        process.wait_for_upcall(CallbackId {
+                                            driver_num: driver_number,
+                                            subscribe_num: upcall_number,
+                                        });
+                                    } else {
+                                        process.set_syscall_return(res);
+                                    }
                                 }
                                 Syscall::ALLOW {
                                     driver_number,
@@ -781,7 +1163,7 @@ impl Kernel {
                                             None => ReturnCode::ENODEVICE,
                                         }
                                     });
-                                    if config::CONFIG.trace_syscalls {
+                                    if self.trace_syscalls_of(process) {
                                         debug!(
                                             "[{:?}] allow({:#x}, {}, @{:#x}, {:#x}) = {:#x} = {:?}",
                                             process.appid(),
@@ -793,6 +1175,43 @@ impl Kernel {
                                             res
                                         );
                                     }
+                                    self.trace_syscall(process, &syscall, res.into(), true);
+                                    process.set_syscall_return_value(res.into());
+                                }
+                                Syscall::READONLY_ALLOW {
+                                    driver_number,
+                                    subdriver_number,
+                                    allow_address,
+                                    allow_size,
+                                } => {
+                                    let res = platform.with_driver(driver_number, |driver| {
+                                        match driver {
+                                            Some(d) => match process
+                                                .allow_readonly(allow_address, allow_size)
+                                            {
+                                                Ok(oslice) => d.allow_readonly(
+                                                    process.appid(),
+                                                    subdriver_number,
+                                                    oslice,
+                                                ),
+                                                Err(err) => err, /* memory not valid */
+                                            },
+                                            None => ReturnCode::ENODEVICE,
+                                        }
+                                    });
+                                    if self.trace_syscalls_of(process) {
+                                        debug!(
+                                            "[{:?}] readonly_allow({:#x}, {}, @{:#x}, {:#x}) = {:#x} = {:?}",
+                                            process.appid(),
+                                            driver_number,
+                                            subdriver_number,
+                                            allow_address as usize,
+                                            allow_size,
+                                            usize::from(res),
+                                            res
+                                        );
+                                    }
+                                    self.trace_syscall(process, &syscall, res.into(), true);
                                     process.set_syscall_return_value(res.into());
                                 }
                             }
@@ -822,11 +1241,21 @@ impl Kernel {
                     // If the process is yielded or hasn't been started it is
                     // waiting for a callback. If there is a task scheduled for
                     // this process go ahead and set the process to execute it.
+                    let is_upcall = process.get_state() == process::State::Yielded;
+                    if process.waiting_for_upcall().is_some() {
+                        // The process is blocked in command-and-wait, and only
+                        // the upcall it waits for can wake it up.
+                        if !process.finish_wait() {
+                            break;
+                        }
+                        self.charge_quota(process, QuotaResource::Upcalls, 1);
+                        continue;
+                    }
                     match process.dequeue_task() {
                         None => break,
                         Some(cb) => match cb {
                             Task::FunctionCall(ccb) => {
-                                if config::CONFIG.trace_syscalls {
+                                if self.trace_syscalls_of(process) {
                                     debug!(
                                         "[{:?}] function_call @{:#x}({:#x}, {:#x}, {:#x}, {:#x})",
                                         process.appid(),
@@ -854,6 +1283,10 @@ impl Kernel {
                             }
                         },
                     }
+                    // The entry point of the process is not an upcall.
+                    if is_upcall {
+                        self.charge_quota(process, QuotaResource::Upcalls, 1);
+                    }
                 }
                 process::State::Fault => {
                     // We should never be scheduling a process in fault.
@@ -867,7 +1300,11 @@ impl Kernel {
                     return_reason = StoppedExecutingReason::Stopped;
                     break;
                 }
-                process::State::StoppedFaulted => {
+                process::State::Terminated => {
+                    return_reason = StoppedExecutingReason::NoWorkLeft;
+                    break;
+                }
+                process::State::StoppedFaulted | process::State::PendingRestart => {
                     return_reason = StoppedExecutingReason::StoppedFaulted;
                     break;
                 }
@@ -13,17 +13,17 @@
 
 use crate::common::list::{List, ListLink, ListNode};
 use crate::platform::Chip;
-use crate::process::ProcessType;
+use crate::process::ProcessSlot;
 use crate::sched::{Kernel, Scheduler, SchedulingDecision, StoppedExecutingReason};
 
 /// A node in the linked list the scheduler uses to track processes
 pub struct CoopProcessNode<'a> {
-    proc: &'static Option<&'static dyn ProcessType>,
+    proc: &'static ProcessSlot,
     next: ListLink<'a, CoopProcessNode<'a>>,
 }
 
 impl<'a> CoopProcessNode<'a> {
-    pub fn new(proc: &'static Option<&'static dyn ProcessType>) -> CoopProcessNode<'a> {
+    pub fn new(proc: &'static ProcessSlot) -> CoopProcessNode<'a> {
         CoopProcessNode {
             proc,
             next: ListLink::empty(),
@@ -62,7 +62,7 @@ impl<'a, C: Chip> Scheduler<C> for CooperativeSched<'a> {
             // Find next ready process. Place any *empty* process slots, or not-ready
             // processes, at the back of the queue.
             for node in self.processes.iter() {
-                match node.proc {
+                match node.proc.get() {
                     Some(proc) => {
                         if proc.ready() {
                             next = Some(proc.appid());
@@ -0,0 +1,456 @@
+//! Earliest Deadline First Scheduler for Tock
+//!
+//! This scheduler is meant for periodic real-time processes. A periodic process
+//! declares a period and an execution budget, either with the `Periodic` TLV in
+//! its TBF header or at runtime through `EDFSched::set_parameters()` (which a
+//! capsule can expose as a system call). At the start of every period a new job
+//! of the process is released, and the job's deadline is the start of the next
+//! period.
+//!
+//! The scheduler follows these rules:
+//!
+//! - Rule 1: Of the periodic processes that are ready and have budget left in
+//!           their current period, the one with the earliest deadline runs.
+//! - Rule 2: A periodic process runs for at most its budget in each period. The
+//!           budget is enforced with the scheduler timer, and a process that
+//!           uses up its budget counts as an overrun and does not run again
+//!           until its next period starts.
+//! - Rule 3: Processes that are not periodic run round-robin in the
+//!           background, whenever no periodic process can run.
+//!
+//! Whenever a new period starts for any process the scheduler re-evaluates
+//! which process runs, so a job with an earlier deadline preempts a running
+//! one. Overruns are reported through `introspection::KernelInfo`.
+
+use crate::callback::AppId;
+use crate::common::cells::OptionalCell;
+use crate::common::list::{List, ListLink, ListNode};
+use crate::hil::time;
+use crate::hil::time::Frequency;
+use crate::platform::Chip;
+use crate::process::ProcessSlot;
+use crate::returncode::ReturnCode;
+use crate::sched::{
+    Kernel, Scheduler, SchedulingDecision, StoppedExecutingReason, MIN_QUANTA_THRESHOLD_US,
+};
+use core::cell::Cell;
+
+struct EdfProcState {
+    /// The process the parameters below belong to. When the process in the
+    /// slot changes, for example because it restarted, the parameters are
+    /// read again from its TBF header.
+    appid: OptionalCell<AppId>,
+    /// Length of the period in microseconds, or 0 if the process is not
+    /// periodic.
+    period_us: Cell<u32>,
+    /// CPU time the process may use in each period, in microseconds.
+    budget_us: Cell<u32>,
+    /// Start of the next period in alarm ticks, which is also the deadline of
+    /// the current job.
+    next_release: Cell<u32>,
+    /// CPU time left in the current period, in microseconds.
+    remaining_us: Cell<u32>,
+}
+
+/// Nodes store per-process state
+pub struct EDFProcessNode<'a> {
+    proc: &'static ProcessSlot,
+    state: EdfProcState,
+    next: ListLink<'a, EDFProcessNode<'a>>,
+}
+
+impl<'a> EDFProcessNode<'a> {
+    pub fn new(proc: &'static ProcessSlot) -> EDFProcessNode<'a> {
+        EDFProcessNode {
+            proc,
+            state: EdfProcState {
+                appid: OptionalCell::empty(),
+                period_us: Cell::new(0),
+                budget_us: Cell::new(0),
+                next_release: Cell::new(0),
+                remaining_us: Cell::new(0),
+            },
+            next: ListLink::empty(),
+        }
+    }
+
+    fn is_periodic(&self) -> bool {
+        self.state.period_us.get() != 0
+    }
+
+    fn ready(&self) -> bool {
+        self.proc.get().map_or(false, |proc| proc.ready())
+    }
+}
+
+impl<'a> ListNode<'a, EDFProcessNode<'a>> for EDFProcessNode<'a> {
+    fn next(&'a self) -> &'static ListLink<'a, EDFProcessNode<'a>> {
+        &self.next
+    }
+}
+
+/// Earliest deadline first scheduler with budget enforcement.
+pub struct EDFSched<'a, A: 'static + time::Alarm<'static>> {
+    alarm: &'static A,
+    pub processes: List<'a, EDFProcessNode<'a>>,
+    /// The node of the process that is running.
+    running: OptionalCell<&'a EDFProcessNode<'a>>,
+    /// Whether the timeslice of the running process is the rest of its
+    /// budget, so that the timeslice expiring means the budget is used up.
+    running_until_budget: Cell<bool>,
+}
+
+impl<'a, A: 'static + time::Alarm<'static>> EDFSched<'a, A> {
+    /// Timeslice for processes that run in the background.
+    pub const BACKGROUND_TIMESLICE_US: u32 = 10000;
+
+    pub fn new(alarm: &'static A) -> Self {
+        Self {
+            alarm,
+            processes: List::new(),
+            running: OptionalCell::empty(),
+            running_until_budget: Cell::new(false),
+        }
+    }
+
+    /// Set the period and budget, in microseconds, of the process `appid`,
+    /// replacing any parameters from its TBF header. Its first period starts
+    /// immediately. A period of 0 makes the process run in the background.
+    ///
+    /// Returns `EINVAL` if the budget is 0 or longer than the period, or if
+    /// `appid` is not a process this scheduler knows about.
+    pub fn set_parameters(&self, appid: AppId, period_us: u32, budget_us: u32) -> ReturnCode {
+        if period_us != 0 && (budget_us == 0 || budget_us > period_us) {
+            return ReturnCode::EINVAL;
+        }
+        self.processes
+            .iter()
+            .find(|node| node.proc.get().map_or(false, |proc| proc.appid() == appid))
+            .map_or(ReturnCode::EINVAL, |node| {
+                node.state.appid.set(appid);
+                self.start_periods(node, period_us, budget_us);
+                ReturnCode::SUCCESS
+            })
+    }
+
+    /// The share of the CPU the periodic processes other than `appid` may
+    /// use, in millionths. Capsules that let processes set their own
+    /// parameters use this for admission control.
+    pub fn utilization_except(&self, appid: AppId) -> u64 {
+        self.processes
+            .iter()
+            .filter(|node| node.proc.get().map_or(false, |proc| proc.appid() != appid))
+            .map(|node| {
+                self.sync_process(node);
+                let period_us = node.state.period_us.get() as u64;
+                if period_us == 0 {
+                    0
+                } else {
+                    node.state.budget_us.get() as u64 * 1_000_000 / period_us
+                }
+            })
+            .sum()
+    }
+
+    fn us_to_ticks(us: u32) -> u32 {
+        (us as u64 * A::Frequency::frequency() as u64 / 1_000_000) as u32
+    }
+
+    fn ticks_to_us(ticks: u32) -> u32 {
+        let us = ticks as u64 * 1_000_000 / A::Frequency::frequency() as u64;
+        if us > u32::MAX as u64 {
+            u32::MAX
+        } else {
+            us as u32
+        }
+    }
+
+    /// Whether the alarm time `a` comes before `b`, allowing for the alarm
+    /// wrapping around.
+    fn before(a: u32, b: u32) -> bool {
+        (b.wrapping_sub(a) as i32) > 0
+    }
+
+    fn start_periods(&self, node: &EDFProcessNode<'a>, period_us: u32, budget_us: u32) {
+        node.state.period_us.set(period_us);
+        node.state.budget_us.set(budget_us);
+        node.state.next_release.set(self.alarm.now());
+        node.state.remaining_us.set(0);
+    }
+
+    /// Make sure the node's parameters belong to the process currently in its
+    /// slot, reading them from the TBF header when the process changed.
+    fn sync_process(&self, node: &EDFProcessNode<'a>) {
+        match node.proc.get() {
+            Some(proc) => {
+                let appid = proc.appid();
+                if node.state.appid.map_or(true, |id| *id != appid) {
+                    node.state.appid.set(appid);
+                    let (period_us, budget_us) = proc.get_periodic_parameters().unwrap_or((0, 0));
+                    self.start_periods(node, period_us, budget_us);
+                }
+            }
+            None => {
+                node.state.appid.clear();
+                node.state.period_us.set(0);
+            }
+        }
+    }
+
+    /// Release a new job if the current period of the node is over. If
+    /// several periods passed, for example because the process was not ready,
+    /// skip ahead to the current one.
+    fn release(&self, node: &EDFProcessNode<'a>, now: u32) {
+        let next_release = node.state.next_release.get();
+        if Self::before(now, next_release) {
+            return;
+        }
+        let period = Self::us_to_ticks(node.state.period_us.get()).max(1);
+        let missed_periods = now.wrapping_sub(next_release) / period;
+        node.state.next_release.set(
+            next_release
+                .wrapping_add(missed_periods.wrapping_mul(period))
+                .wrapping_add(period),
+        );
+        node.state.remaining_us.set(node.state.budget_us.get());
+    }
+
+    /// Rotate the list of processes so that `node` is at the tail, which gives
+    /// background processes their round-robin order.
+    fn move_to_tail(&self, node: &EDFProcessNode<'a>) {
+        while let Some(head) = self.processes.pop_head() {
+            self.processes.push_tail(head);
+            if head as *const _ == node as *const _ {
+                break;
+            }
+        }
+    }
+}
+
+impl<'a, A: 'static + time::Alarm<'static>> time::AlarmClient for EDFSched<'a, A> {
+    fn fired(&self) {
+        // The alarm only wakes the chip so that `next()` runs again when the
+        // next period starts.
+    }
+}
+
+impl<'a, A: 'static + time::Alarm<'static>, C: Chip> Scheduler<C> for EDFSched<'a, A> {
+    fn next(&self, kernel: &Kernel) -> SchedulingDecision {
+        if kernel.processes_blocked() {
+            // No processes ready
+            return SchedulingDecision::TrySleep;
+        }
+
+        let now = self.alarm.now();
+        let mut earliest: Option<&EDFProcessNode<'a>> = None;
+        let mut background: Option<&EDFProcessNode<'a>> = None;
+        let mut next_release: Option<u32> = None;
+        for node in self.processes.iter() {
+            self.sync_process(node);
+            if !node.is_periodic() {
+                if background.is_none() && node.ready() {
+                    background = Some(node);
+                }
+                continue;
+            }
+
+            self.release(node, now);
+            let release = node.state.next_release.get();
+            if next_release.map_or(true, |r| Self::before(release, r)) {
+                next_release = Some(release);
+            }
+            if node.ready()
+                && node.state.remaining_us.get() >= MIN_QUANTA_THRESHOLD_US
+                && earliest.map_or(true, |e| Self::before(release, e.state.next_release.get()))
+            {
+                earliest = Some(node);
+            }
+        }
+
+        // Stop running whatever is chosen when the next period starts, so
+        // that a job with an earlier deadline can preempt it.
+        let until_release_us = next_release.map_or(u32::MAX, |release| {
+            Self::ticks_to_us(release.wrapping_sub(now)).max(MIN_QUANTA_THRESHOLD_US)
+        });
+
+        match (earliest, background) {
+            (Some(node), _) => {
+                let remaining_us = node.state.remaining_us.get();
+                let timeslice = remaining_us.min(until_release_us);
+                self.running.set(node);
+                self.running_until_budget.set(timeslice == remaining_us);
+                let appid = node.proc.get().unwrap().appid(); // Periodic nodes have a process
+                SchedulingDecision::RunProcess((appid, Some(timeslice)))
+            }
+            (None, Some(node)) => {
+                let timeslice = Self::BACKGROUND_TIMESLICE_US.min(until_release_us);
+                self.running.set(node);
+                self.running_until_budget.set(false);
+                let appid = node.proc.get().unwrap().appid(); // Ready nodes have a process
+                SchedulingDecision::RunProcess((appid, Some(timeslice)))
+            }
+            (None, None) => {
+                // Only periodic processes that used up their budget are ready,
+                // so sleep until the next period starts.
+                if let Some(release) = next_release {
+                    self.alarm.set_alarm(release);
+                }
+                SchedulingDecision::TrySleep
+            }
+        }
+    }
+
+    fn result(&self, result: StoppedExecutingReason, execution_time_us: Option<u32>) {
+        let node = match self.running.take() {
+            Some(node) => node,
+            None => return,
+        };
+        if node.is_periodic() {
+            let execution_time_us = execution_time_us.unwrap_or(0);
+            node.state.remaining_us.set(
+                node.state
+                    .remaining_us
+                    .get()
+                    .saturating_sub(execution_time_us),
+            );
+            if result == StoppedExecutingReason::TimesliceExpired && self.running_until_budget.get()
+            {
+                node.state.remaining_us.set(0);
+                node.proc.get().map(|proc| proc.debug_budget_overrun());
+            }
+        } else {
+            self.move_to_tail(node);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::{EDFProcessNode, EDFSched};
+    use crate::callback::AppId;
+    use crate::returncode::ReturnCode;
+    use crate::sched::{Kernel, Scheduler, SchedulingDecision, StoppedExecutingReason};
+    use crate::test_util::{self, TestAlarm, TestChip};
+    use std::boxed::Box;
+    use std::vec::Vec;
+
+    fn scheduler(
+        num_procs: usize,
+    ) -> (
+        &'static Kernel,
+        &'static TestAlarm,
+        &'static EDFSched<'static, TestAlarm>,
+    ) {
+        let kernel = test_util::kernel(num_procs);
+        let alarm = test_util::alarm();
+        let sched: &'static EDFSched<'static, TestAlarm> =
+            Box::leak(Box::new(EDFSched::new(alarm)));
+        let slots = kernel.process_slots(&test_util::TestCapability);
+        for index in 0..num_procs {
+            test_util::process(kernel, index);
+            let node: &'static EDFProcessNode =
+                Box::leak(Box::new(EDFProcessNode::new(&slots[index])));
+            sched.processes.push_tail(node);
+        }
+        (kernel, alarm, sched)
+    }
+
+    /// The process `sched` decides to run and its timeslice.
+    fn next(kernel: &Kernel, sched: &EDFSched<'static, TestAlarm>) -> Option<(AppId, Option<u32>)> {
+        match Scheduler::<TestChip>::next(sched, kernel) {
+            SchedulingDecision::RunProcess(decision) => Some(decision),
+            SchedulingDecision::TrySleep => None,
+        }
+    }
+
+    #[test]
+    fn earliest_deadline_runs_first() {
+        let (kernel, alarm, sched) = scheduler(3);
+        let appids: Vec<AppId> = kernel.get_process_iter().map(|p| p.appid()).collect();
+        // Process 2 stays in the background.
+        assert_eq!(
+            sched.set_parameters(appids[0], 10_000, 2_000),
+            ReturnCode::SUCCESS
+        );
+        assert_eq!(
+            sched.set_parameters(appids[1], 5_000, 1_000),
+            ReturnCode::SUCCESS
+        );
+
+        let (appid, timeslice) = next(kernel, sched).unwrap();
+        assert!(appid == appids[1]);
+        assert_eq!(timeslice, Some(1_000));
+        Scheduler::<TestChip>::result(sched, StoppedExecutingReason::NoWorkLeft, Some(1_000));
+
+        // Process 1 used its budget, so the later deadline runs next.
+        alarm.now.set(1_000);
+        let (appid, timeslice) = next(kernel, sched).unwrap();
+        assert!(appid == appids[0]);
+        assert_eq!(timeslice, Some(2_000));
+        Scheduler::<TestChip>::result(sched, StoppedExecutingReason::NoWorkLeft, Some(2_000));
+
+        // Both periodic processes are out of budget until process 1's next
+        // period, so the background process runs until then.
+        alarm.now.set(3_000);
+        let (appid, timeslice) = next(kernel, sched).unwrap();
+        assert!(appid == appids[2]);
+        assert_eq!(timeslice, Some(2_000));
+        Scheduler::<TestChip>::result(sched, StoppedExecutingReason::TimesliceExpired, Some(2_000));
+
+        alarm.now.set(5_000);
+        let (appid, _) = next(kernel, sched).unwrap();
+        assert!(appid == appids[1]);
+    }
+
+    #[test]
+    fn budget_overrun_is_counted() {
+        let (kernel, alarm, sched) = scheduler(1);
+        let process = kernel.get_process_iter().next().unwrap();
+        assert_eq!(
+            sched.set_parameters(process.appid(), 10_000, 2_000),
+            ReturnCode::SUCCESS
+        );
+
+        // Running less than the budget is not an overrun.
+        let (_, timeslice) = next(kernel, sched).unwrap();
+        assert_eq!(timeslice, Some(2_000));
+        Scheduler::<TestChip>::result(sched, StoppedExecutingReason::KernelPreemption, Some(500));
+        assert_eq!(process.debug_budget_overrun_count(), 0);
+
+        // The next timeslice is the rest of the budget, and using all of it
+        // is an overrun.
+        alarm.now.set(500);
+        let (_, timeslice) = next(kernel, sched).unwrap();
+        assert_eq!(timeslice, Some(1_500));
+        Scheduler::<TestChip>::result(sched, StoppedExecutingReason::TimesliceExpired, Some(1_500));
+        assert_eq!(process.debug_budget_overrun_count(), 1);
+
+        // The process does not run again until its next period, and the
+        // scheduler sets the alarm for then.
+        alarm.now.set(2_000);
+        assert!(next(kernel, sched).is_none());
+        assert_eq!(alarm.alarm.get(), 10_000);
+
+        alarm.now.set(10_000);
+        let (_, timeslice) = next(kernel, sched).unwrap();
+        assert_eq!(timeslice, Some(2_000));
+    }
+
+    #[test]
+    fn utilization_counts_other_periodic_processes() {
+        let (kernel, _, sched) = scheduler(3);
+        let appids: Vec<AppId> = kernel.get_process_iter().map(|p| p.appid()).collect();
+        assert_eq!(sched.utilization_except(appids[0]), 0);
+
+        sched.set_parameters(appids[0], 10_000, 2_000);
+        sched.set_parameters(appids[1], 4_000, 1_000);
+        assert_eq!(sched.utilization_except(appids[2]), 450_000);
+        assert_eq!(sched.utilization_except(appids[0]), 250_000);
+
+        // Background processes use no share.
+        sched.set_parameters(appids[1], 0, 0);
+        assert_eq!(sched.utilization_except(appids[2]), 200_000);
+    }
+}
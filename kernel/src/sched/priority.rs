@@ -1,8 +1,11 @@
 //! Fixed Priority Scheduler for Tock
 //!
-//! This scheduler assigns priority to processes based on their order in the
-//! `PROCESSES` array, and runs the highest priority process available at any
-//! point in time. Kernel tasks (bottom half interrupt handling / deferred call
+//! This scheduler runs the highest priority process available at any point in
+//! time. Processes declare their priority with the `Priority` TLV in their TBF
+//! header (see `ProcessType::priority()`), and processes with the same priority
+//! are ordered by their short ID and then by their index in the `PROCESSES`
+//! array, so the order does not change when apps are reinstalled in a different
+//! order. Kernel tasks (bottom half interrupt handling / deferred call
 //! handling) always take priority over userspace processes.
 //!
 //! Notably, there is no need to enforce timeslices, as it is impossible for a
@@ -14,12 +17,24 @@ use crate::callback::AppId;
 use crate::common::cells::OptionalCell;
 use crate::common::dynamic_deferred_call::DynamicDeferredCall;
 use crate::platform::Chip;
+use crate::process::ProcessType;
 use crate::sched::{Kernel, Scheduler, SchedulingDecision, StoppedExecutingReason};
 
-/// Priority scheduler based on the order of processes in the `PROCESSES` array.
+/// Where a process ranks in the scheduling order. Lower ranks run first.
+type Rank = (u32, u32, usize);
+
+fn rank(process: &dyn ProcessType) -> Rank {
+    (
+        process.priority(),
+        process.short_id(),
+        process.appid().index,
+    )
+}
+
+/// Priority scheduler based on the priorities in the processes' TBF headers.
 pub struct PrioritySched {
     kernel: &'static Kernel,
-    running: OptionalCell<AppId>,
+    running: OptionalCell<Rank>,
 }
 
 impl PrioritySched {
@@ -37,17 +52,17 @@ impl<C: Chip> Scheduler<C> for PrioritySched {
             // No processes ready
             SchedulingDecision::TrySleep
         } else {
-            // Iterates in-order through the process array, always running the
-            // first process it finds that is ready to run. This enforces the
-            // priorities of all processes.
+            // Always run the highest ranked process that is ready to run.
+            // This enforces the priorities of all processes.
             let next = self
                 .kernel
                 .get_process_iter()
-                .find(|&proc| proc.ready())
-                .map_or(None, |proc| Some(proc.appid()));
-            self.running.insert(next);
+                .filter(|&proc| proc.ready())
+                .min_by_key(|&proc| rank(proc))
+                .unwrap();
+            self.running.set(rank(next));
 
-            SchedulingDecision::RunProcess((next.unwrap(), None))
+            SchedulingDecision::RunProcess((next.appid(), None))
         }
     }
 
@@ -58,14 +73,11 @@ impl<C: Chip> Scheduler<C> for PrioritySched {
         // this app is communicating via IPC with a higher priority app.
         !(chip.has_pending_interrupts()
             || DynamicDeferredCall::global_instance_calls_pending().unwrap_or(false)
-            || self
-                .kernel
-                .get_process_iter()
-                .position(|proc| proc.ready())
-                .map_or(false, |ready_idx| {
-                    self.running
-                        .map_or(false, |running| ready_idx < running.index)
-                }))
+            || self.running.map_or(false, |running| {
+                self.kernel
+                    .get_process_iter()
+                    .any(|proc| proc.ready() && rank(proc) < *running)
+            }))
     }
 
     fn result(&self, _: StoppedExecutingReason, _: Option<u32>) {
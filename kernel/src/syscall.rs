@@ -3,6 +3,7 @@
 use core::fmt::Write;
 
 use crate::process;
+use crate::returncode::ReturnCode;
 
 /// The syscall number assignments.
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -47,6 +48,93 @@ pub enum Syscall {
     ///
     /// SVC_NUM = 4
     MEMOP { operand: usize, arg0: usize },
+
+    /// End the process, optionally asking to be restarted.
+    ///
+    /// SVC_NUM = 5
+    EXIT {
+        which: usize,
+        completion_code: usize,
+    },
+
+    /// Share a memory buffer with the kernel that the kernel may only read.
+    /// The buffer can be in the process's flash as well as its RAM.
+    ///
+    /// SVC_NUM = 6
+    #[allow(non_camel_case_types)]
+    READONLY_ALLOW {
+        driver_number: usize,
+        subdriver_number: usize,
+        allow_address: *const u8,
+        allow_size: usize,
+    },
+
+    /// Instruct a capsule to perform an operation, then block until the
+    /// capsule schedules the upcall `upcall_number` of the same driver. The
+    /// arguments of the upcall are returned instead of calling the upcall's
+    /// function. The command number and the upcall number share the second
+    /// register: the command number is in the low 16 bits and the upcall
+    /// number in the high 16 bits.
+    ///
+    /// SVC_NUM = 7
+    #[allow(non_camel_case_types)]
+    COMMAND_WAIT {
+        driver_number: usize,
+        subdriver_number: usize,
+        upcall_number: usize,
+        arg0: usize,
+        arg1: usize,
+    },
+}
+
+/// The value a system call returns to the process.
+///
+/// Most system calls return a single `ReturnCode` in the first register. A
+/// system call can instead succeed or fail with up to three more values, which
+/// are returned in the registers that held the second to fourth arguments.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SyscallReturn {
+    /// Only a `ReturnCode`, in the first register. The other registers are not
+    /// changed.
+    ReturnCode(ReturnCode),
+    /// The system call succeeded. `SUCCESS` is in the first register and the
+    /// values in the other three. Values that are not used should be 0.
+    Success(usize, usize, usize),
+    /// The system call failed. The error code is in the first register and
+    /// the values, which can describe the failure further, in the other three.
+    Failure(ReturnCode, usize, usize, usize),
+}
+
+impl SyscallReturn {
+    /// The value of the first register.
+    pub fn return_code(&self) -> isize {
+        match *self {
+            SyscallReturn::ReturnCode(rc) | SyscallReturn::Failure(rc, _, _, _) => rc.into(),
+            SyscallReturn::Success(_, _, _) => ReturnCode::SUCCESS.into(),
+        }
+    }
+
+    /// The values of the other three registers, or `None` if they are not
+    /// changed.
+    pub fn values(&self) -> Option<[usize; 3]> {
+        match *self {
+            SyscallReturn::ReturnCode(_) => None,
+            SyscallReturn::Success(v0, v1, v2) | SyscallReturn::Failure(_, v0, v1, v2) => {
+                Some([v0, v1, v2])
+            }
+        }
+    }
+
+    /// Whether the system call succeeded.
+    pub fn is_success(&self) -> bool {
+        self.return_code() >= 0
+    }
+}
+
+impl From<ReturnCode> for SyscallReturn {
+    fn from(rc: ReturnCode) -> SyscallReturn {
+        SyscallReturn::ReturnCode(rc)
+    }
 }
 
 /// Why the process stopped executing and execution returned to the kernel.
@@ -60,6 +148,31 @@ pub enum ContextSwitchReason {
     Interrupted,
 }
 
+/// Architecture-specific state of a process captured after it faulted, so that
+/// the fault can be recorded and examined later.
+#[derive(Clone, Copy, Default)]
+pub struct FaultContext {
+    /// The registers of the process, in an architecture-specific order. Only
+    /// the first `num_registers` entries are valid.
+    pub registers: [usize; 32],
+
+    /// How many entries of `registers` are valid.
+    pub num_registers: usize,
+
+    /// Architecture-specific description of the fault, for example the value of
+    /// a fault status register.
+    pub fault_reason: usize,
+
+    /// The address that caused the fault, if the architecture reports it.
+    pub fault_address: Option<usize>,
+
+    /// Whether the process caused a hardware fault. This is `false` if the
+    /// kernel put the process in the fault state itself, for example because
+    /// it exceeded a quota. Architectures only set `fault_reason` and
+    /// `fault_address` for hardware faults.
+    pub hardware_fault: bool,
+}
+
 /// This trait must be implemented by the architecture of the chip Tock is
 /// running on. It allows the kernel to manage switching to and from processes
 /// in an architecture-agnostic manner.
@@ -104,6 +217,19 @@ pub trait UserspaceKernelBoundary {
         return_value: isize,
     );
 
+    /// Set a `SyscallReturn` the process should see when it begins executing
+    /// again after the syscall. The first register gets
+    /// `return_value.return_code()`, and if `return_value.values()` is not
+    /// `None` the next three registers that syscall arguments are passed in
+    /// get those values. Like `set_syscall_return_value`, this will only be
+    /// called after a process has called a syscall.
+    unsafe fn set_syscall_return(
+        &self,
+        stack_pointer: *const usize,
+        state: &mut Self::StoredState,
+        return_value: SyscallReturn,
+    );
+
     /// Set the function that the process should execute when it is resumed.
     /// This has two major uses: 1) sets up the initial function call to
     /// `_start` when the process is started for the very first time; 2) tells
@@ -158,6 +284,21 @@ pub trait UserspaceKernelBoundary {
         state: &Self::StoredState,
         writer: &mut dyn Write,
     );
+
+    /// Store the registers of a process identified by its stack pointer, and
+    /// why it faulted, in `context`. This is called after a process faults.
+    /// The kernel sets `context.hardware_fault` before calling this; if it is
+    /// `false` the fault status registers describe an earlier fault, if any,
+    /// and must not be stored.
+    ///
+    /// The default implementation stores nothing.
+    unsafe fn store_fault_context(
+        &self,
+        _stack_pointer: *const usize,
+        _state: &Self::StoredState,
+        _context: &mut FaultContext,
+    ) {
+    }
 }
 
 /// Helper function for converting raw values passed back from an application
@@ -203,6 +344,23 @@ pub fn arguments_to_syscall(
             operand: r0,
             arg0: r1,
         }),
+        5 => Some(Syscall::EXIT {
+            which: r0,
+            completion_code: r1,
+        }),
+        6 => Some(Syscall::READONLY_ALLOW {
+            driver_number: r0,
+            subdriver_number: r1,
+            allow_address: r2 as *const u8,
+            allow_size: r3,
+        }),
+        7 => Some(Syscall::COMMAND_WAIT {
+            driver_number: r0,
+            subdriver_number: r1 & 0xffff,
+            upcall_number: r1 >> 16,
+            arg0: r2,
+            arg1: r3,
+        }),
         _ => None,
     }
 }
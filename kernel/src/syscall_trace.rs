@@ -0,0 +1,249 @@
+//! Compact binary tracing of system calls.
+//!
+//! Setting `trace_syscalls` in the kernel configuration prints every system
+//! call through `debug!()`, which is slow enough to change the timing of the
+//! system and can easily overrun the debug buffer. Instead, a board can
+//! register a `SyscallTracer` with the kernel, which is then handed every
+//! system call a process makes. `SyscallTraceBuffer` is a tracer that stores
+//! fixed-size binary records in a ring buffer, to be drained later (for
+//! example with the process console's `trace` command) and decoded on the host
+//! with the `tools/syscall_trace` tool.
+//!
+//! Record format
+//! -------------
+//!
+//! Every record is `SYSCALL_TRACE_RECORD_LEN` bytes, all fields little-endian.
+//!
+//! ```text
+//! offset  size  field
+//!      0     4  timestamp, in ticks of the tracer's clock
+//!      4     2  app ID (`AppId::id()`)
+//!      6     1  kind: 0 yield, 1 subscribe, 2 command, 3 allow, 4 memop,
+//!               5 exit, 6 read-only allow, 7 command and wait, 0xff
+//!               records dropped
+//!      7     1  flags, bit 0 set if the system call was not dispatched
+//!               because it was filtered or not permitted
+//!      8     4  driver number (memop: operand, exit: which)
+//!     12     4  subdriver number (command and wait: upcall number in the
+//!               high 16 bits)
+//!     16     4  first argument (subscribe: callback, allow: address,
+//!               memop: argument, exit: completion code; records dropped:
+//!               how many)
+//!     20     4  second argument (subscribe: appdata, allow: size)
+//!     24     4  return value
+//! ```
+//!
+//! Usage
+//! -----
+//!
+//! ```rust,ignore
+//! static mut TRACE_RECORDS: [kernel::SyscallTraceRecord; 64] =
+//!     [[0; kernel::SYSCALL_TRACE_RECORD_LEN]; 64];
+//!
+//! let syscall_trace = static_init!(
+//!     kernel::SyscallTraceBuffer<'static, sam4l::ast::Ast>,
+//!     kernel::SyscallTraceBuffer::new(&sam4l::ast::AST, &mut TRACE_RECORDS)
+//! );
+//! board_kernel.set_syscall_tracer(syscall_trace, &process_management_capability);
+//! ```
+
+use core::cell::Cell;
+
+use crate::callback::AppId;
+use crate::common::cells::MapCell;
+use crate::common::{Queue, RingBuffer};
+use crate::hil::time::{Frequency, Time};
+use crate::syscall::Syscall;
+
+/// Length of one encoded trace record in bytes.
+pub const SYSCALL_TRACE_RECORD_LEN: usize = 28;
+
+/// One encoded trace record.
+pub type SyscallTraceRecord = [u8; SYSCALL_TRACE_RECORD_LEN];
+
+const KIND_RECORDS_DROPPED: u8 = 0xff;
+
+const FLAG_NOT_DISPATCHED: u8 = 0x1;
+
+/// Receives every system call processes make, when registered with
+/// `Kernel::set_syscall_tracer()`.
+pub trait SyscallTracer {
+    /// Record that the process `appid` made the system call `syscall`, which
+    /// returned `return_value`. `dispatched` is false if the kernel did not
+    /// handle the call because it was filtered or not permitted.
+    fn record(&self, appid: AppId, syscall: &Syscall, return_value: isize, dispatched: bool);
+
+    /// Copy as many whole records as fit into `buffer`, removing them from the
+    /// trace, and return how many bytes were written.
+    fn drain(&self, buffer: &mut [u8]) -> usize;
+
+    /// The frequency of the clock used for the timestamps of records, in Hz.
+    fn frequency(&self) -> u32;
+}
+
+/// A `SyscallTracer` that keeps records in a ring buffer until they are
+/// drained.
+///
+/// When the buffer is full new records are dropped, and a record noting how
+/// many were dropped is added once there is room again.
+pub struct SyscallTraceBuffer<'a, T: Time> {
+    time: &'a T,
+    records: MapCell<RingBuffer<'static, SyscallTraceRecord>>,
+    dropped: Cell<u32>,
+}
+
+impl<'a, T: Time> SyscallTraceBuffer<'a, T> {
+    pub fn new(
+        time: &'a T,
+        records: &'static mut [SyscallTraceRecord],
+    ) -> SyscallTraceBuffer<'a, T> {
+        SyscallTraceBuffer {
+            time,
+            records: MapCell::new(RingBuffer::new(records)),
+            dropped: Cell::new(0),
+        }
+    }
+
+    fn encode(&self, app_id: usize, kind: u8, flags: u8, words: [usize; 5]) -> SyscallTraceRecord {
+        let mut record = [0; SYSCALL_TRACE_RECORD_LEN];
+        record[0..4].copy_from_slice(&self.time.now().to_le_bytes());
+        record[4..6].copy_from_slice(&(app_id as u16).to_le_bytes());
+        record[6] = kind;
+        record[7] = flags;
+        for (i, word) in words.iter().enumerate() {
+            record[8 + 4 * i..12 + 4 * i].copy_from_slice(&(*word as u32).to_le_bytes());
+        }
+        record
+    }
+}
+
+impl<T: Time> SyscallTracer for SyscallTraceBuffer<'_, T> {
+    fn record(&self, appid: AppId, syscall: &Syscall, return_value: isize, dispatched: bool) {
+        let (kind, words) = match *syscall {
+            Syscall::YIELD => (0, [0; 4]),
+            Syscall::SUBSCRIBE {
+                driver_number,
+                subdriver_number,
+                callback_ptr,
+                appdata,
+            } => (
+                1,
+                [
+                    driver_number,
+                    subdriver_number,
+                    callback_ptr as usize,
+                    appdata,
+                ],
+            ),
+            Syscall::COMMAND {
+                driver_number,
+                subdriver_number,
+                arg0,
+                arg1,
+            } => (2, [driver_number, subdriver_number, arg0, arg1]),
+            Syscall::COMMAND_WAIT {
+                driver_number,
+                subdriver_number,
+                upcall_number,
+                arg0,
+                arg1,
+            } => (
+                7,
+                [
+                    driver_number,
+                    subdriver_number | upcall_number << 16,
+                    arg0,
+                    arg1,
+                ],
+            ),
+            Syscall::ALLOW {
+                driver_number,
+                subdriver_number,
+                allow_address,
+                allow_size,
+            } => (
+                3,
+                [
+                    driver_number,
+                    subdriver_number,
+                    allow_address as usize,
+                    allow_size,
+                ],
+            ),
+            Syscall::READONLY_ALLOW {
+                driver_number,
+                subdriver_number,
+                allow_address,
+                allow_size,
+            } => (
+                6,
+                [
+                    driver_number,
+                    subdriver_number,
+                    allow_address as usize,
+                    allow_size,
+                ],
+            ),
+            Syscall::MEMOP { operand, arg0 } => (4, [operand, 0, arg0, 0]),
+            Syscall::EXIT {
+                which,
+                completion_code,
+            } => (5, [which, 0, completion_code, 0]),
+        };
+        let flags = if dispatched { 0 } else { FLAG_NOT_DISPATCHED };
+        let record = self.encode(
+            appid.id(),
+            kind,
+            flags,
+            [
+                words[0],
+                words[1],
+                words[2],
+                words[3],
+                return_value as usize,
+            ],
+        );
+
+        self.records.map(|records| {
+            let dropped = self.dropped.get();
+            if dropped > 0 {
+                // Note the dropped records first, so that the gap shows up in
+                // the right place in the trace.
+                if records.available_len() < 2 {
+                    self.dropped.set(dropped.saturating_add(1));
+                    return;
+                }
+                records.enqueue(self.encode(
+                    0,
+                    KIND_RECORDS_DROPPED,
+                    0,
+                    [0, 0, dropped as usize, 0, 0],
+                ));
+                self.dropped.set(0);
+            }
+            if !records.enqueue(record) {
+                self.dropped.set(1);
+            }
+        });
+    }
+
+    fn drain(&self, buffer: &mut [u8]) -> usize {
+        self.records.map_or(0, |records| {
+            let mut written = 0;
+            for chunk in buffer.chunks_exact_mut(SYSCALL_TRACE_RECORD_LEN) {
+                match records.dequeue() {
+                    Some(record) => {
+                        chunk.copy_from_slice(&record);
+                        written += SYSCALL_TRACE_RECORD_LEN;
+                    }
+                    None => break,
+                }
+            }
+            written
+        })
+    }
+
+    fn frequency(&self) -> u32 {
+        T::Frequency::frequency()
+    }
+}
@@ -117,6 +117,12 @@ pub(crate) enum TbfHeaderTypes {
     TbfHeaderWriteableFlashRegions = 2,
     TbfHeaderPackageName = 3,
     TbfHeaderFixedAddresses = 5,
+    TbfHeaderPermissions = 6,
+    TbfHeaderKernelVersion = 8,
+    TbfHeaderShortId = 10,
+    TbfHeaderPeriodic = 11,
+    TbfHeaderPriority = 12,
+    TbfHeaderCredentials = 128,
 
     /// Some field in the header that we do not understand. Since the TLV format
     /// specifies the length of each section, if we get a field we do not
@@ -176,6 +182,95 @@ pub(crate) struct TbfHeaderV2FixedAddresses {
     start_process_flash: u32,
 }
 
+/// A set of commands of one driver that the app is permitted to use.
+///
+/// Command numbers are grouped into blocks of 64. `offset` selects the block
+/// and bit `n` of `allowed_commands` permits command `offset * 64 + n`. An app
+/// may list the same driver more than once to permit commands from several
+/// blocks.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct TbfHeaderV2DriverPermission {
+    driver_number: u32,
+    offset: u32,
+    allowed_commands: u64,
+}
+
+/// The range of kernel ABI versions the app was built to work with.
+///
+/// Versions are compared as (major, minor) pairs, and both ends of the range
+/// are inclusive.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct TbfHeaderV2KernelVersion {
+    min_major: u16,
+    min_minor: u16,
+    max_major: u16,
+    max_minor: u16,
+}
+
+/// Timing requirements of a periodic real-time app, used by deadline-based
+/// schedulers.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct TbfHeaderV2Periodic {
+    period_us: u32,
+    budget_us: u32,
+}
+
+/// Formats of the cryptographic credentials an app can carry in its header.
+/// Format 0 is reserved and is rejected when parsing the header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TbfHeaderV2CredentialsType {
+    /// SHA-256 hash of the app binary (32 bytes).
+    Sha256 = 1,
+    /// HMAC-SHA256 of the app binary (32 bytes) with a key held by the board.
+    HmacSha256 = 2,
+    /// ECDSA signature over the app binary using curve NIST P-256. The
+    /// signature is stored as `r` followed by `s` (64 bytes).
+    EcdsaNistP256 = 3,
+    /// Ed25519 signature over the app binary (64 bytes).
+    Ed25519 = 4,
+}
+
+impl TbfHeaderV2CredentialsType {
+    /// Number of bytes of credential data this format requires.
+    fn data_length(&self) -> usize {
+        match self {
+            TbfHeaderV2CredentialsType::Sha256 => 32,
+            TbfHeaderV2CredentialsType::HmacSha256 => 32,
+            TbfHeaderV2CredentialsType::EcdsaNistP256 => 64,
+            TbfHeaderV2CredentialsType::Ed25519 => 64,
+        }
+    }
+}
+
+/// Cryptographic credentials for the app.
+///
+/// The credentials cover the whole TBF object, header included, except for
+/// the header checksum and the credentials TLV itself. Both of those depend
+/// on the credentials, so they cannot be covered. The kernel does not check
+/// the credentials itself, instead it hands them to the
+/// `ProcessCredentialsPolicy` the board provides when loading processes.
+#[derive(Clone, Copy, Debug)]
+pub struct TbfHeaderV2Credentials {
+    format: TbfHeaderV2CredentialsType,
+    data: &'static [u8],
+    /// Offset of the credentials TLV from the start of the header, and the
+    /// length of the TLV including its padding.
+    tlv_offset: usize,
+    tlv_length: usize,
+}
+
+impl TbfHeaderV2Credentials {
+    /// The format of these credentials.
+    pub fn format(&self) -> TbfHeaderV2CredentialsType {
+        self.format
+    }
+
+    /// The raw credential (hash, MAC, or signature) stored in flash.
+    pub fn data(&self) -> &'static [u8] {
+        self.data
+    }
+}
+
 // Conversion functions from slices to the various TBF fields.
 
 impl core::convert::TryFrom<&[u8]> for TbfHeaderV2Base {
@@ -221,6 +316,12 @@ impl core::convert::TryFrom<u16> for TbfHeaderTypes {
             2 => Ok(TbfHeaderTypes::TbfHeaderWriteableFlashRegions),
             3 => Ok(TbfHeaderTypes::TbfHeaderPackageName),
             5 => Ok(TbfHeaderTypes::TbfHeaderFixedAddresses),
+            6 => Ok(TbfHeaderTypes::TbfHeaderPermissions),
+            8 => Ok(TbfHeaderTypes::TbfHeaderKernelVersion),
+            10 => Ok(TbfHeaderTypes::TbfHeaderShortId),
+            11 => Ok(TbfHeaderTypes::TbfHeaderPeriodic),
+            12 => Ok(TbfHeaderTypes::TbfHeaderPriority),
+            128 => Ok(TbfHeaderTypes::TbfHeaderCredentials),
             _ => Ok(TbfHeaderTypes::Unknown),
         }
     }
@@ -308,11 +409,127 @@ impl core::convert::TryFrom<&[u8]> for TbfHeaderV2FixedAddresses {
     }
 }
 
+impl core::convert::TryFrom<&[u8]> for TbfHeaderV2DriverPermission {
+    type Error = TbfParseError;
+
+    fn try_from(b: &[u8]) -> Result<TbfHeaderV2DriverPermission, Self::Error> {
+        Ok(TbfHeaderV2DriverPermission {
+            driver_number: u32::from_le_bytes(
+                b.get(0..4)
+                    .ok_or(TbfParseError::InternalError)?
+                    .try_into()?,
+            ),
+            offset: u32::from_le_bytes(
+                b.get(4..8)
+                    .ok_or(TbfParseError::InternalError)?
+                    .try_into()?,
+            ),
+            allowed_commands: u64::from_le_bytes(
+                b.get(8..16)
+                    .ok_or(TbfParseError::InternalError)?
+                    .try_into()?,
+            ),
+        })
+    }
+}
+
+impl core::convert::TryFrom<&[u8]> for TbfHeaderV2Periodic {
+    type Error = TbfParseError;
+
+    fn try_from(b: &[u8]) -> Result<TbfHeaderV2Periodic, Self::Error> {
+        Ok(TbfHeaderV2Periodic {
+            period_us: u32::from_le_bytes(
+                b.get(0..4)
+                    .ok_or(TbfParseError::InternalError)?
+                    .try_into()?,
+            ),
+            budget_us: u32::from_le_bytes(
+                b.get(4..8)
+                    .ok_or(TbfParseError::InternalError)?
+                    .try_into()?,
+            ),
+        })
+    }
+}
+
+impl core::convert::TryFrom<&[u8]> for TbfHeaderV2KernelVersion {
+    type Error = TbfParseError;
+
+    fn try_from(b: &[u8]) -> Result<TbfHeaderV2KernelVersion, Self::Error> {
+        Ok(TbfHeaderV2KernelVersion {
+            min_major: u16::from_le_bytes(
+                b.get(0..2)
+                    .ok_or(TbfParseError::InternalError)?
+                    .try_into()?,
+            ),
+            min_minor: u16::from_le_bytes(
+                b.get(2..4)
+                    .ok_or(TbfParseError::InternalError)?
+                    .try_into()?,
+            ),
+            max_major: u16::from_le_bytes(
+                b.get(4..6)
+                    .ok_or(TbfParseError::InternalError)?
+                    .try_into()?,
+            ),
+            max_minor: u16::from_le_bytes(
+                b.get(6..8)
+                    .ok_or(TbfParseError::InternalError)?
+                    .try_into()?,
+            ),
+        })
+    }
+}
+
+impl core::convert::TryFrom<u32> for TbfHeaderV2CredentialsType {
+    type Error = TbfParseError;
+
+    fn try_from(format: u32) -> Result<TbfHeaderV2CredentialsType, Self::Error> {
+        match format {
+            1 => Ok(TbfHeaderV2CredentialsType::Sha256),
+            2 => Ok(TbfHeaderV2CredentialsType::HmacSha256),
+            3 => Ok(TbfHeaderV2CredentialsType::EcdsaNistP256),
+            4 => Ok(TbfHeaderV2CredentialsType::Ed25519),
+            _ => Err(TbfParseError::BadTlvEntry(
+                TbfHeaderTypes::TbfHeaderCredentials as usize,
+            )),
+        }
+    }
+}
+
+impl core::convert::TryFrom<&'static [u8]> for TbfHeaderV2Credentials {
+    type Error = TbfParseError;
+
+    fn try_from(b: &'static [u8]) -> Result<TbfHeaderV2Credentials, Self::Error> {
+        let format: TbfHeaderV2CredentialsType = u32::from_le_bytes(
+            b.get(0..4)
+                .ok_or(TbfParseError::NotEnoughFlash)?
+                .try_into()?,
+        )
+        .try_into()?;
+        let data = b.get(4..).ok_or(TbfParseError::NotEnoughFlash)?;
+
+        // The length must match the format exactly so that policies never see
+        // a truncated hash or signature.
+        if data.len() != format.data_length() {
+            return Err(TbfParseError::BadTlvEntry(
+                TbfHeaderTypes::TbfHeaderCredentials as usize,
+            ));
+        }
+        Ok(TbfHeaderV2Credentials {
+            format,
+            data,
+            tlv_offset: 0,
+            tlv_length: 0,
+        })
+    }
+}
+
 /// Single header that can contain all parts of a v2 header.
 ///
 /// Note, this struct limits the number of writeable regions an app can have to
-/// four since we need to statically know the length of the array to store in
-/// this type.
+/// four, and the number of driver permission entries to eight, since we need
+/// to statically know the length of the arrays to store in this type.
 #[derive(Clone, Copy, Debug)]
 pub(crate) struct TbfHeaderV2 {
     base: TbfHeaderV2Base,
@@ -320,6 +537,12 @@ pub(crate) struct TbfHeaderV2 {
     package_name: Option<&'static str>,
     writeable_regions: Option<[Option<TbfHeaderV2WriteableFlashRegion>; 4]>,
     fixed_addresses: Option<TbfHeaderV2FixedAddresses>,
+    permissions: Option<[Option<TbfHeaderV2DriverPermission>; 8]>,
+    kernel_version: Option<TbfHeaderV2KernelVersion>,
+    short_id: Option<u32>,
+    periodic: Option<TbfHeaderV2Periodic>,
+    priority: Option<u32>,
+    credentials: Option<TbfHeaderV2Credentials>,
 }
 
 /// Type that represents the fields of the Tock Binary Format header.
@@ -446,6 +669,109 @@ impl TbfHeader {
             start => Some(start),
         }
     }
+
+    /// Check whether the app is permitted to use a driver.
+    ///
+    /// If `command_number` is `None` only the driver itself is checked, which
+    /// is used for subscribe and allow. Otherwise the specific command must
+    /// also be permitted. Apps without a permissions entry in their header may
+    /// use every driver and command.
+    pub(crate) fn is_driver_permitted(
+        &self,
+        driver_number: usize,
+        command_number: Option<usize>,
+    ) -> bool {
+        let permissions = match self {
+            TbfHeader::TbfHeaderV2(hd) => match hd.permissions {
+                Some(permissions) => permissions,
+                None => return true,
+            },
+            _ => return true,
+        };
+
+        permissions
+            .iter()
+            .filter_map(|perm| perm.as_ref())
+            .any(|perm| {
+                if perm.driver_number as usize != driver_number {
+                    return false;
+                }
+                match command_number {
+                    None => true,
+                    Some(command_number) => {
+                        perm.offset as usize == command_number / 64
+                            && perm.allowed_commands & (1 << (command_number % 64)) != 0
+                    }
+                }
+            })
+    }
+
+    /// Get the minimum and maximum kernel ABI versions, as (major, minor)
+    /// pairs, the app was built to work with. Returns `None` if the app did not
+    /// specify a range.
+    pub(crate) fn get_kernel_version(&self) -> Option<((u16, u16), (u16, u16))> {
+        match self {
+            TbfHeader::TbfHeaderV2(hd) => hd
+                .kernel_version
+                .map(|kv| ((kv.min_major, kv.min_minor), (kv.max_major, kv.max_minor))),
+            _ => None,
+        }
+    }
+
+    /// Get the persistent short ID assigned to the app, if it has one.
+    pub(crate) fn get_short_id(&self) -> Option<u32> {
+        match self {
+            TbfHeader::TbfHeaderV2(hd) => hd.short_id,
+            _ => None,
+        }
+    }
+
+    /// Get the period and the execution budget per period, both in
+    /// microseconds, the app requested if it runs periodically.
+    pub(crate) fn get_periodic_parameters(&self) -> Option<(u32, u32)> {
+        match self {
+            TbfHeader::TbfHeaderV2(hd) => hd.periodic.map(|p| (p.period_us, p.budget_us)),
+            _ => None,
+        }
+    }
+
+    /// Get the scheduling priority the app requested, if any. Lower values
+    /// are higher priorities.
+    pub(crate) fn get_priority(&self) -> Option<u32> {
+        match self {
+            TbfHeader::TbfHeaderV2(hd) => hd.priority,
+            _ => None,
+        }
+    }
+
+    /// Get the cryptographic credentials included in the header, if any.
+    pub(crate) fn get_credentials(&self) -> Option<TbfHeaderV2Credentials> {
+        match self {
+            TbfHeader::TbfHeaderV2(hd) => hd.credentials,
+            _ => None,
+        }
+    }
+
+    /// Split the TBF object `tbf` this header was parsed from into the pieces
+    /// covered by the credentials, in order. These are all of its bytes except
+    /// the header checksum and the credentials TLV. Without credentials the
+    /// pieces still cover the same bytes, minus the checksum.
+    pub(crate) fn get_credentials_covered(&self, tbf: &'static [u8]) -> Option<[&'static [u8]; 4]> {
+        let hd = match self {
+            TbfHeader::TbfHeaderV2(hd) => hd,
+            _ => return None,
+        };
+        let header_size = hd.base.header_size as usize;
+        let (tlv_start, tlv_end) = hd.credentials.map_or((header_size, header_size), |c| {
+            (c.tlv_offset, c.tlv_offset + c.tlv_length)
+        });
+        Some([
+            tbf.get(0..12)?,
+            tbf.get(16..tlv_start)?,
+            tbf.get(tlv_end..header_size)?,
+            tbf.get(header_size..)?,
+        ])
+    }
 }
 
 /// Parse the TBF header length and the entire length of the TBF binary.
@@ -563,9 +889,18 @@ pub(crate) fn parse_tbf_header(
                     Default::default();
                 let mut app_name_str = "";
                 let mut fixed_address_pointer: Option<TbfHeaderV2FixedAddresses> = None;
+                let mut permissions_pointer: Option<[Option<TbfHeaderV2DriverPermission>; 8]> =
+                    None;
+                let mut kernel_version_pointer: Option<TbfHeaderV2KernelVersion> = None;
+                let mut short_id: Option<u32> = None;
+                let mut periodic_pointer: Option<TbfHeaderV2Periodic> = None;
+                let mut priority: Option<u32> = None;
+                let mut credentials_pointer: Option<TbfHeaderV2Credentials> = None;
 
                 // Iterate the remainder of the header looking for TLV entries.
                 while remaining.len() > 0 {
+                    let tlv_offset = header.len() - remaining.len();
+
                     // Get the T and L portions of the next header (if it is
                     // there).
                     let tlv_header: TbfHeaderTlv = remaining.try_into()?;
@@ -643,6 +978,105 @@ pub(crate) fn parse_tbf_header(
                             }
                         }
 
+                        TbfHeaderTypes::TbfHeaderPermissions => {
+                            let perm_len = mem::size_of::<TbfHeaderV2DriverPermission>();
+                            let number_perms = tlv_header.length as usize / perm_len;
+
+                            // Length must be a multiple of the size of a
+                            // permission entry. Unlike writeable flash regions
+                            // we cannot silently drop entries past the ones we
+                            // have room for, as that would deny the app
+                            // drivers it expects to use, so too many entries
+                            // is an error.
+                            if tlv_header.length as usize % perm_len != 0 || number_perms > 8 {
+                                return Err(TbfParseError::BadTlvEntry(tlv_header.tipe as usize));
+                            }
+
+                            let perm_slice = remaining
+                                .get(0..tlv_header.length as usize)
+                                .ok_or(TbfParseError::NotEnoughFlash)?;
+
+                            let mut perms: [Option<TbfHeaderV2DriverPermission>; 8] =
+                                Default::default();
+                            for i in 0..number_perms {
+                                perms[i] = Some(
+                                    perm_slice
+                                        .get(i * perm_len..(i + 1) * perm_len)
+                                        .ok_or(TbfParseError::NotEnoughFlash)?
+                                        .try_into()?,
+                                );
+                            }
+                            permissions_pointer = Some(perms);
+                        }
+
+                        TbfHeaderTypes::TbfHeaderKernelVersion => {
+                            let entry_len = mem::size_of::<TbfHeaderV2KernelVersion>();
+                            if tlv_header.length as usize == entry_len {
+                                kernel_version_pointer = Some(remaining.try_into()?);
+                            } else {
+                                return Err(TbfParseError::BadTlvEntry(tlv_header.tipe as usize));
+                            }
+                        }
+
+                        TbfHeaderTypes::TbfHeaderShortId => {
+                            // Short IDs with the high bit set are reserved for
+                            // IDs the kernel derives from the package name.
+                            let id = u32::from_le_bytes(
+                                remaining
+                                    .get(0..4)
+                                    .ok_or(TbfParseError::NotEnoughFlash)?
+                                    .try_into()?,
+                            );
+                            if tlv_header.length == 4 && id & 0x80000000 == 0 {
+                                short_id = Some(id);
+                            } else {
+                                return Err(TbfParseError::BadTlvEntry(tlv_header.tipe as usize));
+                            }
+                        }
+
+                        TbfHeaderTypes::TbfHeaderPeriodic => {
+                            // A budget larger than the period could never be
+                            // met.
+                            let entry_len = mem::size_of::<TbfHeaderV2Periodic>();
+                            if tlv_header.length as usize != entry_len {
+                                return Err(TbfParseError::BadTlvEntry(tlv_header.tipe as usize));
+                            }
+                            let periodic: TbfHeaderV2Periodic = remaining.try_into()?;
+                            if periodic.budget_us == 0 || periodic.budget_us > periodic.period_us {
+                                return Err(TbfParseError::BadTlvEntry(tlv_header.tipe as usize));
+                            }
+                            periodic_pointer = Some(periodic);
+                        }
+
+                        TbfHeaderTypes::TbfHeaderPriority => {
+                            if tlv_header.length != 4 {
+                                return Err(TbfParseError::BadTlvEntry(tlv_header.tipe as usize));
+                            }
+                            priority = Some(u32::from_le_bytes(
+                                remaining
+                                    .get(0..4)
+                                    .ok_or(TbfParseError::NotEnoughFlash)?
+                                    .try_into()?,
+                            ));
+                        }
+
+                        TbfHeaderTypes::TbfHeaderCredentials => {
+                            // The entry is a 4 byte format followed by the
+                            // credential itself. Only one entry is allowed,
+                            // as a second one would be covered by the first.
+                            if credentials_pointer.is_some() {
+                                return Err(TbfParseError::BadTlvEntry(tlv_header.tipe as usize));
+                            }
+                            let credentials_buf = remaining
+                                .get(0..tlv_header.length as usize)
+                                .ok_or(TbfParseError::NotEnoughFlash)?;
+                            let mut credentials: TbfHeaderV2Credentials =
+                                credentials_buf.try_into()?;
+                            credentials.tlv_offset = tlv_offset;
+                            credentials.tlv_length = 4 + align4!(tlv_header.length as usize);
+                            credentials_pointer = Some(credentials);
+                        }
+
                         _ => {}
                     }
 
@@ -660,6 +1094,12 @@ pub(crate) fn parse_tbf_header(
                     package_name: Some(app_name_str),
                     writeable_regions: Some(wfr_pointer),
                     fixed_addresses: fixed_address_pointer,
+                    permissions: permissions_pointer,
+                    kernel_version: kernel_version_pointer,
+                    short_id,
+                    periodic: periodic_pointer,
+                    priority,
+                    credentials: credentials_pointer,
                 };
 
                 Ok(TbfHeader::TbfHeaderV2(tbf_header))
@@ -668,3 +1108,167 @@ pub(crate) fn parse_tbf_header(
         _ => Err(TbfParseError::UnsupportedVersion(version)),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{parse_tbf_header, TbfHeader, TbfHeaderV2CredentialsType, TbfParseError};
+    use crate::test_util;
+
+    extern crate std;
+    use std::vec::Vec;
+
+    const PERMISSIONS: u16 = 6;
+    const CREDENTIALS: u16 = 128;
+
+    fn parse(tbf: &'static [u8]) -> Result<TbfHeader, TbfParseError> {
+        let header_size = u16::from_le_bytes([tbf[2], tbf[3]]) as usize;
+        parse_tbf_header(&tbf[..header_size], 2)
+    }
+
+    /// The value of a permissions TLV with one entry per `(driver_number,
+    /// offset, allowed_commands)`.
+    fn permissions(entries: &[(u32, u32, u64)]) -> Vec<u8> {
+        let mut value = Vec::new();
+        for (driver_number, offset, allowed_commands) in entries {
+            value.extend_from_slice(&driver_number.to_le_bytes());
+            value.extend_from_slice(&offset.to_le_bytes());
+            value.extend_from_slice(&allowed_commands.to_le_bytes());
+        }
+        value
+    }
+
+    /// The value of a credentials TLV.
+    fn credentials(format: u32, data: &[u8]) -> Vec<u8> {
+        let mut value = format.to_le_bytes().to_vec();
+        value.extend_from_slice(data);
+        value
+    }
+
+    fn is_bad_credentials(result: Result<TbfHeader, TbfParseError>) -> bool {
+        match result {
+            Err(TbfParseError::BadTlvEntry(tipe)) => tipe == CREDENTIALS as usize,
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn parses_credentials() {
+        let tbf = test_util::tbf_object_with(&[(CREDENTIALS, &credentials(1, &[7; 32]))]);
+        let header = parse(tbf).unwrap();
+        let credentials = header.get_credentials().unwrap();
+        assert_eq!(credentials.format(), TbfHeaderV2CredentialsType::Sha256);
+        assert_eq!(credentials.data(), &[7; 32]);
+
+        let tbf = test_util::tbf_object();
+        assert!(parse(tbf).unwrap().get_credentials().is_none());
+    }
+
+    #[test]
+    fn rejects_invalid_credentials() {
+        // Reserved and unknown formats.
+        for format in [0, 5].iter() {
+            let tbf = test_util::tbf_object_with(&[(CREDENTIALS, &credentials(*format, &[0; 32]))]);
+            assert!(is_bad_credentials(parse(tbf)));
+        }
+
+        // Data that does not match the length of the format.
+        let tbf = test_util::tbf_object_with(&[(CREDENTIALS, &credentials(1, &[0; 31]))]);
+        assert!(is_bad_credentials(parse(tbf)));
+        let tbf = test_util::tbf_object_with(&[(CREDENTIALS, &credentials(3, &[0; 32]))]);
+        assert!(is_bad_credentials(parse(tbf)));
+
+        // More than one credentials entry.
+        let value = credentials(1, &[0; 32]);
+        let tbf = test_util::tbf_object_with(&[(CREDENTIALS, &value), (CREDENTIALS, &value)]);
+        assert!(is_bad_credentials(parse(tbf)));
+    }
+
+    #[test]
+    fn credentials_cover_everything_but_checksum_and_credentials() {
+        let name = b"app";
+        let value = credentials(4, &[0; 64]);
+        let tbf = test_util::tbf_object_with(&[(3, name), (CREDENTIALS, &value), (3, name)]);
+        let header = parse(tbf).unwrap();
+        let covered: Vec<u8> = header
+            .get_credentials_covered(tbf)
+            .unwrap()
+            .iter()
+            .flat_map(|piece| piece.iter().cloned())
+            .collect();
+
+        // The credentials TLV follows the base header (16 bytes), the main
+        // TLV (16 bytes) and the first name TLV (8 bytes).
+        let tlv_start = 16 + 16 + 8;
+        let tlv_end = tlv_start + 4 + value.len();
+        let mut expected = tbf[..12].to_vec();
+        expected.extend_from_slice(&tbf[16..tlv_start]);
+        expected.extend_from_slice(&tbf[tlv_end..]);
+        assert_eq!(covered, expected);
+
+        // Without credentials only the checksum is left out.
+        let tbf = test_util::tbf_object_with(&[(3, name)]);
+        let header = parse(tbf).unwrap();
+        let covered: Vec<u8> = header
+            .get_credentials_covered(tbf)
+            .unwrap()
+            .iter()
+            .flat_map(|piece| piece.iter().cloned())
+            .collect();
+        let mut expected = tbf[..12].to_vec();
+        expected.extend_from_slice(&tbf[16..]);
+        assert_eq!(covered, expected);
+    }
+
+    #[test]
+    fn apps_without_permissions_may_use_every_driver() {
+        let header = parse(test_util::tbf_object()).unwrap();
+        assert!(header.is_driver_permitted(0, None));
+        assert!(header.is_driver_permitted(0x90000, Some(1000)));
+    }
+
+    #[test]
+    fn permissions_allow_listed_commands() {
+        let value = permissions(&[(1, 0, 0b101), (1, 1, 1 << 63), (2, 0, 0)]);
+        let header = parse(test_util::tbf_object_with(&[(PERMISSIONS, &value)])).unwrap();
+
+        // Subscribe and allow only need the driver to be listed.
+        assert!(header.is_driver_permitted(1, None));
+        assert!(header.is_driver_permitted(2, None));
+        assert!(!header.is_driver_permitted(3, None));
+
+        // Commands need their bit set in the entry for their block of 64.
+        assert!(header.is_driver_permitted(1, Some(0)));
+        assert!(!header.is_driver_permitted(1, Some(1)));
+        assert!(header.is_driver_permitted(1, Some(2)));
+        assert!(!header.is_driver_permitted(1, Some(64)));
+        assert!(header.is_driver_permitted(1, Some(127)));
+        assert!(!header.is_driver_permitted(1, Some(128)));
+        assert!(!header.is_driver_permitted(2, Some(0)));
+        assert!(!header.is_driver_permitted(3, Some(0)));
+
+        // An empty list denies everything.
+        let header = parse(test_util::tbf_object_with(&[(PERMISSIONS, &[])])).unwrap();
+        assert!(!header.is_driver_permitted(1, None));
+        assert!(!header.is_driver_permitted(1, Some(0)));
+    }
+
+    #[test]
+    fn rejects_invalid_permissions() {
+        let is_bad_permissions = |result| match result {
+            Err(TbfParseError::BadTlvEntry(tipe)) => tipe == PERMISSIONS as usize,
+            _ => false,
+        };
+
+        // Entries must be complete.
+        let value = permissions(&[(1, 0, 1)]);
+        let tbf = test_util::tbf_object_with(&[(PERMISSIONS, &value[..12])]);
+        assert!(is_bad_permissions(parse(tbf)));
+
+        // At most eight entries fit in the header.
+        let value = permissions(&[(1, 0, 1); 9]);
+        let tbf = test_util::tbf_object_with(&[(PERMISSIONS, &value)]);
+        assert!(is_bad_permissions(parse(tbf)));
+        let value = permissions(&[(1, 0, 1); 8]);
+        assert!(parse(test_util::tbf_object_with(&[(PERMISSIONS, &value)])).is_ok());
+    }
+}
@@ -0,0 +1,313 @@
+//! Helpers for unit tests that need a kernel with processes in it.
+//!
+//! The processes are real `Process` objects created from a minimal TBF header
+//! on a chip without memory protection. They are never switched to, so tests
+//! can use them to exercise everything the kernel does on behalf of a process
+//! except running it.
+
+extern crate std;
+
+use core::cell::Cell;
+use core::fmt::Write;
+use core::mem::size_of;
+use std::boxed::Box;
+use std::vec;
+
+use crate::capabilities::{MemoryAllocationCapability, ProcessManagementCapability};
+use crate::hil::time::{self, Frequency};
+use crate::platform::Chip;
+use crate::process::{
+    FaultResponse, FunctionCall, NoCredentialsCheck, Process, ProcessCredentialsPolicy,
+    ProcessLoadError, ProcessType, State, Task,
+};
+use crate::sched::Kernel;
+use crate::syscall::{ContextSwitchReason, SyscallReturn, UserspaceKernelBoundary};
+
+/// Bytes of RAM each test process gets.
+const PROCESS_MEMORY: usize = 16 * 1024;
+
+/// Minimum RAM the test processes ask for in their TBF header.
+const MINIMUM_RAM: u32 = 4096;
+
+pub(crate) struct TestCapability;
+unsafe impl MemoryAllocationCapability for TestCapability {}
+unsafe impl ProcessManagementCapability for TestCapability {}
+
+pub(crate) struct TestBoundary;
+
+impl UserspaceKernelBoundary for TestBoundary {
+    type StoredState = ();
+
+    unsafe fn initialize_process(
+        &self,
+        stack_pointer: *const usize,
+        _stack_size: usize,
+        _state: &mut (),
+    ) -> Result<*const usize, ()> {
+        Ok(stack_pointer)
+    }
+
+    unsafe fn set_syscall_return_value(&self, _: *const usize, _: &mut (), _: isize) {}
+
+    unsafe fn set_syscall_return(&self, _: *const usize, _: &mut (), _: SyscallReturn) {}
+
+    unsafe fn set_process_function(
+        &self,
+        stack_pointer: *const usize,
+        _remaining_stack_memory: usize,
+        _state: &mut (),
+        _callback: FunctionCall,
+    ) -> Result<*mut usize, *mut usize> {
+        Ok(stack_pointer as *mut usize)
+    }
+
+    unsafe fn switch_to_process(
+        &self,
+        _stack_pointer: *const usize,
+        _state: &mut (),
+    ) -> (*mut usize, ContextSwitchReason) {
+        panic!("test processes do not run");
+    }
+
+    unsafe fn print_context(&self, _: *const usize, _: &(), _: &mut dyn Write) {}
+}
+
+pub(crate) struct TestChip {
+    boundary: TestBoundary,
+}
+
+impl Chip for TestChip {
+    type MPU = ();
+    type UserspaceKernelBoundary = TestBoundary;
+    type SchedulerTimer = ();
+    type WatchDog = ();
+
+    fn service_pending_interrupts(&self) {}
+
+    fn has_pending_interrupts(&self) -> bool {
+        false
+    }
+
+    fn mpu(&self) -> &() {
+        &()
+    }
+
+    fn scheduler_timer(&self) -> &() {
+        &()
+    }
+
+    fn watchdog(&self) -> &() {
+        &()
+    }
+
+    fn userspace_kernel_boundary(&self) -> &TestBoundary {
+        &self.boundary
+    }
+
+    fn sleep(&self) {}
+
+    unsafe fn atomic<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        f()
+    }
+
+    unsafe fn print_state(&self, _: &mut dyn Write) {}
+}
+
+pub(crate) struct Freq1MHz;
+
+impl Frequency for Freq1MHz {
+    fn frequency() -> u32 {
+        1_000_000
+    }
+}
+
+/// An alarm whose time only moves when the test sets it. It never fires on
+/// its own; tests call the client's `fired()` themselves.
+pub(crate) struct TestAlarm {
+    pub(crate) now: Cell<u32>,
+    pub(crate) alarm: Cell<u32>,
+    pub(crate) enabled: Cell<bool>,
+}
+
+impl time::Time for TestAlarm {
+    type Frequency = Freq1MHz;
+
+    fn now(&self) -> u32 {
+        self.now.get()
+    }
+
+    fn max_tics(&self) -> u32 {
+        u32::MAX
+    }
+}
+
+impl<'a> time::Alarm<'a> for TestAlarm {
+    fn set_alarm(&self, tics: u32) {
+        self.alarm.set(tics);
+        self.enabled.set(true);
+    }
+
+    fn get_alarm(&self) -> u32 {
+        self.alarm.get()
+    }
+
+    fn set_client(&'a self, _client: &'a dyn time::AlarmClient) {}
+
+    fn is_enabled(&self) -> bool {
+        self.enabled.get()
+    }
+
+    fn disable(&self) {
+        self.enabled.set(false);
+    }
+}
+
+/// Create a test alarm whose time starts at 0.
+pub(crate) fn alarm() -> &'static TestAlarm {
+    Box::leak(Box::new(TestAlarm {
+        now: Cell::new(0),
+        alarm: Cell::new(0),
+        enabled: Cell::new(false),
+    }))
+}
+
+/// Create a kernel with room for `num_procs` processes.
+pub(crate) fn kernel(num_procs: usize) -> &'static Kernel {
+    let processes = Box::leak(vec![None; num_procs].into_boxed_slice());
+    Box::leak(Box::new(Kernel::new(processes)))
+}
+
+/// Bytes of app binary that follow the header in the test TBF objects.
+const BINARY_SIZE: usize = 32;
+
+/// A TBF object for an enabled app with a main TLV followed by `tlvs`, each
+/// given as a type and its value. The binary after the header is filled with
+/// a counting pattern.
+pub(crate) fn tbf_object_with(tlvs: &[(u16, &[u8])]) -> &'static [u8] {
+    let mut header = vec![0u8; 16];
+    let mut tlv = |tipe: u16, value: &[u8]| {
+        header.extend_from_slice(&tipe.to_le_bytes());
+        header.extend_from_slice(&(value.len() as u16).to_le_bytes());
+        header.extend_from_slice(value);
+        header.resize((header.len() + 3) / 4 * 4, 0);
+    };
+    let mut main = vec![];
+    main.extend_from_slice(&0u32.to_le_bytes()); // init function offset
+    main.extend_from_slice(&0u32.to_le_bytes()); // protected size
+    main.extend_from_slice(&MINIMUM_RAM.to_le_bytes());
+    tlv(1, &main);
+    for (tipe, value) in tlvs {
+        tlv(*tipe, value);
+    }
+
+    let header_size = header.len();
+    let total_size = header_size + BINARY_SIZE;
+    header[0..2].copy_from_slice(&2u16.to_le_bytes()); // version
+    header[2..4].copy_from_slice(&(header_size as u16).to_le_bytes());
+    header[4..8].copy_from_slice(&(total_size as u32).to_le_bytes());
+    header[8..12].copy_from_slice(&1u32.to_le_bytes()); // enabled
+    let checksum = header
+        .chunks_exact(4)
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        .fold(0, |sum, word| sum ^ word);
+    header[12..16].copy_from_slice(&checksum.to_le_bytes());
+
+    let mut tbf = header;
+    tbf.extend((0..BINARY_SIZE).map(|i| i as u8));
+    Box::leak(tbf.into_boxed_slice())
+}
+
+/// A TBF object for an enabled app with nothing but a main TLV.
+pub(crate) fn tbf_object() -> &'static [u8] {
+    tbf_object_with(&[])
+}
+
+/// Call `Process::create()` for the TBF object `flash` and slot `index` of
+/// `kernel`, and insert the process into the processes array if one was
+/// created. Any grants must be created before the first process.
+pub(crate) fn create(
+    kernel: &'static Kernel,
+    index: usize,
+    flash: &'static [u8],
+    credentials_policy: &'static dyn ProcessCredentialsPolicy,
+) -> Result<Option<&'static dyn ProcessType>, ProcessLoadError> {
+    create_with(
+        kernel,
+        index,
+        flash,
+        credentials_policy,
+        FaultResponse::Stop,
+    )
+}
+
+/// Like `create()`, but with `fault_response` instead of stopping the process
+/// when it faults.
+pub(crate) fn create_with(
+    kernel: &'static Kernel,
+    index: usize,
+    flash: &'static [u8],
+    credentials_policy: &'static dyn ProcessCredentialsPolicy,
+    fault_response: FaultResponse,
+) -> Result<Option<&'static dyn ProcessType>, ProcessLoadError> {
+    let chip: &'static TestChip = Box::leak(Box::new(TestChip {
+        boundary: TestBoundary,
+    }));
+    let words = vec![0usize; PROCESS_MEMORY / size_of::<usize>()].into_boxed_slice();
+    let memory = Box::leak(words);
+    let memory =
+        unsafe { core::slice::from_raw_parts_mut(memory.as_mut_ptr() as *mut u8, PROCESS_MEMORY) };
+    let header_length = u16::from_le_bytes([flash[2], flash[3]]) as usize;
+
+    let (process, _) = unsafe {
+        Process::create(
+            kernel,
+            chip,
+            flash,
+            header_length,
+            2,
+            memory,
+            fault_response,
+            credentials_policy,
+            index,
+        )?
+    };
+    if let Some(process) = process {
+        assert!(kernel.insert_process(index, process));
+    }
+    Ok(process)
+}
+
+/// Create a process named `process<index>` in slot `index` of the processes
+/// array of `kernel`. Any grants must be created before the first process.
+pub(crate) fn process(kernel: &'static Kernel, index: usize) -> &'static dyn ProcessType {
+    process_with(kernel, index, FaultResponse::Stop)
+}
+
+/// Like `process()`, but with `fault_response` instead of stopping the
+/// process when it faults.
+pub(crate) fn process_with(
+    kernel: &'static Kernel,
+    index: usize,
+    fault_response: FaultResponse,
+) -> &'static dyn ProcessType {
+    let name = std::format!("process{}", index);
+    let tbf = tbf_object_with(&[(3, name.as_bytes())]);
+    match create_with(kernel, index, tbf, &NoCredentialsCheck {}, fault_response) {
+        Ok(Some(process)) => process,
+        _ => panic!("could not create the test process"),
+    }
+}
+
+/// Switch `process` to its queued entry point so that it is `Running`.
+pub(crate) fn run(process: &dyn ProcessType) {
+    match process.dequeue_task() {
+        Some(Task::FunctionCall(function_call)) => unsafe {
+            process.set_process_function(function_call)
+        },
+        _ => panic!("the process has no entry point queued"),
+    }
+    assert!(process.get_state() == State::Running);
+}
@@ -0,0 +1,356 @@
+//! Converts a process core dump written by `capsules::process_core_dump` into
+//! an ELF core file that GDB can load together with the app's ELF.
+//!
+//! The dump has to be read off the board first, for example with
+//! `tockloader read`. See `capsules/src/process_core_dump.rs` for the format.
+
+use std::convert::TryInto;
+use std::fs;
+use std::process;
+
+const MAGIC: u32 = 0x4443_4B54; // "TKCD"
+const FORMAT_VERSION: u32 = 1;
+const HEADER_LEN: usize = 76;
+
+const FLAG_FAULT_ADDRESS_VALID: u32 = 0x1;
+const FLAG_MEMORY_TRUNCATED: u32 = 0x2;
+const FLAG_KERNEL_FAULT: u32 = 0x4;
+
+/// Architectures we know how to turn into a core file, identified by the
+/// number of registers in the dump.
+#[derive(Clone, Copy, PartialEq)]
+enum Arch {
+    /// R0-R12, SP, LR, PC, xPSR.
+    CortexM,
+    /// x1-x31, PC.
+    Rv32i,
+}
+
+impl Arch {
+    fn from_num_registers(num_registers: usize) -> Option<Arch> {
+        match num_registers {
+            17 => Some(Arch::CortexM),
+            32 => Some(Arch::Rv32i),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Arch::CortexM => "Cortex-M",
+            Arch::Rv32i => "RV32I",
+        }
+    }
+
+    fn register_names(self) -> Vec<String> {
+        match self {
+            Arch::CortexM => (0..13)
+                .map(|i| format!("r{}", i))
+                .chain(["sp", "lr", "pc", "xpsr"].iter().map(|s| s.to_string()))
+                .collect(),
+            Arch::Rv32i => (1..32)
+                .map(|i| format!("x{}", i))
+                .chain(std::iter::once("pc".to_string()))
+                .collect(),
+        }
+    }
+
+    fn elf_machine(self) -> u16 {
+        match self {
+            Arch::CortexM => 40, // EM_ARM
+            Arch::Rv32i => 243,  // EM_RISCV
+        }
+    }
+
+    fn elf_flags(self) -> u32 {
+        match self {
+            Arch::CortexM => 0x0500_0000, // EF_ARM_EABI_VER5
+            Arch::Rv32i => 0,
+        }
+    }
+
+    /// The general purpose registers in the order of the Linux
+    /// `elf_gregset_t` for the architecture, which is what GDB expects in a
+    /// core file.
+    fn gregset(self, registers: &[u32]) -> Vec<u32> {
+        match self {
+            Arch::CortexM => {
+                // r0-r15, cpsr, orig_r0. GDB decides whether code is Thumb
+                // from the T bit of the CPSR, which is at a different position
+                // in the xPSR.
+                let xpsr = registers[16];
+                let cpsr = (xpsr & 0xF800_0000) | if xpsr & (1 << 24) != 0 { 0x20 } else { 0 };
+                let mut gregs = registers[..16].to_vec();
+                gregs.push(cpsr);
+                gregs.push(registers[0]);
+                gregs
+            }
+            Arch::Rv32i => {
+                // pc, x1-x31.
+                let mut gregs = vec![registers[31]];
+                gregs.extend_from_slice(&registers[..31]);
+                gregs
+            }
+        }
+    }
+}
+
+struct CoreDump {
+    flags: u32,
+    short_id: u32,
+    restart_count: u32,
+    fault_reason: u32,
+    fault_address: u32,
+    flash_start: u32,
+    flash_app_start: u32,
+    flash_end: u32,
+    memory_start: u32,
+    memory_end: u32,
+    app_break: u32,
+    kernel_memory_break: u32,
+    stack_pointer: u32,
+    registers: Vec<u32>,
+    name: String,
+    memory: Vec<u8>,
+}
+
+fn truncated() -> String {
+    "Dump is truncated".to_string()
+}
+
+/// `length` bytes of `data` from `offset`, or an error if the dump is too
+/// short. The offset and length come from the dump, so they may be garbage.
+fn bytes(data: &[u8], offset: usize, length: usize) -> Result<&[u8], String> {
+    offset
+        .checked_add(length)
+        .and_then(|end| data.get(offset..end))
+        .ok_or_else(truncated)
+}
+
+fn word(data: &[u8], offset: usize) -> Result<u32, String> {
+    bytes(data, offset, 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+}
+
+impl CoreDump {
+    fn parse(data: &[u8]) -> Result<CoreDump, String> {
+        let w = |i: usize| word(data, 4 * i);
+
+        if w(0)? != MAGIC {
+            return Err("No complete core dump found (bad magic)".to_string());
+        }
+        if w(1)? != FORMAT_VERSION {
+            return Err(format!("Unsupported core dump version {}", w(1)?));
+        }
+        let length = w(2)? as usize;
+        let data = bytes(data, 0, length)?;
+
+        let num_registers = w(16)? as usize;
+        let name_length = w(17)? as usize;
+        let memory_length = w(18)? as usize;
+        let registers_length = num_registers.checked_mul(4).ok_or_else(truncated)?;
+        let registers = bytes(data, HEADER_LEN, registers_length)?
+            .chunks(4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        let name_offset = HEADER_LEN + registers_length;
+        let name = bytes(data, name_offset, name_length)
+            .map(|name| String::from_utf8_lossy(name).into_owned())?;
+        let memory_offset = name_length
+            .checked_add(3)
+            .and_then(|padded| name_offset.checked_add(padded & !3))
+            .ok_or_else(truncated)?;
+        let memory = bytes(data, memory_offset, memory_length)?.to_vec();
+
+        Ok(CoreDump {
+            flags: w(3)?,
+            short_id: w(4)?,
+            restart_count: w(5)?,
+            fault_reason: w(6)?,
+            fault_address: w(7)?,
+            flash_start: w(8)?,
+            flash_app_start: w(9)?,
+            flash_end: w(10)?,
+            memory_start: w(11)?,
+            memory_end: w(12)?,
+            app_break: w(13)?,
+            kernel_memory_break: w(14)?,
+            stack_pointer: w(15)?,
+            registers,
+            name,
+            memory,
+        })
+    }
+
+    fn print(&self, arch: Option<Arch>) {
+        println!("Process:        {}", self.name);
+        println!("Short ID:       {:#010x}", self.short_id);
+        println!("Restart count:  {}", self.restart_count);
+        println!(
+            "Architecture:   {}",
+            arch.map_or("unknown", |arch| arch.name())
+        );
+        if self.flags & FLAG_KERNEL_FAULT != 0 {
+            println!("Fault reason:   faulted by the kernel");
+        } else {
+            println!("Fault reason:   {:#010x}", self.fault_reason);
+        }
+        if self.flags & FLAG_FAULT_ADDRESS_VALID != 0 {
+            println!("Fault address:  {:#010x}", self.fault_address);
+        }
+        println!();
+        println!(
+            "Flash:          {:#010x}-{:#010x} (app code from {:#010x})",
+            self.flash_start, self.flash_end, self.flash_app_start
+        );
+        println!(
+            "RAM:            {:#010x}-{:#010x}",
+            self.memory_start, self.memory_end
+        );
+        println!("App break:      {:#010x}", self.app_break);
+        println!("Kernel break:   {:#010x}", self.kernel_memory_break);
+        println!("Stack pointer:  {:#010x}", self.stack_pointer);
+        print!(
+            "RAM in dump:    {:#010x}-{:#010x}",
+            self.memory_start,
+            self.memory_start as usize + self.memory.len()
+        );
+        if self.flags & FLAG_MEMORY_TRUNCATED != 0 {
+            print!(" (truncated)");
+        }
+        println!();
+        println!();
+
+        let names = match arch {
+            Some(arch) => arch.register_names(),
+            None => (0..self.registers.len())
+                .map(|i| format!("reg{}", i))
+                .collect(),
+        };
+        for (name, value) in names.iter().zip(self.registers.iter()) {
+            println!("{:>6}: {:#010x}", name, value);
+        }
+    }
+
+    /// Build an ELF core file with the registers in a `NT_PRSTATUS` note and
+    /// the RAM in a loadable segment.
+    fn to_elf_core(&self, arch: Arch) -> Vec<u8> {
+        const ELF_HEADER_LEN: usize = 52;
+        const PROGRAM_HEADER_LEN: usize = 32;
+        const PRSTATUS_REG_OFFSET: usize = 72;
+        const SIGSEGV: u16 = 11;
+
+        // struct elf_prstatus for 32-bit targets: the general purpose
+        // registers at offset 72, followed by pr_fpvalid.
+        let gregs = arch.gregset(&self.registers);
+        let mut prstatus = vec![0u8; PRSTATUS_REG_OFFSET + 4 * gregs.len() + 4];
+        prstatus[12..14].copy_from_slice(&SIGSEGV.to_le_bytes());
+        for (i, reg) in gregs.iter().enumerate() {
+            let offset = PRSTATUS_REG_OFFSET + 4 * i;
+            prstatus[offset..offset + 4].copy_from_slice(&reg.to_le_bytes());
+        }
+
+        let mut note = Vec::new();
+        note.extend_from_slice(&5u32.to_le_bytes()); // namesz
+        note.extend_from_slice(&(prstatus.len() as u32).to_le_bytes()); // descsz
+        note.extend_from_slice(&1u32.to_le_bytes()); // NT_PRSTATUS
+        note.extend_from_slice(b"CORE\0\0\0\0");
+        note.extend_from_slice(&prstatus);
+
+        let note_offset = ELF_HEADER_LEN + 2 * PROGRAM_HEADER_LEN;
+        let memory_offset = note_offset + note.len();
+
+        let mut elf = Vec::new();
+        // ELF header.
+        elf.extend_from_slice(&[0x7f, b'E', b'L', b'F', 1, 1, 1, 0]);
+        elf.extend_from_slice(&[0; 8]);
+        elf.extend_from_slice(&4u16.to_le_bytes()); // ET_CORE
+        elf.extend_from_slice(&arch.elf_machine().to_le_bytes());
+        elf.extend_from_slice(&1u32.to_le_bytes()); // EV_CURRENT
+        elf.extend_from_slice(&0u32.to_le_bytes()); // e_entry
+        elf.extend_from_slice(&(ELF_HEADER_LEN as u32).to_le_bytes()); // e_phoff
+        elf.extend_from_slice(&0u32.to_le_bytes()); // e_shoff
+        elf.extend_from_slice(&arch.elf_flags().to_le_bytes());
+        elf.extend_from_slice(&(ELF_HEADER_LEN as u16).to_le_bytes());
+        elf.extend_from_slice(&(PROGRAM_HEADER_LEN as u16).to_le_bytes());
+        elf.extend_from_slice(&2u16.to_le_bytes()); // e_phnum
+        elf.extend_from_slice(&40u16.to_le_bytes()); // e_shentsize
+        elf.extend_from_slice(&0u16.to_le_bytes()); // e_shnum
+        elf.extend_from_slice(&0u16.to_le_bytes()); // e_shstrndx
+
+        // PT_NOTE
+        for value in &[4, note_offset as u32, 0, 0, note.len() as u32, 0, 0, 4] {
+            elf.extend_from_slice(&u32::to_le_bytes(*value));
+        }
+        // PT_LOAD, read and write
+        let memory_length = self.memory.len() as u32;
+        for value in &[
+            1,
+            memory_offset as u32,
+            self.memory_start,
+            self.memory_start,
+            memory_length,
+            memory_length,
+            6,
+            4,
+        ] {
+            elf.extend_from_slice(&u32::to_le_bytes(*value));
+        }
+
+        elf.extend_from_slice(&note);
+        elf.extend_from_slice(&self.memory);
+        elf
+    }
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "Usage: coredump <DUMP> [<CORE>]
+Print the contents of a process core dump, and convert it to an ELF core
+file CORE if given.
+
+DUMP is the raw contents of the core dump region, as written by the
+process_core_dump capsule.
+
+Examples:
+  coredump dump.bin            Print the registers and memory layout
+  coredump dump.bin app.core   Also write a core file for GDB"
+    );
+    process::exit(1);
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 || args.len() > 3 {
+        usage();
+    }
+
+    let data = fs::read(&args[1]).unwrap_or_else(|err| {
+        eprintln!("Unable to read {}: {}", args[1], err);
+        process::exit(1);
+    });
+    let dump = CoreDump::parse(&data).unwrap_or_else(|err| {
+        eprintln!("{}: {}", args[1], err);
+        process::exit(1);
+    });
+    let arch = Arch::from_num_registers(dump.registers.len());
+    dump.print(arch);
+
+    if let Some(core) = args.get(2) {
+        let arch = arch.unwrap_or_else(|| {
+            eprintln!("Unknown architecture, unable to write a core file");
+            process::exit(1);
+        });
+        fs::write(core, dump.to_elf_core(arch)).unwrap_or_else(|err| {
+            eprintln!("Unable to write {}: {}", core, err);
+            process::exit(1);
+        });
+        println!();
+        println!("Wrote {}. Load it in GDB with the app's ELF:", core);
+        println!("  gdb <app>.elf {}", core);
+        println!(
+            "If the app is position independent, relocate its symbols to where it ran, with the \
+             .text section at {:#010x}.",
+            dump.flash_app_start
+        );
+    }
+}
@@ -0,0 +1,384 @@
+//! Decodes a binary syscall trace, as printed by the process console's `trace`
+//! command, into a readable timeline.
+//!
+//! The input is the console output, with any other lines mixed in. Lines of
+//! the form `trace clock <hz>` set the frequency of the timestamps, and lines
+//! of the form `trace <hex>` hold one record each. See
+//! `kernel/src/syscall_trace.rs` for the record format.
+
+use std::convert::TryInto;
+use std::fs;
+use std::io::{self, Read};
+use std::process;
+
+const RECORD_LEN: usize = 28;
+
+const KIND_RECORDS_DROPPED: u8 = 0xff;
+const FLAG_NOT_DISPATCHED: u8 = 0x1;
+
+struct Record {
+    timestamp: u32,
+    app_id: u16,
+    kind: u8,
+    flags: u8,
+    driver: u32,
+    subdriver: u32,
+    arg0: u32,
+    arg1: u32,
+    return_value: i32,
+}
+
+impl Record {
+    fn parse(hex: &str) -> Option<Record> {
+        if hex.len() != 2 * RECORD_LEN {
+            return None;
+        }
+        let bytes = (0..RECORD_LEN)
+            .map(|i| u8::from_str_radix(hex.get(2 * i..2 * i + 2)?, 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+        let word =
+            |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        Some(Record {
+            timestamp: word(0),
+            app_id: u16::from_le_bytes([bytes[4], bytes[5]]),
+            kind: bytes[6],
+            flags: bytes[7],
+            driver: word(8),
+            subdriver: word(12),
+            arg0: word(16),
+            arg1: word(20),
+            return_value: word(24) as i32,
+        })
+    }
+
+    fn describe(&self) -> String {
+        let call = match self.kind {
+            0 => "yield()".to_string(),
+            1 => format!(
+                "subscribe({:#x}, {}, @{:#x}, {:#x})",
+                self.driver, self.subdriver, self.arg0, self.arg1
+            ),
+            2 => format!(
+                "command({:#x}, {}, {:#x}, {:#x})",
+                self.driver, self.subdriver, self.arg0, self.arg1
+            ),
+            3 => format!(
+                "allow({:#x}, {}, @{:#x}, {})",
+                self.driver, self.subdriver, self.arg0, self.arg1
+            ),
+            4 => format!("memop({}, {:#x})", self.driver, self.arg0),
+            5 => format!("exit({}, {:#x})", self.driver, self.arg0),
+            6 => format!(
+                "readonly_allow({:#x}, {}, @{:#x}, {})",
+                self.driver, self.subdriver, self.arg0, self.arg1
+            ),
+            7 => format!(
+                "command_wait({:#x}, {}, {}, {:#x}, {:#x})",
+                self.driver,
+                self.subdriver & 0xffff,
+                self.subdriver >> 16,
+                self.arg0,
+                self.arg1
+            ),
+            kind => format!("unknown syscall {}", kind),
+        };
+        let mut description = if self.kind == 0 {
+            call
+        } else {
+            format!("{} = {}", call, return_code(self.return_value))
+        };
+        if self.flags & FLAG_NOT_DISPATCHED != 0 {
+            description.push_str(" [not dispatched]");
+        }
+        description
+    }
+}
+
+/// Formats a return value like the kernel's `ReturnCode`.
+fn return_code(value: i32) -> String {
+    let name = match value {
+        0 => "SUCCESS",
+        -1 => "FAIL",
+        -2 => "EBUSY",
+        -3 => "EALREADY",
+        -4 => "EOFF",
+        -5 => "ERESERVE",
+        -6 => "EINVAL",
+        -7 => "ESIZE",
+        -8 => "ECANCEL",
+        -9 => "ENOMEM",
+        -10 => "ENOSUPPORT",
+        -11 => "ENODEVICE",
+        -12 => "EUNINSTALLED",
+        -13 => "ENOACK",
+        value => return format!("{:#x}", value),
+    };
+    name.to_string()
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "Usage: syscall_trace [<FILE>]
+Decode the syscall trace in FILE, or standard input if no FILE is given.
+
+FILE is the output of one or more `trace` commands of the process console,
+for example as saved from `tockloader listen`. Other lines are ignored.
+
+Examples:
+  syscall_trace console.log"
+    );
+    process::exit(1);
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let input = match args.len() {
+        1 => {
+            let mut input = String::new();
+            io::stdin()
+                .read_to_string(&mut input)
+                .unwrap_or_else(|err| {
+                    eprintln!("Unable to read standard input: {}", err);
+                    process::exit(1);
+                });
+            input
+        }
+        2 => fs::read_to_string(&args[1]).unwrap_or_else(|err| {
+            eprintln!("Unable to read {}: {}", args[1], err);
+            process::exit(1);
+        }),
+        _ => usage(),
+    };
+
+    let mut frequency: Option<u32> = None;
+    // Timestamps are from a 32-bit clock that wraps around, so count the
+    // wraps to keep the timeline increasing.
+    let mut start: Option<u64> = None;
+    let mut last_timestamp = 0;
+    let mut wraps = 0u64;
+
+    println!("{:>12}  {:>4}  Syscall", "Time", "App");
+    for line in input.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields.as_slice() {
+            ["trace", "clock", hz] => frequency = hz.parse().ok().filter(|&hz| hz > 0),
+            ["trace", hex] => {
+                let record = match Record::parse(hex) {
+                    Some(record) => record,
+                    None => {
+                        eprintln!("Ignoring malformed record: {}", hex);
+                        continue;
+                    }
+                };
+
+                if record.kind == KIND_RECORDS_DROPPED {
+                    println!("{:>12}  {:>4}  ({} records dropped)", "", "", record.arg0);
+                    continue;
+                }
+
+                if start.is_some() && record.timestamp < last_timestamp {
+                    wraps += 1;
+                }
+                last_timestamp = record.timestamp;
+                let ticks = (wraps << 32) + record.timestamp as u64;
+                let elapsed = ticks - *start.get_or_insert(ticks);
+                let time = match frequency {
+                    Some(hz) => format!("{:.6}s", elapsed as f64 / hz as f64),
+                    None => format!("{}", elapsed),
+                };
+
+                println!("{:>12}  {:>4}  {}", time, record.app_id, record.describe());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Decodes records encoded by the kernel's `SyscallTraceBuffer`, so that the
+/// two cannot drift apart.
+#[cfg(test)]
+mod test {
+    use super::{Record, KIND_RECORDS_DROPPED, RECORD_LEN};
+    use kernel::capabilities::ExternalProcessCapability;
+    use kernel::hil::time::{Freq16KHz, Time};
+    use kernel::syscall::Syscall;
+    use kernel::{AppId, Kernel, SyscallTraceBuffer, SyscallTracer, SYSCALL_TRACE_RECORD_LEN};
+    use std::cell::Cell;
+    use std::fmt::Write;
+
+    struct TestCapability;
+    unsafe impl ExternalProcessCapability for TestCapability {}
+
+    struct TestTime {
+        now: Cell<u32>,
+    }
+
+    impl Time for TestTime {
+        type Frequency = Freq16KHz;
+
+        fn now(&self) -> u32 {
+            self.now.get()
+        }
+
+        fn max_tics(&self) -> u32 {
+            u32::MAX
+        }
+    }
+
+    fn tracer(
+        num_records: usize,
+    ) -> (
+        &'static TestTime,
+        &'static SyscallTraceBuffer<'static, TestTime>,
+    ) {
+        let time = Box::leak(Box::new(TestTime { now: Cell::new(0) }));
+        let records = vec![[0; SYSCALL_TRACE_RECORD_LEN]; num_records];
+        let tracer = Box::leak(Box::new(SyscallTraceBuffer::new(
+            time,
+            Box::leak(records.into_boxed_slice()),
+        )));
+        (time, tracer)
+    }
+
+    fn appid(identifier: usize) -> AppId {
+        let kernel = Box::leak(Box::new(Kernel::new(Box::leak(Box::new([None])))));
+        AppId::new_external(kernel, identifier, 0, &TestCapability)
+    }
+
+    /// Drain `tracer` and decode the records the way the tool reads them
+    /// from the process console.
+    fn drain(tracer: &dyn SyscallTracer) -> Vec<Record> {
+        let mut buffer = [0; 16 * SYSCALL_TRACE_RECORD_LEN];
+        let len = tracer.drain(&mut buffer);
+        buffer[..len]
+            .chunks(SYSCALL_TRACE_RECORD_LEN)
+            .map(|record| {
+                let mut hex = String::new();
+                for byte in record {
+                    write!(hex, "{:02x}", byte).unwrap();
+                }
+                Record::parse(&hex).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn record_length_matches_the_kernel() {
+        assert_eq!(RECORD_LEN, SYSCALL_TRACE_RECORD_LEN);
+    }
+
+    #[test]
+    fn decodes_every_syscall_the_kernel_records() {
+        let (time, tracer) = tracer(16);
+        let appid = appid(7);
+        let calls = [
+            (Syscall::YIELD, 0, "yield()"),
+            (
+                Syscall::SUBSCRIBE {
+                    driver_number: 0x40001,
+                    subdriver_number: 2,
+                    callback_ptr: 0x2000_0100 as *mut (),
+                    appdata: 0x55,
+                },
+                0,
+                "subscribe(0x40001, 2, @0x20000100, 0x55) = SUCCESS",
+            ),
+            (
+                Syscall::COMMAND {
+                    driver_number: 0x1,
+                    subdriver_number: 3,
+                    arg0: 0x10,
+                    arg1: 0x20,
+                },
+                -6,
+                "command(0x1, 3, 0x10, 0x20) = EINVAL",
+            ),
+            (
+                Syscall::ALLOW {
+                    driver_number: 0x2,
+                    subdriver_number: 1,
+                    allow_address: 0x2000_0200 as *mut u8,
+                    allow_size: 64,
+                },
+                0,
+                "allow(0x2, 1, @0x20000200, 64) = SUCCESS",
+            ),
+            (
+                Syscall::MEMOP {
+                    operand: 1,
+                    arg0: 0x400,
+                },
+                0x2000_1000,
+                "memop(1, 0x400) = 0x20001000",
+            ),
+            (
+                Syscall::EXIT {
+                    which: 1,
+                    completion_code: 0x3,
+                },
+                0,
+                "exit(1, 0x3) = SUCCESS",
+            ),
+            (
+                Syscall::READONLY_ALLOW {
+                    driver_number: 0x2,
+                    subdriver_number: 4,
+                    allow_address: 0x300 as *const u8,
+                    allow_size: 8,
+                },
+                -10,
+                "readonly_allow(0x2, 4, @0x300, 8) = ENOSUPPORT",
+            ),
+            (
+                Syscall::COMMAND_WAIT {
+                    driver_number: 0x3,
+                    subdriver_number: 5,
+                    upcall_number: 6,
+                    arg0: 0x7,
+                    arg1: 0x8,
+                },
+                0,
+                "command_wait(0x3, 5, 6, 0x7, 0x8) = SUCCESS",
+            ),
+        ];
+        for (i, (syscall, return_value, _)) in calls.iter().enumerate() {
+            time.now.set(100 * i as u32);
+            tracer.record(appid, syscall, *return_value, true);
+        }
+
+        let records = drain(tracer);
+        assert_eq!(records.len(), calls.len());
+        for (i, (record, (_, _, description))) in records.iter().zip(calls.iter()).enumerate() {
+            assert_eq!(record.timestamp, 100 * i as u32);
+            assert_eq!(record.app_id, 7);
+            assert_eq!(record.describe(), *description);
+        }
+    }
+
+    #[test]
+    fn decodes_filtered_syscalls_and_dropped_records() {
+        // The buffer holds two records.
+        let (_, tracer) = tracer(3);
+        let appid = appid(1);
+        let command = Syscall::COMMAND {
+            driver_number: 0x1,
+            subdriver_number: 0,
+            arg0: 0,
+            arg1: 0,
+        };
+        tracer.record(appid, &command, -10, false);
+        for _ in 0..3 {
+            tracer.record(appid, &command, 0, true);
+        }
+        assert_eq!(
+            drain(tracer)[0].describe(),
+            "command(0x1, 0, 0x0, 0x0) = ENOSUPPORT [not dispatched]"
+        );
+
+        tracer.record(appid, &command, 0, true);
+        let records = drain(tracer);
+        assert_eq!(records[0].kind, KIND_RECORDS_DROPPED);
+        assert_eq!(records[0].arg0, 2);
+        assert_eq!(records[1].describe(), "command(0x1, 0, 0x0, 0x0) = SUCCESS");
+    }
+}